@@ -8,7 +8,8 @@ use std::collections::HashMap;
 use std::env;
 
 use starlight::{
-    EntropyParams, PreCheckParams, PreCheckResponse, Sentinel, SentinelConfig, SentinelHandler,
+    CancelToken, EntropyParams, PreCheckParams, PreCheckResponse, Sentinel, SentinelConfig,
+    SentinelHandler,
 };
 use tracing::{info, warn};
 
@@ -23,7 +24,7 @@ impl JanitorHandler {
 
 #[async_trait::async_trait]
 impl SentinelHandler for JanitorHandler {
-    async fn on_pre_check(&self, params: PreCheckParams) -> PreCheckResponse {
+    async fn on_pre_check(&self, params: PreCheckParams, _cancel: CancelToken) -> PreCheckResponse {
         info!(
             "Pre-check received - command: {}, selector: {:?}",
             params.command, params.selector
@@ -41,6 +42,8 @@ impl SentinelHandler for JanitorHandler {
 
             return PreCheckResponse::Hijack {
                 reason: format!("Detected {} blocking elements", params.blocking.len()),
+                confidence: None,
+                targets: selectors.iter().map(|s| s.to_string()).collect(),
             };
         }
 
@@ -108,10 +111,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Connect to Hub
     sentinel.connect(&hub_url).await?;
 
-    // Run message loop (blocks until stopped)
+    // Run message loop (blocks until stopped or interrupted)
     info!("Sentinel running. Press Ctrl+C to stop.");
 
-    sentinel.run().await?;
+    sentinel.run_until_signal().await?;
 
     info!("JanitorSentinel stopped");
     Ok(())
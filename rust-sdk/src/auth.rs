@@ -1,10 +1,23 @@
 //! JWT authentication for Starlight Protocol.
 
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData,
+    Validation,
+};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
 
-use crate::error::Result;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{Error, Result};
+
+/// Predicate returning `true` for tokens that must be rejected.
+pub type RevocationCheck<C = Claims> = Arc<dyn Fn(&C) -> bool + Send + Sync>;
 
 /// JWT claims for Starlight authentication.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,16 +35,41 @@ pub struct Claims {
     #[serde(default)]
     pub iss: Option<String>,
 
+    /// Unique token id, for revoking individual tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+
     /// Additional claims
     #[serde(flatten)]
     pub extra: std::collections::HashMap<String, serde_json::Value>,
 }
 
 /// JWT handler for generating and validating tokens.
-#[derive(Clone)]
-pub struct JwtHandler {
+///
+/// Tokens carry [`Claims`] unless another claims type is chosen with
+/// [`new_with_claims`](Self::new_with_claims).
+pub struct JwtHandler<C = Claims> {
     secret: String,
+    verification_keys: Vec<String>,
     expires_in_seconds: i64,
+    revocation_check: Option<RevocationCheck<C>>,
+    clock: Arc<dyn Clock>,
+    max_iat_skew: Option<Duration>,
+    _claims: PhantomData<fn() -> C>,
+}
+
+impl<C> Clone for JwtHandler<C> {
+    fn clone(&self) -> Self {
+        Self {
+            secret: self.secret.clone(),
+            verification_keys: self.verification_keys.clone(),
+            expires_in_seconds: self.expires_in_seconds,
+            revocation_check: self.revocation_check.clone(),
+            clock: Arc::clone(&self.clock),
+            max_iat_skew: self.max_iat_skew,
+            _claims: PhantomData,
+        }
+    }
 }
 
 impl JwtHandler {
@@ -47,10 +85,7 @@ impl JwtHandler {
     /// let handler = JwtHandler::new("my-super-secret-key-at-least-32-chars");
     /// ```
     pub fn new(secret: impl Into<String>) -> Self {
-        Self {
-            secret: secret.into(),
-            expires_in_seconds: 3600, // 1 hour default
-        }
+        Self::new_with_claims(secret)
     }
 
     /// Set token expiration time in seconds.
@@ -76,20 +111,143 @@ impl JwtHandler {
     /// ```
     #[allow(clippy::result_large_err)]
     pub fn generate_token(&self, subject: impl Into<String>) -> Result<String> {
-        let now = Utc::now();
-        let exp = now + Duration::seconds(self.expires_in_seconds);
+        let now = self.clock.now();
+        let exp = now + self.expires_in_seconds;
 
         let claims = Claims {
             sub: subject.into(),
-            iat: now.timestamp(),
-            exp: exp.timestamp(),
+            iat: now,
+            exp,
             iss: Some("starlight-rust-sdk".to_string()),
+            jti: Some(Uuid::new_v4().to_string()),
             extra: std::collections::HashMap::new(),
         };
 
+        self.encode_claims(&claims)
+    }
+
+    /// Refresh a token by generating a new one with the same subject.
+    ///
+    /// # Arguments
+    /// * `token` - The existing token to refresh
+    ///
+    /// # Returns
+    /// A new token with extended expiration
+    #[allow(clippy::result_large_err)]
+    pub fn refresh_token(&self, token: &str) -> Result<String> {
+        let claims = self.verify_token(token)?;
+        self.generate_token(claims.sub)
+    }
+}
+
+impl<C> JwtHandler<C> {
+    /// Create a JWT handler for a custom claims type.
+    ///
+    /// The claims must include an `exp` field (Unix timestamp), which is
+    /// checked on verification.
+    ///
+    /// # Example
+    /// ```
+    /// use serde::{Deserialize, Serialize};
+    /// use starlight::JwtHandler;
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct TenantClaims {
+    ///     sub: String,
+    ///     exp: i64,
+    ///     tenant: String,
+    /// }
+    ///
+    /// let handler = JwtHandler::<TenantClaims>::new_with_claims("secret");
+    /// ```
+    pub fn new_with_claims(secret: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+            verification_keys: Vec::new(),
+            expires_in_seconds: 3600, // 1 hour default
+            revocation_check: None,
+            clock: Arc::new(SystemClock),
+            max_iat_skew: None,
+            _claims: PhantomData,
+        }
+    }
+
+    /// Accept tokens signed with any of `keys` in addition to the primary secret.
+    ///
+    /// Use this during key rotation: tokens are always signed with the
+    /// primary secret, while tokens signed with a previous key keep
+    /// verifying until it is removed. Tokens carry a `kid` header derived
+    /// from their signing key, so the matching key is tried first.
+    ///
+    /// # Example
+    /// ```
+    /// use starlight::JwtHandler;
+    ///
+    /// let handler = JwtHandler::new("new-secret")
+    ///     .with_verification_keys(vec!["old-secret".to_string()]);
+    /// ```
+    pub fn with_verification_keys(mut self, keys: Vec<String>) -> Self {
+        self.verification_keys = keys;
+        self
+    }
+
+    /// Reject tokens for which `check` returns `true`, even if still valid.
+    ///
+    /// Every generated token has a unique `jti`, so the check can revoke
+    /// single tokens as well as whole subjects.
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::Arc;
+    /// use starlight::JwtHandler;
+    ///
+    /// let handler = JwtHandler::new("secret")
+    ///     .with_revocation_check(Arc::new(|claims| claims.sub == "CompromisedSentinel"));
+    /// ```
+    pub fn with_revocation_check(mut self, check: RevocationCheck<C>) -> Self {
+        self.revocation_check = Some(check);
+        self
+    }
+
+    /// Read the current time from `clock` when issuing and verifying tokens.
+    ///
+    /// Defaults to the system clock; tests can inject a fake one to check
+    /// expiry without waiting.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Reject tokens whose `iat` is more than `skew` in the future.
+    ///
+    /// A token issued well ahead of the current time points to an issuer
+    /// with a misconfigured clock. Tokens without an `iat` are not checked.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use starlight::JwtHandler;
+    ///
+    /// let handler = JwtHandler::new("secret").with_max_iat_skew(Duration::from_secs(600));
+    /// ```
+    pub fn with_max_iat_skew(mut self, skew: Duration) -> Self {
+        self.max_iat_skew = Some(skew);
+        self
+    }
+}
+
+impl<C: Serialize + DeserializeOwned> JwtHandler<C> {
+    /// Sign a token carrying the given claims with the primary secret.
+    #[allow(clippy::result_large_err)]
+    pub fn encode_claims(&self, claims: &C) -> Result<String> {
+        let header = Header {
+            kid: Some(key_id(&self.secret)?),
+            ..Header::default()
+        };
+
         let token = encode(
-            &Header::default(),
-            &claims,
+            &header,
+            claims,
             &EncodingKey::from_secret(self.secret.as_bytes()),
         )?;
 
@@ -98,6 +256,9 @@ impl JwtHandler {
 
     /// Verify and decode a JWT token.
     ///
+    /// The token is checked against the primary secret and every
+    /// verification key, starting with the key named by its `kid` header.
+    ///
     /// # Arguments
     /// * `token` - The JWT token to verify
     ///
@@ -105,35 +266,96 @@ impl JwtHandler {
     /// The decoded claims if valid
     ///
     /// # Errors
-    /// Returns an error if the token is invalid or expired
+    /// Returns an error if the token is invalid, expired, or revoked
+    #[allow(clippy::result_large_err)]
+    pub fn verify_token(&self, token: &str) -> Result<C> {
+        let claims = self.decode_claims(token)?;
+
+        if let Some(ref is_revoked) = self.revocation_check {
+            if is_revoked(&claims) {
+                return Err(Error::TokenRevoked);
+            }
+        }
+
+        Ok(claims)
+    }
+
+    /// Decode a token against each configured key.
     #[allow(clippy::result_large_err)]
-    pub fn verify_token(&self, token: &str) -> Result<Claims> {
+    fn decode_claims(&self, token: &str) -> Result<C> {
         let mut validation = Validation::default();
-        validation.set_required_spec_claims(&["exp", "sub"]);
+        validation.set_required_spec_claims(&["exp"]);
+        // Checked against `self.clock` instead.
+        validation.validate_exp = false;
 
-        let token_data: TokenData<Claims> = decode(
-            token,
-            &DecodingKey::from_secret(self.secret.as_bytes()),
-            &validation,
-        )?;
+        let mut keys: Vec<&String> = std::iter::once(&self.secret)
+            .chain(&self.verification_keys)
+            .collect();
+        if let Some(kid) = decode_header(token)?.kid {
+            // Stable sort: the key matching `kid` moves to the front.
+            keys.sort_by_key(|key| key_id(key).ok().as_deref() != Some(kid.as_str()));
+        }
 
-        Ok(token_data.claims)
+        let mut last_error = None;
+        for key in keys {
+            let result: std::result::Result<TokenData<Value>, _> = decode(
+                token,
+                &DecodingKey::from_secret(key.as_bytes()),
+                &validation,
+            );
+
+            match result {
+                Ok(token_data) => return self.check_times(token_data.claims, validation.leeway),
+                // Any other error means the signature matched this key.
+                Err(e) if *e.kind() == ErrorKind::InvalidSignature => last_error = Some(e),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(last_error
+            .unwrap_or_else(|| ErrorKind::InvalidSignature.into())
+            .into())
     }
 
-    /// Refresh a token by generating a new one with the same subject.
-    ///
-    /// # Arguments
-    /// * `token` - The existing token to refresh
-    ///
-    /// # Returns
-    /// A new token with extended expiration
+    /// Reject claims whose `exp` has passed, allowing `leeway` seconds of
+    /// skew, or whose `iat` is further ahead than `max_iat_skew`.
     #[allow(clippy::result_large_err)]
-    pub fn refresh_token(&self, token: &str) -> Result<String> {
-        let claims = self.verify_token(token)?;
-        self.generate_token(claims.sub)
+    fn check_times(&self, claims: Value, leeway: u64) -> Result<C> {
+        let now = self.clock.now();
+        let exp = claims.get("exp").and_then(Value::as_i64).ok_or_else(|| {
+            jsonwebtoken::errors::Error::from(ErrorKind::MissingRequiredClaim("exp".to_string()))
+        })?;
+        if exp < now.saturating_sub_unsigned(leeway) {
+            return Err(jsonwebtoken::errors::Error::from(ErrorKind::ExpiredSignature).into());
+        }
+
+        if let (Some(skew), Some(iat)) =
+            (self.max_iat_skew, claims.get("iat").and_then(Value::as_i64))
+        {
+            if iat > now.saturating_add_unsigned(skew.as_secs()) {
+                return Err(jsonwebtoken::errors::Error::from(ErrorKind::ImmatureSignature).into());
+            }
+        }
+
+        let claims = serde_json::from_value(claims).map_err(jsonwebtoken::errors::Error::from)?;
+        Ok(claims)
     }
 }
 
+/// Derive a key id from a secret without revealing it.
+///
+/// The id is a prefix of the secret's HMAC over a fixed label, so it is
+/// stable across processes and SDK versions.
+#[allow(clippy::result_large_err)]
+fn key_id(secret: &str) -> Result<String> {
+    let mac = jsonwebtoken::crypto::sign(
+        b"starlight-kid",
+        &EncodingKey::from_secret(secret.as_bytes()),
+        Algorithm::HS256,
+    )?;
+    Ok(mac[..12].to_string())
+}
+
 impl Default for JwtHandler {
     fn default() -> Self {
         Self::new("starlight-default-secret-not-for-production")
@@ -143,6 +365,7 @@ impl Default for JwtHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{Duration, Utc};
 
     #[test]
     fn test_generate_and_verify() {
@@ -169,6 +392,68 @@ mod tests {
         );
     }
 
+    #[derive(Debug)]
+    struct FakeClock(std::sync::atomic::AtomicI64);
+
+    impl FakeClock {
+        fn advance(&self, seconds: i64) {
+            self.0
+                .fetch_add(seconds, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> i64 {
+            self.0.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn test_fake_clock_drives_expiry() {
+        let clock = Arc::new(FakeClock(1_700_000_000.into()));
+        let handler = JwtHandler::new("test-secret-key-32-characters-long")
+            .with_expiry(300)
+            .with_clock(clock.clone());
+
+        let token = handler.generate_token("TestSentinel").unwrap();
+        let claims = handler.verify_token(&token).unwrap();
+        assert_eq!(claims.iat, 1_700_000_000);
+        assert_eq!(claims.exp, 1_700_000_300);
+
+        // Still accepted within the 60 second leeway.
+        clock.advance(360);
+        assert!(handler.verify_token(&token).is_ok());
+
+        clock.advance(1);
+        match handler.verify_token(&token) {
+            Err(Error::Jwt(e)) => assert_eq!(*e.kind(), ErrorKind::ExpiredSignature),
+            other => panic!("expected expired token, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_iat_too_far_in_future() {
+        let now = 1_700_000_000;
+        let issuer = JwtHandler::new("test-secret-key-32-characters-long")
+            .with_clock(Arc::new(FakeClock((now + 3600).into())));
+        let token = issuer.generate_token("TestSentinel").unwrap();
+
+        let handler = JwtHandler::new("test-secret-key-32-characters-long")
+            .with_clock(Arc::new(FakeClock(now.into())));
+        assert!(handler.verify_token(&token).is_ok());
+
+        let strict = handler.with_max_iat_skew(std::time::Duration::from_secs(600));
+        match strict.verify_token(&token) {
+            Err(Error::Jwt(e)) => assert_eq!(*e.kind(), ErrorKind::ImmatureSignature),
+            other => panic!("expected future iat to be rejected, got {:?}", other),
+        }
+
+        // Within the allowance.
+        let issuer = issuer.with_clock(Arc::new(FakeClock((now + 300).into())));
+        let token = issuer.generate_token("TestSentinel").unwrap();
+        assert!(strict.verify_token(&token).is_ok());
+    }
+
     #[test]
     fn test_refresh_token() {
         let handler = JwtHandler::new("test-secret-key-32-characters-long");
@@ -186,4 +471,76 @@ mod tests {
         // Refreshed token should have same or later expiration
         assert!(refreshed_claims.exp >= original_claims.exp);
     }
+
+    #[test]
+    fn test_rotated_key_still_verifies() {
+        let old = JwtHandler::new("old-secret-key-32-characters-long!");
+        let token = old.generate_token("TestSentinel").unwrap();
+
+        let rotated = JwtHandler::new("new-secret-key-32-characters-long!")
+            .with_verification_keys(vec!["old-secret-key-32-characters-long!".to_string()]);
+        assert_eq!(rotated.verify_token(&token).unwrap().sub, "TestSentinel");
+
+        // New tokens are signed with the primary key only.
+        let fresh = rotated.generate_token("TestSentinel").unwrap();
+        assert!(old.verify_token(&fresh).is_err());
+
+        let unrelated = JwtHandler::new("unrelated-secret-32-characters-long");
+        assert!(unrelated.verify_token(&token).is_err());
+    }
+
+    #[test]
+    fn test_kid_header_names_signing_key() {
+        let handler = JwtHandler::new("primary-secret");
+        let token = handler.generate_token("TestSentinel").unwrap();
+
+        let kid = decode_header(&token).unwrap().kid.unwrap();
+        assert_eq!(kid, key_id("primary-secret").unwrap());
+        assert_ne!(kid, key_id("other-secret").unwrap());
+        assert!(!kid.contains("primary"));
+    }
+
+    #[test]
+    fn test_revocation_check() {
+        let handler = JwtHandler::new("test-secret-key-32-characters-long")
+            .with_revocation_check(Arc::new(|claims| claims.sub == "Compromised"));
+
+        let good = handler.generate_token("TestSentinel").unwrap();
+        let claims = handler.verify_token(&good).unwrap();
+        assert!(claims.jti.is_some());
+
+        let bad = handler.generate_token("Compromised").unwrap();
+        assert!(matches!(
+            handler.verify_token(&bad),
+            Err(Error::TokenRevoked)
+        ));
+    }
+
+    #[test]
+    fn test_custom_claims_type() {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct TenantClaims {
+            sub: String,
+            exp: i64,
+            tenant: String,
+        }
+
+        let handler =
+            JwtHandler::<TenantClaims>::new_with_claims("test-secret-key-32-characters-long");
+        let token = handler
+            .encode_claims(&TenantClaims {
+                sub: "TestSentinel".to_string(),
+                exp: (Utc::now() + Duration::minutes(5)).timestamp(),
+                tenant: "acme".to_string(),
+            })
+            .unwrap();
+
+        let claims = handler.verify_token(&token).unwrap();
+        assert_eq!(claims.tenant, "acme");
+        assert_eq!(claims.sub, "TestSentinel");
+
+        // The default `Claims` type needs fields this token doesn't carry.
+        let default = JwtHandler::new("test-secret-key-32-characters-long");
+        assert!(default.verify_token(&token).is_err());
+    }
 }
@@ -0,0 +1,128 @@
+//! Delay policies for reconnect attempts.
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Decides how long to wait before each reconnect attempt.
+///
+/// `attempt` starts at 1 for the first retry. `reset` is called before a
+/// new series of attempts begins, so stateful strategies can start over.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use starlight::backoff::ConstantBackoff;
+/// use starlight::client::ClientConfig;
+///
+/// let config = ClientConfig::new("ws://localhost:8080")
+///     .with_backoff(ConstantBackoff::new(Duration::from_secs(2)));
+/// ```
+pub trait BackoffStrategy: Debug + Send {
+    /// Delay before reconnect attempt number `attempt`.
+    fn next_delay(&mut self, attempt: u32) -> Duration;
+
+    /// Forget any state from a previous series of attempts.
+    fn reset(&mut self) {}
+}
+
+/// Doubles the delay after every attempt, up to a maximum.
+///
+/// This is the default, built from `ClientConfig::reconnect_delay_ms` and
+/// `ClientConfig::max_reconnect_delay_ms`.
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    max: Duration,
+}
+
+impl ExponentialBackoff {
+    /// Start at `initial` and never exceed `max`.
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self { initial, max }
+    }
+}
+
+impl BackoffStrategy for ExponentialBackoff {
+    fn next_delay(&mut self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// Waits the same delay before every attempt.
+#[derive(Debug, Clone)]
+pub struct ConstantBackoff {
+    delay: Duration,
+}
+
+impl ConstantBackoff {
+    /// Wait `delay` before every attempt.
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
+    }
+}
+
+impl BackoffStrategy for ConstantBackoff {
+    fn next_delay(&mut self, _attempt: u32) -> Duration {
+        self.delay
+    }
+}
+
+/// Grows the delay by a fixed step after every attempt, up to a maximum.
+#[derive(Debug, Clone)]
+pub struct LinearBackoff {
+    initial: Duration,
+    step: Duration,
+    max: Duration,
+}
+
+impl LinearBackoff {
+    /// Start at `initial`, add `step` per attempt, and never exceed `max`.
+    pub fn new(initial: Duration, step: Duration, max: Duration) -> Self {
+        Self { initial, step, max }
+    }
+}
+
+impl BackoffStrategy for LinearBackoff {
+    fn next_delay(&mut self, attempt: u32) -> Duration {
+        let steps = self.step.saturating_mul(attempt.saturating_sub(1));
+        self.initial.saturating_add(steps).min(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delays(strategy: &mut dyn BackoffStrategy, attempts: u32) -> Vec<u64> {
+        (1..=attempts)
+            .map(|attempt| strategy.next_delay(attempt).as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn test_exponential_sequence() {
+        let mut backoff =
+            ExponentialBackoff::new(Duration::from_millis(100), Duration::from_millis(1000));
+        assert_eq!(
+            delays(&mut backoff, 6),
+            vec![100, 200, 400, 800, 1000, 1000]
+        );
+
+        // Huge attempt numbers saturate at the cap instead of overflowing.
+        assert_eq!(backoff.next_delay(u32::MAX), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_constant_and_linear_sequences() {
+        let mut constant = ConstantBackoff::new(Duration::from_millis(250));
+        assert_eq!(delays(&mut constant, 3), vec![250, 250, 250]);
+
+        let mut linear = LinearBackoff::new(
+            Duration::from_millis(100),
+            Duration::from_millis(150),
+            Duration::from_millis(500),
+        );
+        assert_eq!(delays(&mut linear, 5), vec![100, 250, 400, 500, 500]);
+    }
+}
@@ -0,0 +1,134 @@
+//! Short-lived cache of pre-check decisions.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::messages::{PreCheckParams, PreCheckResponse};
+
+/// Identifies pre-checks that should get the same decision.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DecisionKey {
+    url: Option<String>,
+    command: String,
+    selector: Option<String>,
+}
+
+impl DecisionKey {
+    pub(crate) fn new(params: &PreCheckParams) -> Self {
+        Self {
+            url: params.url.clone(),
+            command: params.command.clone(),
+            selector: params.selector.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    response: PreCheckResponse,
+    stored: Instant,
+    last_used: u64,
+}
+
+/// LRU cache of decisions that expire after a fixed TTL.
+#[derive(Debug)]
+pub(crate) struct DecisionCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<DecisionKey, Entry>,
+    clock: u64,
+}
+
+impl DecisionCache {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Look up a decision stored less than `ttl` ago.
+    pub(crate) fn get(&mut self, key: &DecisionKey) -> Option<PreCheckResponse> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        if entry.stored.elapsed() >= self.ttl {
+            self.entries.remove(key);
+            return None;
+        }
+        entry.last_used = self.clock;
+        Some(entry.response.clone())
+    }
+
+    /// Remove and return a decision stored less than `ttl` ago.
+    pub(crate) fn take(&mut self, key: &DecisionKey) -> Option<PreCheckResponse> {
+        let response = self.get(key)?;
+        self.entries.remove(key);
+        Some(response)
+    }
+
+    /// Store a decision, evicting the least recently used one when full.
+    pub(crate) fn insert(&mut self, key: DecisionKey, response: PreCheckResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.clock += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(
+            key,
+            Entry {
+                response,
+                stored: Instant::now(),
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// Forget every stored decision.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(selector: &str) -> DecisionKey {
+        DecisionKey {
+            url: Some("https://example.com".into()),
+            command: "click".into(),
+            selector: Some(selector.into()),
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction_and_ttl() {
+        let mut cache = DecisionCache::new(2, Duration::from_secs(60));
+        cache.insert(key("#a"), PreCheckResponse::Clear);
+        cache.insert(key("#b"), PreCheckResponse::Clear);
+
+        // Touch #a so #b becomes the least recently used.
+        assert!(cache.get(&key("#a")).is_some());
+        cache.insert(key("#c"), PreCheckResponse::Clear);
+        assert!(cache.get(&key("#a")).is_some());
+        assert!(cache.get(&key("#b")).is_none());
+        assert!(cache.get(&key("#c")).is_some());
+
+        let mut expired = DecisionCache::new(2, Duration::ZERO);
+        expired.insert(key("#a"), PreCheckResponse::Clear);
+        assert!(expired.get(&key("#a")).is_none());
+    }
+}
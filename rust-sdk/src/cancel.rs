@@ -0,0 +1,93 @@
+//! Cancellation signal for in-flight handler work.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+/// Signals that the connection a pre-check arrived on has been lost.
+///
+/// Passed to [`SentinelHandler::on_pre_check`](crate::SentinelHandler::on_pre_check).
+/// Once cancelled, any decision would be sent nowhere, so slow handlers
+/// can check [`is_cancelled`](Self::is_cancelled) or race their work
+/// against [`cancelled`](Self::cancelled) to give up early.
+///
+/// # Example
+/// ```rust
+/// # use starlight::CancelToken;
+/// # async fn expensive_check() {}
+/// # async fn example(cancel: CancelToken) {
+/// tokio::select! {
+///     _ = expensive_check() => {}
+///     _ = cancel.cancelled() => return,
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    /// Create a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the token, waking everyone waiting on it.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Check whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the token is cancelled.
+    pub async fn cancelled(&self) {
+        let notified = self.inner.notify.notified();
+        tokio::pin!(notified);
+        // Register before checking the flag so a concurrent cancel isn't missed.
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_wakes_waiters() {
+        let token = CancelToken::new();
+        assert!(!token.is_cancelled());
+
+        let waiter = tokio::spawn({
+            let token = token.clone();
+            async move { token.cancelled().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        token.cancel();
+
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(token.is_cancelled());
+
+        // Already cancelled tokens resolve immediately.
+        token.cancelled().await;
+    }
+}
@@ -0,0 +1,219 @@
+//! Sending payloads too large for one frame as a sequence of chunks.
+//!
+//! [`WebSocketClient::send_chunked`](crate::WebSocketClient::send_chunked)
+//! serializes a message's params to JSON text and sends slices of it as
+//! `starlight.chunk` notifications:
+//!
+//! ```json
+//! {"jsonrpc":"2.0","method":"starlight.chunk","params":{"chunk_id":"5f0c…","chunk_index":0,"total_chunks":3,"data":"{\"image\":\"iVBOR"}}
+//! ```
+//!
+//! followed by a `starlight.chunk_end` naming the original method:
+//!
+//! ```json
+//! {"jsonrpc":"2.0","method":"starlight.chunk_end","params":{"chunk_id":"5f0c…","method":"starlight.screenshot","total_chunks":3}}
+//! ```
+//!
+//! The receiver joins the `data` of chunks `0..total_chunks` in index
+//! order and parses the result as the params of a `method` notification.
+//! Chunks of different payloads may interleave; `chunk_id` tells them
+//! apart. The client reassembles chunked messages from the Hub the same
+//! way, so [`receive`](crate::WebSocketClient::receive) returns the
+//! original notification. Incomplete or malformed sequences are dropped
+//! with a warning.
+
+use std::collections::{BTreeMap, HashMap};
+
+use tracing::warn;
+
+use crate::messages::{methods, ChunkEndParams, ChunkParams, RawMessage, JSONRPC_VERSION};
+
+/// Split `text` into slices of at most `chunk_size` bytes.
+///
+/// Slices end on char boundaries, so one may be shorter; a char longer
+/// than `chunk_size` gets a slice of its own.
+pub(crate) fn split(text: &str, chunk_size: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = chunk_size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (part, tail) = rest.split_at(end);
+        parts.push(part);
+        rest = tail;
+    }
+    if parts.is_empty() {
+        parts.push("");
+    }
+    parts
+}
+
+#[derive(Debug)]
+struct Partial {
+    total: u32,
+    parts: BTreeMap<u32, String>,
+}
+
+/// Collects inbound chunks until their sequence is complete.
+#[derive(Debug, Default)]
+pub(crate) struct Reassembler {
+    partial: HashMap<String, Partial>,
+}
+
+impl Reassembler {
+    /// Take the chunks out of `messages`, returning the other messages and
+    /// any chunked message completed by them, in arrival order.
+    pub(crate) fn accept(&mut self, messages: Vec<RawMessage>) -> Vec<RawMessage> {
+        messages
+            .into_iter()
+            .filter_map(|message| match message.method.as_str() {
+                methods::CHUNK => {
+                    self.add(message.params);
+                    None
+                }
+                methods::CHUNK_END => self.finish(message.params),
+                _ => Some(message),
+            })
+            .collect()
+    }
+
+    /// Forget partial sequences, e.g. from a connection that was lost.
+    pub(crate) fn clear(&mut self) {
+        self.partial.clear();
+    }
+
+    fn add(&mut self, params: serde_json::Value) {
+        let chunk: ChunkParams = match serde_json::from_value(params) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                warn!("Ignoring malformed chunk: {}", e);
+                return;
+            }
+        };
+        if chunk.chunk_index >= chunk.total_chunks {
+            warn!(
+                "Ignoring chunk {} of {} for {}",
+                chunk.chunk_index, chunk.total_chunks, chunk.chunk_id
+            );
+            return;
+        }
+
+        let partial = self
+            .partial
+            .entry(chunk.chunk_id.clone())
+            .or_insert_with(|| Partial {
+                total: chunk.total_chunks,
+                parts: BTreeMap::new(),
+            });
+        if partial.total != chunk.total_chunks {
+            warn!(
+                "Chunks of {} disagree on total_chunks, dropping",
+                chunk.chunk_id
+            );
+            self.partial.remove(&chunk.chunk_id);
+            return;
+        }
+        partial.parts.insert(chunk.chunk_index, chunk.data);
+    }
+
+    fn finish(&mut self, params: serde_json::Value) -> Option<RawMessage> {
+        let end: ChunkEndParams = match serde_json::from_value(params) {
+            Ok(end) => end,
+            Err(e) => {
+                warn!("Ignoring malformed chunk end: {}", e);
+                return None;
+            }
+        };
+        let Some(partial) = self.partial.remove(&end.chunk_id) else {
+            warn!("No chunks received for {}", end.chunk_id);
+            return None;
+        };
+        if partial.total != end.total_chunks || partial.parts.len() as u32 != partial.total {
+            warn!(
+                "{} of {} chunks received for {}, dropping",
+                partial.parts.len(),
+                end.total_chunks,
+                end.chunk_id
+            );
+            return None;
+        }
+
+        let text: String = partial.parts.into_values().collect();
+        match serde_json::from_str(&text) {
+            Ok(params) => Some(RawMessage {
+                jsonrpc: JSONRPC_VERSION.to_string(),
+                method: end.method,
+                params,
+                id: None,
+                result: None,
+                error: None,
+            }),
+            Err(e) => {
+                warn!("Reassembled {} is not valid JSON: {}", end.chunk_id, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(method: &str, params: serde_json::Value) -> RawMessage {
+        serde_json::from_value(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_on_char_boundaries() {
+        assert_eq!(split("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(split("aéb", 2), vec!["a", "é", "b"]);
+        assert_eq!(split("😀", 1), vec!["😀"]);
+        assert_eq!(split("", 4), vec![""]);
+    }
+
+    #[test]
+    fn test_reassembly_out_of_order_and_incomplete() {
+        let chunk = |id: &str, index: u32, data: &str| {
+            message(
+                methods::CHUNK,
+                json!({ "chunk_id": id, "chunk_index": index, "total_chunks": 2, "data": data }),
+            )
+        };
+        let end = |id: &str| {
+            message(
+                methods::CHUNK_END,
+                json!({ "chunk_id": id, "method": "starlight.screenshot", "total_chunks": 2 }),
+            )
+        };
+        let other = message("starlight.entropy", json!({}));
+
+        let mut reassembler = Reassembler::default();
+        let out = reassembler.accept(vec![
+            chunk("a", 1, r#""x"}"#),
+            other.clone(),
+            chunk("b", 0, r#"{"n":"#),
+            chunk("a", 0, r#"{"n":"#),
+            end("a"),
+            end("b"),
+        ]);
+
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0], other);
+        assert_eq!(out[1].method, "starlight.screenshot");
+        assert_eq!(out[1].params, json!({ "n": "x" }));
+        assert!(reassembler.partial.is_empty());
+
+        // A missing chunk drops the message.
+        let out = reassembler.accept(vec![chunk("c", 1, r#""x"}"#), end("c")]);
+        assert!(out.is_empty());
+        assert!(reassembler.partial.is_empty());
+    }
+}
@@ -1,17 +1,44 @@
 //! WebSocket client for connecting to the Starlight Hub.
 
-use std::sync::Arc;
-use std::time::Duration;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
 
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
-use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
 use tokio::time::sleep;
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::CapacityError;
+use tokio_tungstenite::tungstenite::handshake::client::{Request, Response};
+use tokio_tungstenite::tungstenite::http::header::{SEC_WEBSOCKET_PROTOCOL, USER_AGENT};
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::{
+    connect_async_with_config, tungstenite::Message, MaybeTlsStream, WebSocketStream,
+};
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
+use crate::backoff::{BackoffStrategy, ExponentialBackoff};
+use crate::chunk::{self, Reassembler};
+use crate::clock::Delay;
+use crate::codec::Codec;
 use crate::error::{Error, Result};
-use crate::messages::RawMessage;
+use crate::messages::{
+    decode_frame, methods, ChunkEndParams, ChunkParams, JsonRpcNotification, RawMessage,
+};
+use crate::proxy::ProxyConfig;
+use crate::ratelimit::{OutboundLimiter, RateLimit, RateLimitAction};
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
+use crate::transport::{self, Transport};
 
 /// Type alias for the WebSocket stream.
 pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
@@ -19,7 +46,12 @@ pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 /// WebSocket client configuration.
 #[derive(Debug, Clone)]
 pub struct ClientConfig {
-    /// Hub URL (e.g., "ws://localhost:8080")
+    /// Hub URLs (e.g., "ws://localhost:8080"), tried in order on failover
+    pub urls: Vec<String>,
+
+    /// Single Hub URL, from before `urls`. When set, it is the only
+    /// endpoint and `urls` is ignored.
+    #[deprecated(note = "use `urls` or `ClientConfig::new`")]
     pub url: String,
 
     /// Enable auto-reconnection
@@ -33,16 +65,111 @@ pub struct ClientConfig {
 
     /// Maximum reconnection attempts (0 = unlimited)
     pub max_reconnect_attempts: u32,
+
+    /// Give up reconnecting after this much time (unlimited when `None`)
+    pub max_reconnect_elapsed: Option<Duration>,
+
+    /// Reconnect delay policy (exponential from `reconnect_delay_ms` when `None`)
+    pub backoff: Option<Arc<StdMutex<dyn BackoffStrategy>>>,
+
+    /// Waits out the backoff between reconnect attempts (tokio's timer when `None`)
+    pub delay: Option<Arc<dyn Delay>>,
+
+    /// Maximum inbound message size in bytes (tungstenite default when `None`)
+    pub max_message_size: Option<usize>,
+
+    /// Maximum inbound frame size in bytes (tungstenite default when `None`)
+    pub max_frame_size: Option<usize>,
+
+    /// Proxy to tunnel the connection through (direct when `None`)
+    pub proxy: Option<ProxyConfig>,
+
+    /// Local address to originate the connection from (OS choice when `None`)
+    pub local_bind_addr: Option<SocketAddr>,
+
+    /// Try the Hub's IPv6 addresses before its IPv4 ones
+    pub prefer_ipv6: bool,
+
+    /// Addresses used for these host names instead of DNS, like curl's
+    /// `--resolve` (the proxy resolves hosts when one is set)
+    pub resolve_overrides: HashMap<String, IpAddr>,
+
+    /// WebSocket subprotocols offered in `Sec-WebSocket-Protocol`, in
+    /// order of preference (none offered when empty)
+    pub subprotocols: Vec<String>,
+
+    /// `User-Agent` sent with the upgrade request
+    /// (`starlight-rust-sdk/<SDK_VERSION>` when `None`)
+    pub user_agent: Option<String>,
+
+    /// Wire encoding for messages (JSON text by default)
+    pub codec: Codec,
+
+    /// Capacity of the outbound queue feeding the writer task
+    pub send_buffer: usize,
+
+    /// How long `send` waits for room in the outbound queue before
+    /// failing with `Error::Timeout`
+    pub send_timeout: Duration,
+
+    /// Token bucket limits on outbound messages, keyed by method
+    pub rate_limits: HashMap<String, RateLimit>,
+
+    /// Drop a hijack repeating the previous hijack's reason within this
+    /// window (never when `None`)
+    pub hijack_coalesce_window: Option<Duration>,
+
+    /// What happens to messages over a rate limit or coalesced away
+    pub rate_limit_action: RateLimitAction,
+
+    /// Close codes after which the Sentinel must not reconnect
+    /// (default: 1008 policy violation and the 4000-4999 application range)
+    pub non_retryable_close_codes: Vec<RangeInclusive<u16>>,
+
+    /// Start [`WebSocketClient::stats`] from zero after each reconnect
+    /// (cumulative over reconnects by default)
+    pub reset_stats_on_reconnect: bool,
+
+    /// Mask screenshots and auth tokens in logged frames
+    pub redact_logs: bool,
+
+    /// TLS settings for `wss://` URLs (default TLS when `None`)
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
 }
 
 impl Default for ClientConfig {
+    #[allow(deprecated)]
     fn default() -> Self {
         Self {
-            url: "ws://localhost:8080".to_string(),
+            urls: vec!["ws://localhost:8080".to_string()],
+            url: String::new(),
             auto_reconnect: true,
             reconnect_delay_ms: 1000,
             max_reconnect_delay_ms: 30000,
             max_reconnect_attempts: 0, // Unlimited
+            max_reconnect_elapsed: None,
+            backoff: None,
+            delay: None,
+            max_message_size: None,
+            max_frame_size: None,
+            proxy: None,
+            local_bind_addr: None,
+            prefer_ipv6: false,
+            resolve_overrides: HashMap::new(),
+            subprotocols: Vec::new(),
+            user_agent: None,
+            codec: Codec::Json,
+            send_buffer: 64,
+            send_timeout: Duration::from_secs(10),
+            rate_limits: HashMap::new(),
+            hijack_coalesce_window: None,
+            rate_limit_action: RateLimitAction::Drop,
+            non_retryable_close_codes: vec![1008..=1008, 4000..=4999],
+            reset_stats_on_reconnect: false,
+            redact_logs: true,
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 }
@@ -51,46 +178,570 @@ impl ClientConfig {
     /// Create a new client config with the given URL.
     pub fn new(url: impl Into<String>) -> Self {
         Self {
-            url: url.into(),
+            urls: vec![url.into()],
             ..Default::default()
         }
     }
+
+    /// Replace `urls` with the deprecated `url`, if it is set.
+    #[allow(deprecated)]
+    pub(crate) fn merge_url(mut self) -> Self {
+        if !self.url.is_empty() {
+            self.urls = vec![std::mem::take(&mut self.url)];
+        }
+        self
+    }
+
+    /// Add a fallback Hub URL, tried after the ones already configured.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.urls.push(url.into());
+        self
+    }
+
+    /// Set TLS settings for `wss://` connections.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Limit the size of inbound messages and frames.
+    ///
+    /// Oversized messages fail with `Error::ConnectionClosed` ("message too large")
+    /// instead of being buffered.
+    pub fn with_size_limits(mut self, max_message_size: usize, max_frame_size: usize) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self.max_frame_size = Some(max_frame_size);
+        self
+    }
+
+    /// Connect through an HTTP or SOCKS5 proxy.
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Originate the connection from `addr`, e.g. a specific interface on a
+    /// multi-homed host. Use port 0 to let the OS pick the port.
+    ///
+    /// Only Hub addresses of the same family as `addr` are tried.
+    pub fn with_local_bind_addr(mut self, addr: SocketAddr) -> Self {
+        self.local_bind_addr = Some(addr);
+        self
+    }
+
+    /// Try the Hub's IPv6 addresses first when its host name resolves to both.
+    pub fn with_prefer_ipv6(mut self) -> Self {
+        self.prefer_ipv6 = true;
+        self
+    }
+
+    /// Connect to `addr` whenever a Hub URL names `host`, skipping DNS.
+    ///
+    /// The URL's host name is still sent in the upgrade request and used
+    /// for TLS. Host names match case-insensitively.
+    pub fn with_resolve_override(mut self, host: impl Into<String>, addr: IpAddr) -> Self {
+        self.resolve_overrides.insert(host.into(), addr);
+        self
+    }
+
+    /// Stop reconnecting once `budget` has elapsed since the first attempt.
+    ///
+    /// Applies alongside `max_reconnect_attempts`; whichever is hit first
+    /// ends the reconnect loop.
+    pub fn with_max_reconnect_elapsed(mut self, budget: Duration) -> Self {
+        self.max_reconnect_elapsed = Some(budget);
+        self
+    }
+
+    /// Use a custom delay policy between reconnect attempts.
+    pub fn with_backoff(mut self, backoff: impl BackoffStrategy + 'static) -> Self {
+        self.backoff = Some(Arc::new(StdMutex::new(backoff)));
+        self
+    }
+
+    /// Wait between reconnect attempts with `delay` instead of tokio's timer.
+    ///
+    /// Lets tests check the backoff sequence without sleeping.
+    pub fn with_delay(mut self, delay: impl Delay + 'static) -> Self {
+        self.delay = Some(Arc::new(delay));
+        self
+    }
+
+    /// Identify the connection to the Hub with a custom `User-Agent`.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Offer WebSocket subprotocols (e.g. `starlight.v1`) during the upgrade.
+    ///
+    /// The one the Hub selects is available from
+    /// [`WebSocketClient::negotiated_protocol`] after connecting.
+    pub fn with_subprotocols(mut self, protocols: Vec<impl Into<String>>) -> Self {
+        self.subprotocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Encode messages with `codec` instead of JSON text.
+    ///
+    /// The Hub must be configured for the same encoding.
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Set the outbound queue capacity and how long a send may wait for room.
+    ///
+    /// When the Hub stops draining the socket, the queue fills and further
+    /// sends fail with `Error::Timeout` after `timeout`.
+    pub fn with_send_buffer(mut self, capacity: usize, timeout: Duration) -> Self {
+        self.send_buffer = capacity;
+        self.send_timeout = timeout;
+        self
+    }
+
+    /// Limit how often messages with `method` may be sent.
+    ///
+    /// Methods without a limit are never throttled. Applies to messages
+    /// sent with [`WebSocketClient::send_json`] and friends and to a
+    /// Sentinel's own calls such as `hijack` and `action`, but not to its
+    /// pre-check decisions, which the Hub is waiting on.
+    pub fn with_rate_limit(mut self, method: impl Into<String>, limit: RateLimit) -> Self {
+        self.rate_limits.insert(method.into(), limit);
+        self
+    }
+
+    /// Coalesce identical back-to-back hijack reasons sent within `window`.
+    ///
+    /// Like [`with_rate_limit`](Self::with_rate_limit), this leaves hijacks
+    /// sent as pre-check decisions alone.
+    pub fn with_hijack_coalescing(mut self, window: Duration) -> Self {
+        self.hijack_coalesce_window = Some(window);
+        self
+    }
+
+    /// Choose whether rate-limited messages are dropped with a warning
+    /// (the default) or fail with `Error::RateLimited`.
+    pub fn with_rate_limit_action(mut self, action: RateLimitAction) -> Self {
+        self.rate_limit_action = action;
+        self
+    }
+
+    /// Replace the set of close codes that stop auto-reconnect.
+    ///
+    /// Single codes are written as one-element ranges, e.g. `4001..=4001`.
+    pub fn with_non_retryable_close_codes(mut self, codes: Vec<RangeInclusive<u16>>) -> Self {
+        self.non_retryable_close_codes = codes;
+        self
+    }
+
+    /// Log frames exactly as sent and received.
+    ///
+    /// By default, debug logs of frames replace `screenshot` values with
+    /// `"<redacted {n} bytes>"` and `auth_token` values with
+    /// `"<redacted>"`, keeping them short and free of credentials.
+    pub fn without_log_redaction(mut self) -> Self {
+        self.redact_logs = false;
+        self
+    }
+
+    /// Count [`WebSocketClient::stats`] per connection, resetting them after
+    /// each reconnect, instead of over the client's lifetime.
+    pub fn with_stats_reset_on_reconnect(mut self) -> Self {
+        self.reset_stats_on_reconnect = true;
+        self
+    }
+
+    /// Check whether a close code is in the non-retryable set.
+    fn is_non_retryable_close(&self, code: u16) -> bool {
+        self.non_retryable_close_codes
+            .iter()
+            .any(|range| range.contains(&code))
+    }
+
+    /// Build the tungstenite WebSocket configuration.
+    fn websocket_config(&self) -> WebSocketConfig {
+        let mut config = WebSocketConfig::default();
+        if self.max_message_size.is_some() {
+            config.max_message_size = self.max_message_size;
+        }
+        if self.max_frame_size.is_some() {
+            config.max_frame_size = self.max_frame_size;
+        }
+        config
+    }
+
+    /// Replace the Hub URLs with the given list.
+    pub fn with_urls(mut self, urls: Vec<impl Into<String>>) -> Self {
+        self.urls = urls.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+/// Lock a shared backoff strategy, recovering from a poisoned lock.
+fn lock_backoff(
+    backoff: &StdMutex<dyn BackoffStrategy>,
+) -> std::sync::MutexGuard<'_, dyn BackoffStrategy + 'static> {
+    backoff.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Traffic over a [`WebSocketClient`], from [`WebSocketClient::stats`].
+///
+/// Bytes are frame payload lengths. Frame counts cover both directions.
+/// Outbound frames count once written to the socket, so frames dropped
+/// from a full queue or lost with the connection don't.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Payload bytes sent to the Hub
+    pub bytes_sent: u64,
+    /// Payload bytes received from the Hub
+    pub bytes_received: u64,
+    /// Text frames sent and received
+    pub text_frames: u64,
+    /// Binary frames sent and received
+    pub binary_frames: u64,
+    /// Pings sent and received
+    pub pings: u64,
+    /// Pongs sent and received
+    pub pongs: u64,
+}
+
+/// Counters behind [`ConnectionStats`], shared by the client's clones.
+#[derive(Debug, Default)]
+struct StatsCounters {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    text_frames: AtomicU64,
+    binary_frames: AtomicU64,
+    pings: AtomicU64,
+    pongs: AtomicU64,
+}
+
+impl StatsCounters {
+    /// Count a frame sent to the Hub.
+    fn sent(&self, message: &Message) {
+        self.bytes_sent
+            .fetch_add(message.len() as u64, Ordering::Relaxed);
+        self.frame(message);
+    }
+
+    /// Count a frame received from the Hub.
+    fn received(&self, message: &Message) {
+        self.bytes_received
+            .fetch_add(message.len() as u64, Ordering::Relaxed);
+        self.frame(message);
+    }
+
+    fn frame(&self, message: &Message) {
+        let counter = match message {
+            Message::Text(_) => &self.text_frames,
+            Message::Binary(_) => &self.binary_frames,
+            Message::Ping(_) => &self.pings,
+            Message::Pong(_) => &self.pongs,
+            Message::Close(_) | Message::Frame(_) => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> ConnectionStats {
+        ConnectionStats {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            text_frames: self.text_frames.load(Ordering::Relaxed),
+            binary_frames: self.binary_frames.load(Ordering::Relaxed),
+            pings: self.pings.load(Ordering::Relaxed),
+            pongs: self.pongs.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [
+            &self.bytes_sent,
+            &self.bytes_received,
+            &self.text_frames,
+            &self.binary_frames,
+            &self.pings,
+            &self.pongs,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
 }
 
 /// WebSocket client for Starlight Hub communication.
+///
+/// The socket is split: `receive` only touches the read half and every send
+/// goes through a queue drained by a writer task, so a task blocked in
+/// `receive` (such as `Sentinel::run`) never holds up sends from others.
 pub struct WebSocketClient {
     config: ClientConfig,
-    stream: Arc<RwLock<Option<WsStream>>>,
-    sender: Arc<Mutex<Option<mpsc::Sender<Message>>>>,
+    stream: Arc<RwLock<Option<SplitStream<WsStream>>>>,
+    sender: Arc<Mutex<Option<mpsc::Sender<Outbound>>>>,
     connected: Arc<RwLock<bool>>,
     reconnect_count: Arc<RwLock<u32>>,
+    current_endpoint: Arc<RwLock<usize>>,
+    pending: Arc<Mutex<VecDeque<RawMessage>>>,
+    last_error: Arc<StdMutex<Option<String>>>,
+    negotiated_protocol: Arc<StdMutex<Option<String>>>,
+    limiter: Arc<StdMutex<OutboundLimiter>>,
+    stats: Arc<StatsCounters>,
+    chunks: Arc<StdMutex<Reassembler>>,
 }
 
 impl WebSocketClient {
     /// Create a new WebSocket client.
     pub fn new(config: ClientConfig) -> Self {
         Self {
-            config,
+            config: config.merge_url(),
             stream: Arc::new(RwLock::new(None)),
             sender: Arc::new(Mutex::new(None)),
             connected: Arc::new(RwLock::new(false)),
             reconnect_count: Arc::new(RwLock::new(0)),
+            current_endpoint: Arc::new(RwLock::new(0)),
+            pending: Arc::new(Mutex::new(VecDeque::new())),
+            last_error: Arc::new(StdMutex::new(None)),
+            negotiated_protocol: Arc::new(StdMutex::new(None)),
+            limiter: Arc::new(StdMutex::new(OutboundLimiter::default())),
+            stats: Arc::default(),
+            chunks: Arc::default(),
         }
     }
 
     /// Connect to the Hub.
+    ///
+    /// Endpoints are tried round-robin starting from the current one,
+    /// advancing to the next on failure. Returns the last error if none
+    /// of them accept the connection.
     pub async fn connect(&self) -> Result<()> {
-        info!("Connecting to Hub at {}", self.config.url);
+        if self.config.urls.is_empty() {
+            return Err(Error::InvalidConfig("No Hub URLs configured".to_string()));
+        }
 
-        let (ws_stream, _) = connect_async(&self.config.url).await?;
+        let start = *self.current_endpoint.read().await;
+        let mut last_error = None;
 
-        info!("Connected to Hub");
+        for offset in 0..self.config.urls.len() {
+            let index = (start + offset) % self.config.urls.len();
+            let url = &self.config.urls[index];
+            info!("Connecting to Hub at {}", url);
 
-        *self.stream.write().await = Some(ws_stream);
-        *self.connected.write().await = true;
-        *self.reconnect_count.write().await = 0;
+            match self.dial(url).await {
+                Ok((ws_stream, response)) => {
+                    let protocol = response
+                        .headers()
+                        .get(SEC_WEBSOCKET_PROTOCOL)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string);
+                    match protocol {
+                        Some(ref protocol) => info!("Connected to Hub ({})", protocol),
+                        None => info!("Connected to Hub"),
+                    }
+                    *self
+                        .negotiated_protocol
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner()) = protocol;
 
-        Ok(())
+                    let (sink, stream) = ws_stream.split();
+                    let (tx, rx) = mpsc::channel(self.config.send_buffer.max(1));
+                    tokio::spawn(write_loop(
+                        sink,
+                        rx,
+                        Arc::clone(&self.connected),
+                        Arc::clone(&self.stats),
+                        self.config.redact_logs,
+                    ));
+
+                    *self.current_endpoint.write().await = index;
+                    *self.sender.lock().await = Some(tx);
+                    *self.stream.write().await = Some(stream);
+                    *self.connected.write().await = true;
+                    *self.reconnect_count.write().await = 0;
+                    self.chunks
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .clear();
+
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!("Connection to {} failed: {}", url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        let error = last_error.unwrap_or(Error::NotConnected);
+        self.record_error(&error);
+        Err(error)
+    }
+
+    /// Build the upgrade request for `url`, offering the configured subprotocols.
+    #[allow(clippy::result_large_err)]
+    fn upgrade_request(&self, url: &str) -> Result<Request> {
+        let mut request = url.into_client_request()?;
+
+        if !self.config.subprotocols.is_empty() {
+            let protocols = self.config.subprotocols.join(", ");
+            let value = HeaderValue::from_str(&protocols).map_err(|_| {
+                Error::InvalidConfig(format!("Invalid WebSocket subprotocols: {}", protocols))
+            })?;
+            request.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, value);
+        }
+
+        let user_agent = match self.config.user_agent {
+            Some(ref user_agent) => user_agent.clone(),
+            None => format!("starlight-rust-sdk/{}", crate::SDK_VERSION),
+        };
+        let value = HeaderValue::from_str(&user_agent)
+            .map_err(|_| Error::InvalidConfig(format!("Invalid User-Agent: {}", user_agent)))?;
+        request.headers_mut().insert(USER_AGENT, value);
+
+        Ok(request)
+    }
+
+    /// Open a WebSocket to a single endpoint.
+    async fn dial(&self, url: &str) -> Result<(WsStream, Response)> {
+        let ws_config = Some(self.config.websocket_config());
+        let request = self.upgrade_request(url)?;
+
+        let custom_tcp = self.config.proxy.is_some()
+            || self.config.local_bind_addr.is_some()
+            || self.config.prefer_ipv6
+            || !self.config.resolve_overrides.is_empty();
+        if custom_tcp {
+            return self.dial_tcp(url, request, ws_config).await;
+        }
+
+        #[cfg(feature = "tls")]
+        if let Some(ref tls) = self.config.tls {
+            let connector = tokio_tungstenite::Connector::Rustls(tls.build()?);
+            return Ok(tokio_tungstenite::connect_async_tls_with_config(
+                request,
+                ws_config,
+                false,
+                Some(connector),
+            )
+            .await?);
+        }
+
+        Ok(connect_async_with_config(request, ws_config, false).await?)
+    }
+
+    /// Open a WebSocket over a TCP stream we set up ourselves: through the
+    /// proxy, or directly honoring `local_bind_addr`, `prefer_ipv6` and
+    /// `resolve_overrides`.
+    async fn dial_tcp(
+        &self,
+        url: &str,
+        request: Request,
+        ws_config: Option<WebSocketConfig>,
+    ) -> Result<(WsStream, Response)> {
+        let uri = request.uri();
+        let secure = uri.scheme_str() == Some("wss");
+        let host = uri
+            .host()
+            .ok_or_else(|| Error::InvalidConfig(format!("Hub URL has no host: {}", url)))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+
+        let tcp = match self.config.proxy {
+            Some(ref proxy) => proxy.connect(&host, port).await?,
+            None => self.connect_tcp(&host, port).await?,
+        };
+
+        #[cfg(feature = "tls")]
+        {
+            let connector = match self.config.tls {
+                Some(ref tls) => Some(tokio_tungstenite::Connector::Rustls(tls.build()?)),
+                None => None,
+            };
+            Ok(
+                tokio_tungstenite::client_async_tls_with_config(request, tcp, ws_config, connector)
+                    .await?,
+            )
+        }
+
+        #[cfg(not(feature = "tls"))]
+        {
+            if secure {
+                return Err(Error::InvalidConfig(
+                    "wss:// over a custom connection requires the `tls` feature".to_string(),
+                ));
+            }
+            Ok(tokio_tungstenite::client_async_with_config(
+                request,
+                MaybeTlsStream::Plain(tcp),
+                ws_config,
+            )
+            .await?)
+        }
+    }
+
+    /// Resolve the Hub host and connect to the first address that accepts,
+    /// preferred address family first.
+    async fn connect_tcp(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let overridden = self
+            .config
+            .resolve_overrides
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(host));
+        let mut addrs: Vec<SocketAddr> = match overridden {
+            Some((_, &ip)) => {
+                debug!("Resolving {} to {} (override)", host, ip);
+                vec![SocketAddr::new(ip, port)]
+            }
+            None => tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|e| Error::Connection(WsError::Io(e)))?
+                .collect(),
+        };
+        if let Some(local) = self.config.local_bind_addr {
+            addrs.retain(|addr| addr.is_ipv6() == local.is_ipv6());
+        }
+        // Stable, so the resolver's order is kept within each family.
+        addrs.sort_by_key(|addr| addr.is_ipv6() != self.config.prefer_ipv6);
+
+        let mut last_error = None;
+        for addr in addrs {
+            match connect_from(self.config.local_bind_addr, addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    debug!("Connecting to {} failed: {}", addr, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(match last_error {
+            Some(e) => Error::Connection(WsError::Io(e)),
+            None => Error::InvalidConfig(format!(
+                "No usable address for {} (local bind address {:?})",
+                host, self.config.local_bind_addr
+            )),
+        })
+    }
+
+    /// Get the URL of the currently active (or last attempted) Hub endpoint.
+    ///
+    /// `None` when no Hub URLs are configured.
+    pub async fn current_url(&self) -> Option<String> {
+        self.config
+            .urls
+            .get(*self.current_endpoint.read().await)
+            .cloned()
+    }
+
+    /// The subprotocol the Hub selected during the last successful connect.
+    ///
+    /// `None` when no subprotocols were offered or the Hub didn't pick one.
+    pub fn negotiated_protocol(&self) -> Option<String> {
+        self.negotiated_protocol
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
     }
 
     /// Check if connected to Hub.
@@ -100,45 +751,286 @@ impl WebSocketClient {
 
     /// Send a message to the Hub.
     pub async fn send(&self, message: &str) -> Result<()> {
-        let mut stream_guard = self.stream.write().await;
+        self.send_message(Message::Text(message.to_string())).await
+    }
 
-        if let Some(ref mut stream) = *stream_guard {
-            stream.send(Message::Text(message.to_string())).await?;
-            debug!("Sent: {}", message);
-            Ok(())
-        } else {
-            Err(Error::NotConnected)
+    /// Send a raw WebSocket frame to the Hub.
+    ///
+    /// The frame is queued for the writer task. If the queue stays full for
+    /// longer than `ClientConfig::send_timeout`, fails with `Error::Timeout`.
+    /// JSON text frames are subject to the configured rate limits.
+    pub async fn send_message(&self, message: Message) -> Result<()> {
+        if !self.admit(&message)? {
+            return Ok(());
         }
+        self.enqueue(message).await
     }
 
-    /// Send a typed message (serializes to JSON).
+    /// Send a frame, retrying after transient write errors.
+    ///
+    /// Errors that [`Error::is_retryable`] accepts, such as a full outbound
+    /// queue, are retried up to `retries` times with `backoff` between
+    /// attempts; if the connection was lost meanwhile and `auto_reconnect`
+    /// is on, it is reconnected first. Other errors fail straight away.
+    /// Each attempt waits until the frame is written, so a write that fails
+    /// on a dropped connection is retried too.
+    /// JSON text is re-encoded for the configured codec.
+    pub async fn send_with_retry(
+        &self,
+        message: Message,
+        retries: u32,
+        mut backoff: impl BackoffStrategy,
+    ) -> Result<()> {
+        transport::send_with_retry(
+            self,
+            message,
+            retries,
+            &mut backoff,
+            self.config.auto_reconnect,
+            self.config.delay.as_deref(),
+        )
+        .await
+    }
+
+    /// Check a frame against the rate limits.
+    ///
+    /// Returns `Ok(false)` if it should be dropped. Only single JSON-RPC
+    /// messages in text frames are limited.
+    #[allow(clippy::result_large_err)]
+    fn admit(&self, message: &Message) -> Result<bool> {
+        if self.config.rate_limits.is_empty() && self.config.hijack_coalesce_window.is_none() {
+            return Ok(true);
+        }
+        let Message::Text(text) = message else {
+            return Ok(true);
+        };
+        let Ok(frame) = serde_json::from_str::<serde_json::Value>(text) else {
+            return Ok(true);
+        };
+        let Some(method) = frame["method"].as_str() else {
+            return Ok(true);
+        };
+
+        let admitted = self
+            .limiter
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .admit(
+                method,
+                frame["params"]["reason"].as_str(),
+                self.config.rate_limits.get(method).copied(),
+                self.config.hijack_coalesce_window,
+            );
+        if admitted {
+            return Ok(true);
+        }
+
+        match self.config.rate_limit_action {
+            RateLimitAction::Drop => {
+                warn!("Rate limit exceeded for {}, dropping message", method);
+                Ok(false)
+            }
+            RateLimitAction::Error => Err(Error::RateLimited(method.to_string())),
+        }
+    }
+
+    /// Queue a frame for the writer task.
+    async fn enqueue(&self, message: Message) -> Result<()> {
+        self.enqueue_with(message, None).await
+    }
+
+    /// Queue a frame and wait until the writer has written it.
+    ///
+    /// Fails if the write fails or the connection is lost first, so the
+    /// caller knows the frame never left.
+    async fn enqueue_confirmed(&self, message: Message) -> Result<()> {
+        let (written, result) = oneshot::channel();
+        self.enqueue_with(message, Some(written)).await?;
+        match result.await {
+            Ok(result) => result,
+            Err(_) => Err(Error::closed(
+                "Connection lost before the frame was written",
+            )),
+        }
+    }
+
+    async fn enqueue_with(
+        &self,
+        message: Message,
+        written: Option<oneshot::Sender<Result<()>>>,
+    ) -> Result<()> {
+        let sender = self.sender.lock().await.clone();
+        let Some(sender) = sender else {
+            return Err(Error::NotConnected);
+        };
+
+        let queued = sender.send((message, written));
+        match tokio::time::timeout(self.config.send_timeout, queued).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(Error::ChannelError),
+            Err(_) => {
+                warn!(
+                    "Outbound queue full for {:?}, dropping message",
+                    self.config.send_timeout
+                );
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Send a typed message, encoded with the configured codec.
     pub async fn send_json<T: serde::Serialize>(&self, message: &T) -> Result<()> {
-        let json = serde_json::to_string(message)?;
-        self.send(&json).await
+        self.send_message(self.config.codec.encode(message)?).await
+    }
+
+    /// Send a `method` notification with params `payload` as several frames.
+    ///
+    /// The params are serialized to JSON text and sent in slices of at most
+    /// `chunk_size` bytes, for payloads such as screenshots that are too
+    /// large for one comfortable frame. See [`chunk`](crate::chunk) for the
+    /// frames sent and how the Hub reassembles them.
+    pub async fn send_chunked<T: serde::Serialize>(
+        &self,
+        method: &str,
+        payload: &T,
+        chunk_size: usize,
+    ) -> Result<()> {
+        if chunk_size == 0 {
+            return Err(Error::InvalidConfig(
+                "chunk_size must be at least 1".to_string(),
+            ));
+        }
+        let text = serde_json::to_string(payload)?;
+        let parts = chunk::split(&text, chunk_size);
+        let chunk_id = Uuid::new_v4().to_string();
+        let total_chunks = parts.len() as u32;
+
+        for (index, data) in parts.into_iter().enumerate() {
+            let params = ChunkParams {
+                chunk_id: chunk_id.clone(),
+                chunk_index: index as u32,
+                total_chunks,
+                data: data.to_string(),
+            };
+            self.send_json(&JsonRpcNotification::new(methods::CHUNK, params))
+                .await?;
+        }
+        let end = ChunkEndParams {
+            chunk_id,
+            method: method.to_string(),
+            total_chunks,
+        };
+        self.send_json(&JsonRpcNotification::new(methods::CHUNK_END, end))
+            .await
+    }
+
+    /// Send several messages as a single JSON-RPC batch frame.
+    ///
+    /// The Hub may answer with a batch of its own; use [`receive_many`](Self::receive_many)
+    /// to read it and match responses to requests by id.
+    pub async fn send_batch<T: serde::Serialize>(&self, requests: &[T]) -> Result<()> {
+        self.send_message(self.config.codec.encode(requests)?).await
     }
 
     /// Receive a message from the Hub.
+    ///
+    /// If the Hub sent a batch, its elements are returned one per call, in order.
+    ///
+    /// Cancel-safe: if the future is dropped before completing, no message
+    /// is lost.
     pub async fn receive(&self) -> Result<Option<RawMessage>> {
+        let mut pending = self.pending.lock().await;
+        if pending.is_empty() {
+            pending.extend(self.read_next().await?);
+        }
+        Ok(pending.pop_front())
+    }
+
+    /// The most recent connection, receive, or reconnect error, if any.
+    ///
+    /// Useful for finding out why a Sentinel's run loop exited. The error
+    /// is kept after a successful reconnect.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Remember `error` for [`last_error`](Self::last_error).
+    fn record_error(&self, error: &Error) {
+        *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(error.to_string());
+    }
+
+    /// Receive every message in the next frame from the Hub.
+    ///
+    /// A single message yields a one-element vector, a batch yields its
+    /// elements in order, and control frames yield an empty vector. Messages
+    /// left over from a batch partially consumed by [`receive`](Self::receive)
+    /// are returned first.
+    pub async fn receive_many(&self) -> Result<Vec<RawMessage>> {
+        let mut pending = self.pending.lock().await;
+        if !pending.is_empty() {
+            return Ok(pending.drain(..).collect());
+        }
+
+        self.read_next().await
+    }
+
+    /// Read the next frame, recording any error for [`last_error`](Self::last_error).
+    ///
+    /// Chunks are held back until their message is complete.
+    async fn read_next(&self) -> Result<Vec<RawMessage>> {
+        match self.read_frame().await {
+            Ok(messages) => Ok(self
+                .chunks
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .accept(messages)),
+            Err(e) => {
+                self.record_error(&e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Read and decode the next frame from the stream.
+    async fn read_frame(&self) -> Result<Vec<RawMessage>> {
         let mut stream_guard = self.stream.write().await;
 
         if let Some(ref mut stream) = *stream_guard {
-            match stream.next().await {
+            let next = stream.next().await;
+            if let Some(Ok(ref message)) = next {
+                self.stats.received(message);
+            }
+            match next {
                 Some(Ok(Message::Text(text))) => {
-                    debug!("Received: {}", text);
-                    let msg: RawMessage = serde_json::from_str(&text)?;
-                    Ok(Some(msg))
+                    debug!("Received: {}", loggable(&text, self.config.redact_logs));
+                    decode_frame(&text)
                 }
-                Some(Ok(Message::Close(_))) => {
-                    warn!("Connection closed by Hub");
+                #[cfg(feature = "msgpack")]
+                Some(Ok(Message::Binary(data))) if self.config.codec == Codec::MessagePack => {
+                    debug!("Received {} byte MessagePack frame", data.len());
+                    crate::codec::decode_binary(&data)
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    let error = Error::closed_by_hub(frame);
+                    warn!("{}", error);
                     *self.connected.write().await = false;
-                    Err(Error::ConnectionClosed("Closed by Hub".to_string()))
+                    Err(error)
                 }
                 Some(Ok(Message::Ping(data))) => {
                     // Respond to ping with pong
-                    stream.send(Message::Pong(data)).await?;
-                    Ok(None)
+                    drop(stream_guard);
+                    self.send_message(Message::Pong(data)).await?;
+                    Ok(Vec::new())
+                }
+                Some(Ok(_)) => Ok(Vec::new()), // Ignore other message types
+                Some(Err(WsError::Capacity(CapacityError::MessageTooLong { size, max_size }))) => {
+                    error!("Inbound message too large ({} > {} bytes)", size, max_size);
+                    *self.connected.write().await = false;
+                    Err(Error::closed("message too large"))
                 }
-                Some(Ok(_)) => Ok(None), // Ignore other message types
                 Some(Err(e)) => {
                     error!("WebSocket error: {}", e);
                     *self.connected.write().await = false;
@@ -146,7 +1038,7 @@ impl WebSocketClient {
                 }
                 None => {
                     *self.connected.write().await = false;
-                    Err(Error::ConnectionClosed("Stream ended".to_string()))
+                    Err(Error::closed("Stream ended"))
                 }
             }
         } else {
@@ -156,10 +1048,27 @@ impl WebSocketClient {
 
     /// Attempt to reconnect with exponential backoff.
     pub async fn reconnect(&self) -> Result<()> {
-        let mut delay = self.config.reconnect_delay_ms;
+        let mut default_backoff = ExponentialBackoff::new(
+            Duration::from_millis(self.config.reconnect_delay_ms),
+            Duration::from_millis(self.config.max_reconnect_delay_ms),
+        );
+        if let Some(ref backoff) = self.config.backoff {
+            lock_backoff(backoff).reset();
+        }
+
         let mut attempts = 0;
+        let started = Instant::now();
 
         loop {
+            if let Some(budget) = self.config.max_reconnect_elapsed {
+                if started.elapsed() >= budget {
+                    error!("Reconnect budget of {:?} exhausted", budget);
+                    let error = Error::closed("reconnect budget exhausted");
+                    self.record_error(&error);
+                    return Err(error);
+                }
+            }
+
             attempts += 1;
             *self.reconnect_count.write().await = attempts;
 
@@ -170,22 +1079,31 @@ impl WebSocketClient {
                     "Max reconnection attempts ({}) exceeded",
                     self.config.max_reconnect_attempts
                 );
-                return Err(Error::ConnectionClosed(
-                    "Max reconnection attempts exceeded".to_string(),
-                ));
+                let error = Error::closed("Max reconnection attempts exceeded");
+                self.record_error(&error);
+                return Err(error);
             }
 
-            info!("Reconnection attempt {} (delay: {}ms)", attempts, delay);
-            sleep(Duration::from_millis(delay)).await;
+            let delay = match self.config.backoff {
+                Some(ref backoff) => lock_backoff(backoff).next_delay(attempts),
+                None => default_backoff.next_delay(attempts),
+            };
+            info!("Reconnection attempt {} (delay: {:?})", attempts, delay);
+            match self.config.delay {
+                Some(ref waiter) => waiter.sleep(delay).await,
+                None => sleep(delay).await,
+            }
 
             match self.connect().await {
                 Ok(()) => {
                     info!("Reconnected successfully after {} attempts", attempts);
+                    if self.config.reset_stats_on_reconnect {
+                        self.stats.reset();
+                    }
                     return Ok(());
                 }
                 Err(e) => {
                     warn!("Reconnection failed: {}", e);
-                    delay = (delay * 2).min(self.config.max_reconnect_delay_ms);
                 }
             }
         }
@@ -193,13 +1111,16 @@ impl WebSocketClient {
 
     /// Close the connection.
     pub async fn close(&self) -> Result<()> {
-        let mut stream_guard = self.stream.write().await;
-
-        if let Some(ref mut stream) = *stream_guard {
-            stream.close(None).await?;
+        // Dropping the sender lets the writer flush the close frame and exit.
+        if let Some(sender) = self.sender.lock().await.take() {
+            let _ = sender.send((Message::Close(None), None)).await;
         }
 
-        *stream_guard = None;
+        // A reader blocked in `receive` holds the read half; it sees the
+        // Hub's close reply and ends on its own, so don't wait for it here.
+        if let Ok(mut stream) = self.stream.try_write() {
+            *stream = None;
+        }
         *self.connected.write().await = false;
 
         info!("Connection closed");
@@ -210,6 +1131,162 @@ impl WebSocketClient {
     pub async fn reconnect_count(&self) -> u32 {
         *self.reconnect_count.read().await
     }
+
+    /// Bytes and frames sent and received so far.
+    ///
+    /// Cumulative over reconnects unless
+    /// [`ClientConfig::with_stats_reset_on_reconnect`] is set.
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats.snapshot()
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for WebSocketClient {
+    async fn send(&self, message: Message) -> Result<()> {
+        if !self.admit(&message)? {
+            return Ok(());
+        }
+        // The Sentinel produces JSON text; re-encode it for other codecs.
+        self.enqueue(self.config.codec.transcode(message)?).await
+    }
+
+    async fn send_decision(&self, message: Message) -> Result<()> {
+        self.enqueue(self.config.codec.transcode(message)?).await
+    }
+
+    async fn send_confirmed(&self, message: Message) -> Result<()> {
+        if !self.admit(&message)? {
+            return Ok(());
+        }
+        self.enqueue_confirmed(self.config.codec.transcode(message)?)
+            .await
+    }
+
+    async fn recv(&self) -> Result<Option<RawMessage>> {
+        self.receive().await
+    }
+
+    async fn reconnect(&self) -> Result<()> {
+        WebSocketClient::reconnect(self).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        WebSocketClient::close(self).await
+    }
+
+    async fn is_connected(&self) -> bool {
+        WebSocketClient::is_connected(self).await
+    }
+
+    async fn url(&self) -> Option<String> {
+        self.current_url().await
+    }
+
+    fn should_reconnect(&self, error: &Error) -> bool {
+        match error.close_code() {
+            Some(code) => !self.config.is_non_retryable_close(code),
+            None => error.is_retryable(),
+        }
+    }
+}
+
+/// `text` as it should appear in logs, masked if `redact` is set.
+fn loggable(text: &str, redact: bool) -> Cow<'_, str> {
+    if !redact {
+        return Cow::Borrowed(text);
+    }
+    let Ok(mut frame) = serde_json::from_str::<serde_json::Value>(text) else {
+        return Cow::Borrowed(text);
+    };
+    if mask_secrets(&mut frame) {
+        Cow::Owned(frame.to_string())
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Mask every `screenshot` and `auth_token` string in `value`, returning
+/// whether there were any.
+fn mask_secrets(value: &mut serde_json::Value) -> bool {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(fields) => {
+            let mut masked = false;
+            for (key, value) in fields.iter_mut() {
+                let replacement = match (key.as_str(), &*value) {
+                    ("screenshot", Value::String(data)) => {
+                        Some(format!("<redacted {} bytes>", data.len()))
+                    }
+                    ("auth_token", Value::String(_)) => Some("<redacted>".to_string()),
+                    _ => None,
+                };
+                match replacement {
+                    Some(replacement) => {
+                        *value = Value::String(replacement);
+                        masked = true;
+                    }
+                    None => masked |= mask_secrets(value),
+                }
+            }
+            masked
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .fold(false, |masked, item| mask_secrets(item) | masked),
+        _ => false,
+    }
+}
+
+/// Connect to `addr`, binding the socket to `local` first if given.
+async fn connect_from(local: Option<SocketAddr>, addr: SocketAddr) -> std::io::Result<TcpStream> {
+    let socket = if addr.is_ipv6() {
+        TcpSocket::new_v6()?
+    } else {
+        TcpSocket::new_v4()?
+    };
+    if let Some(local) = local {
+        socket.bind(local)?;
+    }
+    socket.connect(addr).await
+}
+
+/// Write queued frames to the socket until the queue is closed or a write fails.
+/// A frame for the writer task, with where to report whether it was written.
+type Outbound = (Message, Option<oneshot::Sender<Result<()>>>);
+
+async fn write_loop(
+    mut sink: SplitSink<WsStream, Message>,
+    mut queue: mpsc::Receiver<Outbound>,
+    connected: Arc<RwLock<bool>>,
+    stats: Arc<StatsCounters>,
+    redact_logs: bool,
+) {
+    while let Some((message, written)) = queue.recv().await {
+        match message {
+            Message::Text(ref text) => debug!("Sent: {}", loggable(text, redact_logs)),
+            _ => debug!("Sent: {}", message),
+        }
+        // Counted once written, so frames that never leave aren't.
+        let counted = (!matches!(message, Message::Close(_))).then(|| message.clone());
+        if let Err(e) = sink.send(message).await {
+            error!("WebSocket write failed: {}", e);
+            *connected.write().await = false;
+            if let Some(written) = written {
+                let _ = written.send(Err(Error::Connection(e)));
+            }
+            return;
+        }
+        if let Some(ref message) = counted {
+            stats.sent(message);
+        }
+        if let Some(written) = written {
+            let _ = written.send(Ok(()));
+        }
+    }
+
+    let _ = sink.close().await;
 }
 
 impl Clone for WebSocketClient {
@@ -220,6 +1297,776 @@ impl Clone for WebSocketClient {
             sender: Arc::clone(&self.sender),
             connected: Arc::clone(&self.connected),
             reconnect_count: Arc::clone(&self.reconnect_count),
+            current_endpoint: Arc::clone(&self.current_endpoint),
+            pending: Arc::clone(&self.pending),
+            last_error: Arc::clone(&self.last_error),
+            negotiated_protocol: Arc::clone(&self.negotiated_protocol),
+            limiter: Arc::clone(&self.limiter),
+            stats: Arc::clone(&self.stats),
+            chunks: Arc::clone(&self.chunks),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Id, JsonRpcRequest};
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+    type ServerStream = WebSocketStream<TcpStream>;
+
+    /// Start a WebSocket server that runs `session` for every connection.
+    async fn spawn_hub_with<F, Fut>(session: F) -> String
+    where
+        F: Fn(ServerStream) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let session = Arc::new(session);
+
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let session = Arc::clone(&session);
+                tokio::spawn(async move {
+                    if let Ok(ws) = tokio_tungstenite::accept_async(tcp).await {
+                        session(ws).await;
+                    }
+                });
+            }
+        });
+
+        format!("ws://{}", addr)
+    }
+
+    /// Start a WebSocket server that accepts connections and holds them open.
+    async fn spawn_hub() -> String {
+        spawn_hub_with(|mut ws| async move { while let Some(Ok(_)) = ws.next().await {} }).await
+    }
+
+    /// Get a URL on which nothing is listening.
+    async fn dead_url() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        format!("ws://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_live_endpoint() {
+        let dead = dead_url().await;
+        let live = spawn_hub().await;
+
+        let client = WebSocketClient::new(ClientConfig::new(&dead).with_url(&live));
+        client.connect().await.unwrap();
+
+        assert!(client.is_connected().await);
+        assert_eq!(client.current_url().await, Some(live));
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_single_url_field() {
+        let live = spawn_hub().await;
+        let config = ClientConfig {
+            url: live.clone(),
+            ..Default::default()
+        };
+        let client = WebSocketClient::new(config);
+        client.connect().await.unwrap();
+        assert_eq!(client.current_url().await, Some(live));
+    }
+
+    #[tokio::test]
+    async fn test_no_urls() {
+        let client = WebSocketClient::new(ClientConfig::default().with_urls(Vec::<String>::new()));
+        assert_eq!(client.current_url().await, None);
+        assert_eq!(Transport::url(&client).await, None);
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_batch_round_trip() {
+        // Answers each batch with one response per request, in reverse order.
+        let url = spawn_hub_with(|mut ws| async move {
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let requests: Vec<serde_json::Value> = serde_json::from_str(&text).unwrap();
+                let responses: Vec<_> = requests
+                    .iter()
+                    .rev()
+                    .map(|r| serde_json::json!({ "jsonrpc": "2.0", "result": r["params"], "id": r["id"] }))
+                    .collect();
+                let reply = serde_json::to_string(&responses).unwrap();
+                let _ = ws.send(Message::Text(reply)).await;
+            }
+        })
+        .await;
+
+        let client = WebSocketClient::new(ClientConfig::new(url));
+        client.connect().await.unwrap();
+
+        let requests: Vec<_> = (1..=3)
+            .map(|n| {
+                JsonRpcRequest::new("starlight.action", serde_json::json!({ "n": n }), n as i64)
+            })
+            .collect();
+        client.send_batch(&requests).await.unwrap();
+
+        let responses: HashMap<Id, RawMessage> = client
+            .receive_many()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.id.clone().unwrap(), r))
+            .collect();
+        assert_eq!(responses.len(), 3);
+        for request in &requests {
+            let response = &responses[&request.id];
+            assert!(response.is_response());
+            assert_eq!(response.result.as_ref(), Some(&request.params));
+        }
+
+        // The same batch read through `receive` arrives one message at a time, in order.
+        client.send_batch(&requests).await.unwrap();
+        for expected in [3, 2, 1] {
+            let msg = client.receive().await.unwrap().unwrap();
+            assert_eq!(msg.id, Some(Id::Num(expected)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunked_round_trip() {
+        // Echoes every frame back.
+        let url = spawn_hub_with(|mut ws| async move {
+            while let Some(Ok(message)) = ws.next().await {
+                if message.is_text() && ws.send(message).await.is_err() {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        let client = WebSocketClient::new(ClientConfig::new(url));
+        client.connect().await.unwrap();
+
+        let payload = serde_json::json!({ "image": "A".repeat(1024 * 1024), "format": "png" });
+        client
+            .send_chunked("starlight.screenshot", &payload, 64 * 1024)
+            .await
+            .unwrap();
+
+        let message = loop {
+            if let Some(message) = client.receive().await.unwrap() {
+                break message;
+            }
+        };
+        assert_eq!(message.method, "starlight.screenshot");
+        assert_eq!(message.params, payload);
+        assert!(client.stats().text_frames > 16);
+    }
+
+    #[tokio::test]
+    async fn test_logs_redact_screenshots_and_tokens() {
+        use std::io::Write;
+
+        #[derive(Clone, Default)]
+        struct Captured(Arc<StdMutex<Vec<u8>>>);
+
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let blob = "iVBORw0KGgo".repeat(1000);
+        let url = spawn_hub_with({
+            let blob = blob.clone();
+            move |mut ws| {
+                let frame = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "starlight.pre_check",
+                    "params": { "command": "click", "screenshot": blob },
+                    "id": 1,
+                });
+                async move {
+                    let _ = ws.send(Message::Text(frame.to_string())).await;
+                    while let Some(Ok(_)) = ws.next().await {}
+                }
+            }
+        })
+        .await;
+
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer({
+                let captured = captured.clone();
+                move || captured.clone()
+            })
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let client = WebSocketClient::new(ClientConfig::new(url));
+        client.connect().await.unwrap();
+        let message = client.receive().await.unwrap().unwrap();
+        assert_eq!(message.params["screenshot"], blob.as_str());
+
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("Received:"), "{}", logs);
+        assert!(logs.contains(&format!("<redacted {} bytes>", blob.len())));
+        assert!(!logs.contains(&blob));
+
+        let registration =
+            r#"{"method":"starlight.registration","params":{"auth_token":"eyJhbGciOi"}}"#;
+        assert_eq!(
+            loggable(registration, true),
+            r#"{"method":"starlight.registration","params":{"auth_token":"<redacted>"}}"#
+        );
+        assert_eq!(loggable(registration, false), registration);
+    }
+
+    #[tokio::test]
+    async fn test_all_endpoints_dead() {
+        let config = ClientConfig::new(dead_url().await).with_url(dead_url().await);
+        let client = WebSocketClient::new(config);
+
+        assert!(matches!(client.connect().await, Err(Error::Connection(_))));
+        assert!(!client.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn test_last_error_recorded() {
+        let client = WebSocketClient::new(ClientConfig::new(dead_url().await));
+        assert_eq!(client.last_error(), None);
+
+        let err = client.connect().await.unwrap_err();
+        assert_eq!(client.last_error(), Some(err.to_string()));
+        assert!(client
+            .last_error()
+            .unwrap()
+            .starts_with("WebSocket connection error"));
+
+        assert!(client.receive().await.is_err());
+        assert_eq!(client.last_error().as_deref(), Some("Not connected to Hub"));
+    }
+
+    #[tokio::test]
+    async fn test_send_times_out_when_hub_stops_reading() {
+        // The Hub never reads, so the socket buffers and then the queue fill up.
+        let url = spawn_hub_with(|ws| async move {
+            sleep(Duration::from_secs(30)).await;
+            drop(ws);
+        })
+        .await;
+
+        let config = ClientConfig::new(url).with_send_buffer(1, Duration::from_millis(100));
+        let client = WebSocketClient::new(config);
+        client.connect().await.unwrap();
+
+        let payload = "x".repeat(1024 * 1024);
+        let mut result = Ok(());
+        let mut queued = 0;
+        for _ in 0..256 {
+            result = client.send(&payload).await;
+            if result.is_err() {
+                break;
+            }
+            queued += 1;
         }
+        assert!(matches!(result, Err(Error::Timeout)), "{:?}", result);
+
+        // The rejected frame, and any still queued, were never sent.
+        let stats = client.stats();
+        assert!(stats.text_frames < queued, "{:?}", stats);
+        assert!(stats.bytes_sent < queued * payload.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_after_hub_drops_connection() {
+        use crate::backoff::ConstantBackoff;
+        use std::sync::atomic::AtomicUsize;
+
+        // The first connection takes one frame and then closes; later ones
+        // take everything.
+        let connections = Arc::new(AtomicUsize::new(0));
+        let (frames_tx, mut frames) = mpsc::unbounded_channel();
+        let url = spawn_hub_with(move |mut ws| {
+            let first = connections.fetch_add(1, Ordering::SeqCst) == 0;
+            let frames_tx = frames_tx.clone();
+            async move {
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let _ = frames_tx.send(text);
+                    if first {
+                        let _ = ws.close(None).await;
+                    }
+                }
+            }
+        })
+        .await;
+
+        let config = ClientConfig {
+            reconnect_delay_ms: 10,
+            ..ClientConfig::new(url)
+        };
+        let client = WebSocketClient::new(config);
+        client.connect().await.unwrap();
+        client.send("action 1").await.unwrap();
+        assert_eq!(frames.recv().await.unwrap(), "action 1");
+        assert!(client.receive().await.is_err());
+
+        // The write fails on the closed connection; the frame is sent
+        // again after reconnecting.
+        let backoff = ConstantBackoff::new(Duration::from_millis(10));
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            client.send_with_retry(Message::Text("action 2".into()), 3, backoff),
+        )
+        .await
+        .expect("retry should finish")
+        .unwrap();
+        let frame = tokio::time::timeout(Duration::from_secs(1), frames.recv())
+            .await
+            .expect("action 2 should arrive after the reconnect");
+        assert_eq!(frame.unwrap(), "action 2");
+        assert!(client.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn test_hijacks_rate_limited_and_coalesced() {
+        use crate::messages::{methods, HijackParams, JsonRpcNotification};
+
+        let (count_tx, mut count_rx) = mpsc::unbounded_channel();
+        let url = spawn_hub_with(move |mut ws| {
+            let count_tx = count_tx.clone();
+            async move {
+                let mut hijacks = 0;
+                while let Some(Ok(message)) = ws.next().await {
+                    if message
+                        .to_text()
+                        .unwrap_or_default()
+                        .contains(methods::HIJACK)
+                    {
+                        hijacks += 1;
+                    }
+                }
+                let _ = count_tx.send(hijacks);
+            }
+        })
+        .await;
+        let hijack =
+            |reason: String| JsonRpcNotification::new(methods::HIJACK, HijackParams { reason });
+
+        // Token bucket: a burst of 5, then nothing for a minute.
+        let config = ClientConfig::new(url.clone())
+            .with_rate_limit(methods::HIJACK, RateLimit::new(5, Duration::from_secs(60)));
+        let client = WebSocketClient::new(config);
+        client.connect().await.unwrap();
+        for i in 0..100 {
+            client
+                .send_json(&hijack(format!("popup {}", i)))
+                .await
+                .unwrap();
+        }
+        client.close().await.unwrap();
+        assert_eq!(count_rx.recv().await, Some(5));
+
+        // Coalescing: identical reasons collapse, a new reason goes through.
+        let config = ClientConfig::new(url.clone())
+            .with_hijack_coalescing(Duration::from_secs(60))
+            .with_rate_limit_action(RateLimitAction::Error);
+        let client = WebSocketClient::new(config);
+        client.connect().await.unwrap();
+        client.send_json(&hijack("popup".into())).await.unwrap();
+        for _ in 0..99 {
+            let err = client.send_json(&hijack("popup".into())).await.unwrap_err();
+            assert!(matches!(err, Error::RateLimited(ref method) if method == methods::HIJACK));
+        }
+        client
+            .send_json(&hijack("cookie banner".into()))
+            .await
+            .unwrap();
+        client.close().await.unwrap();
+        assert_eq!(count_rx.recv().await, Some(2));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    async fn test_subprotocol_negotiation() {
+        use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (offered_tx, offered_rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let callback = |request: &Request, mut response: Response| {
+                let offered = request.headers().get(SEC_WEBSOCKET_PROTOCOL).cloned();
+                response.headers_mut().insert(
+                    SEC_WEBSOCKET_PROTOCOL,
+                    HeaderValue::from_static("starlight.v1"),
+                );
+                let _ = offered_tx.send(offered);
+                Ok(response)
+            };
+            let mut ws = tokio_tungstenite::accept_hdr_async(tcp, callback)
+                .await
+                .unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let config = ClientConfig::new(url).with_subprotocols(vec!["starlight.v2", "starlight.v1"]);
+        let client = WebSocketClient::new(config);
+        assert_eq!(client.negotiated_protocol(), None);
+        client.connect().await.unwrap();
+
+        let offered = offered_rx.await.unwrap().unwrap();
+        assert_eq!(offered, "starlight.v2, starlight.v1");
+        assert_eq!(
+            client.negotiated_protocol().as_deref(),
+            Some("starlight.v1")
+        );
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    async fn test_user_agent_header() {
+        use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (agents_tx, mut agents_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let agents_tx = agents_tx.clone();
+                let callback = move |request: &Request, response: Response| {
+                    let agent = request.headers().get(USER_AGENT).cloned();
+                    let _ = agents_tx.send(agent);
+                    Ok(response)
+                };
+                let mut ws = tokio_tungstenite::accept_hdr_async(tcp, callback)
+                    .await
+                    .unwrap();
+                tokio::spawn(async move { while let Some(Ok(_)) = ws.next().await {} });
+            }
+        });
+
+        let client = WebSocketClient::new(ClientConfig::new(url.clone()));
+        client.connect().await.unwrap();
+        let agent = agents_rx.recv().await.unwrap().unwrap();
+        assert_eq!(
+            agent,
+            format!("starlight-rust-sdk/{}", crate::SDK_VERSION).as_str()
+        );
+
+        let client = WebSocketClient::new(ClientConfig::new(url).with_user_agent("janitor/2.1"));
+        client.connect().await.unwrap();
+        assert_eq!(agents_rx.recv().await.unwrap().unwrap(), "janitor/2.1");
+    }
+
+    #[tokio::test]
+    async fn test_close_code_surfaced() {
+        let url = spawn_hub_with(|mut ws| async move {
+            let frame = CloseFrame {
+                code: CloseCode::Policy,
+                reason: "invalid token".into(),
+            };
+            let _ = ws.close(Some(frame)).await;
+            while let Some(Ok(_)) = ws.next().await {}
+        })
+        .await;
+
+        let client = WebSocketClient::new(ClientConfig::new(url));
+        client.connect().await.unwrap();
+
+        let err = client.receive().await.unwrap_err();
+        assert_eq!(err.close_code(), Some(1008));
+        assert_eq!(err.to_string(), "Connection closed (1008): invalid token");
+        assert_eq!(client.last_error(), Some(err.to_string()));
+        assert!(!err.is_retryable());
+        assert!(!client.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn test_connection_stats() {
+        // Echoes each text frame, then pings and sends a binary frame.
+        let url = spawn_hub_with(|mut ws| async move {
+            while let Some(Ok(message)) = ws.next().await {
+                if let Message::Text(text) = message {
+                    let _ = ws.send(Message::Text(text)).await;
+                    let _ = ws.send(Message::Ping(vec![1, 2, 3])).await;
+                    let _ = ws.send(Message::Binary(vec![0; 4])).await;
+                }
+            }
+        })
+        .await;
+
+        let config = ClientConfig {
+            reconnect_delay_ms: 10,
+            ..ClientConfig::new(url)
+        };
+        let client = WebSocketClient::new(config.clone());
+        client.connect().await.unwrap();
+        assert_eq!(client.stats(), ConnectionStats::default());
+
+        let hello = r#"{"jsonrpc":"2.0","method":"hello"}"#;
+        let len = hello.len() as u64;
+        client.send(hello).await.unwrap();
+        assert!(client.receive().await.unwrap().is_some());
+        // The ping (answered with a pong), then the binary frame.
+        assert!(client.receive_many().await.unwrap().is_empty());
+        assert!(client.receive_many().await.unwrap().is_empty());
+        // Sent frames are counted once the writer has written them.
+        while client.stats().pongs == 0 {
+            sleep(Duration::from_millis(1)).await;
+        }
+
+        let expected = ConnectionStats {
+            bytes_sent: len + 3,
+            bytes_received: len + 3 + 4,
+            text_frames: 2,
+            binary_frames: 1,
+            pings: 1,
+            pongs: 1,
+        };
+        assert_eq!(client.stats(), expected);
+
+        // Cumulative over reconnects by default.
+        client.reconnect().await.unwrap();
+        assert_eq!(client.stats(), expected);
+
+        let client = WebSocketClient::new(config.with_stats_reset_on_reconnect());
+        client.connect().await.unwrap();
+        client.send(hello).await.unwrap();
+        while client.stats().bytes_sent == 0 {
+            sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(client.stats().bytes_sent, len);
+        client.reconnect().await.unwrap();
+        assert_eq!(client.stats(), ConnectionStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_elapsed_budget() {
+        let config = ClientConfig {
+            reconnect_delay_ms: 10,
+            max_reconnect_delay_ms: 20,
+            ..ClientConfig::new(dead_url().await)
+        }
+        .with_max_reconnect_elapsed(Duration::from_millis(100));
+        let client = WebSocketClient::new(config);
+
+        let started = Instant::now();
+        match client.reconnect().await {
+            Err(Error::ConnectionClosed { reason, .. }) => {
+                assert_eq!(reason, "reconnect budget exhausted")
+            }
+            other => panic!("expected exhausted budget, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(client.reconnect_count().await > 1);
+        assert_eq!(
+            client.last_error().as_deref(),
+            Some("Connection closed: reconnect budget exhausted")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_uses_injected_delay() {
+        #[derive(Debug, Clone, Default)]
+        struct RecordingDelay(Arc<StdMutex<Vec<Duration>>>);
+
+        #[async_trait::async_trait]
+        impl Delay for RecordingDelay {
+            async fn sleep(&self, duration: Duration) {
+                self.0.lock().unwrap().push(duration);
+            }
+        }
+
+        let delays = RecordingDelay::default();
+        let config = ClientConfig {
+            reconnect_delay_ms: 60_000,
+            max_reconnect_delay_ms: 150_000,
+            max_reconnect_attempts: 3,
+            ..ClientConfig::new(dead_url().await)
+        }
+        .with_delay(delays.clone());
+        let client = WebSocketClient::new(config);
+
+        let started = Instant::now();
+        assert!(client.reconnect().await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            *delays.0.lock().unwrap(),
+            [60, 120, 150].map(Duration::from_secs)
+        );
+    }
+
+    #[test]
+    fn test_non_retryable_close_codes() {
+        let closed = |code| Error::ConnectionClosed {
+            code: Some(code),
+            reason: String::new(),
+        };
+
+        let client = WebSocketClient::new(ClientConfig::default());
+        assert!(!client.should_reconnect(&closed(1008)));
+        assert!(!client.should_reconnect(&closed(4001)));
+        assert!(client.should_reconnect(&closed(1001)));
+        assert!(client.should_reconnect(&Error::closed("Stream ended")));
+
+        let config = ClientConfig::default().with_non_retryable_close_codes(vec![4401..=4401]);
+        let client = WebSocketClient::new(config);
+        assert!(client.should_reconnect(&closed(1008)));
+        assert!(!client.should_reconnect(&closed(4401)));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_message_rejected() {
+        let url = spawn_hub_with(|mut ws| async move {
+            let _ = ws.send(Message::Text("x".repeat(64 * 1024))).await;
+            while let Some(Ok(_)) = ws.next().await {}
+        })
+        .await;
+
+        let client = WebSocketClient::new(ClientConfig::new(url).with_size_limits(1024, 1024));
+        client.connect().await.unwrap();
+
+        match client.receive().await {
+            Err(Error::ConnectionClosed { reason, .. }) => assert_eq!(reason, "message too large"),
+            other => panic!("expected message too large, got {:?}", other),
+        }
+        assert!(!client.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_with_local_bind_addr() {
+        // Skip where the environment has no IPv6 loopback.
+        let Ok(listener) = TcpListener::bind("[::1]:0").await else {
+            return;
+        };
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (peer_tx, peer_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (tcp, peer) = listener.accept().await.unwrap();
+            let _ = peer_tx.send(peer);
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let local_port = std::net::TcpListener::bind("[::1]:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = ClientConfig::new(url)
+            .with_prefer_ipv6()
+            .with_local_bind_addr(SocketAddr::from((
+                std::net::Ipv6Addr::LOCALHOST,
+                local_port,
+            )));
+        let client = WebSocketClient::new(config);
+        client.connect().await.unwrap();
+        assert!(client.is_connected().await);
+
+        let peer = peer_rx.await.unwrap();
+        assert!(peer.is_ipv6());
+        assert_eq!(peer.port(), local_port);
+    }
+
+    #[tokio::test]
+    async fn test_local_bind_addr_family_mismatch() {
+        let url = spawn_hub().await; // IPv4 only
+        let config = ClientConfig::new(url).with_local_bind_addr("[::1]:0".parse().unwrap());
+        let client = WebSocketClient::new(config);
+        let err = client.connect().await.unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    async fn test_resolve_override() {
+        use tokio_tungstenite::tungstenite::handshake::server::{
+            Request as UpgradeRequest, Response as UpgradeResponse,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (host_tx, host_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let record_host = |request: &UpgradeRequest, response: UpgradeResponse| {
+                let _ = host_tx.send(request.headers()["host"].to_str().unwrap().to_string());
+                Ok(response)
+            };
+            let mut ws = tokio_tungstenite::accept_hdr_async(tcp, record_host)
+                .await
+                .unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let url = format!("ws://hub.starlight.invalid:{}", port);
+        let config = ClientConfig::new(url)
+            .with_resolve_override("Hub.Starlight.Invalid", IpAddr::from([127, 0, 0, 1]));
+        let client = WebSocketClient::new(config);
+        client.connect().await.unwrap();
+        assert!(client.is_connected().await);
+        assert_eq!(
+            host_rx.await.unwrap(),
+            format!("hub.starlight.invalid:{}", port)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_http_proxy() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let hub_url = spawn_hub().await;
+        let hub_addr = hub_url.trim_start_matches("ws://").to_string();
+
+        // Minimal CONNECT proxy that records the request head and splices.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_url = format!("http://{}", listener.local_addr().unwrap());
+        let (head_tx, head_rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut inbound, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                head.push(inbound.read_u8().await.unwrap());
+            }
+            let head = String::from_utf8(head).unwrap();
+
+            let target = head.split_whitespace().nth(1).unwrap().to_string();
+            let mut outbound = TcpStream::connect(target).await.unwrap();
+            inbound
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+            let _ = head_tx.send(head);
+            let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+        });
+
+        let config = ClientConfig::new(&hub_url)
+            .with_proxy(ProxyConfig::new(proxy_url).with_credentials("ci", "secret"));
+        let client = WebSocketClient::new(config);
+        client.connect().await.unwrap();
+        assert!(client.is_connected().await);
+
+        let head = head_rx.await.unwrap();
+        assert!(head.starts_with(&format!("CONNECT {} HTTP/1.1\r\n", hub_addr)));
+        assert!(head.contains(&format!("Host: {}\r\n", hub_addr)));
+        assert!(head.contains("Proxy-Authorization: Basic Y2k6c2VjcmV0\r\n"));
     }
 }
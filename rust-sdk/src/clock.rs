@@ -0,0 +1,68 @@
+//! Time sources, injectable so tests can control time.
+//!
+//! `JwtHandler` reads the current time from a [`Clock`] and [`WebSocketClient::reconnect`](crate::WebSocketClient::reconnect)
+//! waits between attempts with a [`Delay`]. The defaults use the system
+//! clock and tokio's timer; tests can swap in fakes to check token expiry
+//! or backoff without real waiting.
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+
+/// The current time, as a Unix timestamp in seconds.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "jwt")] {
+/// use std::sync::atomic::{AtomicI64, Ordering};
+/// use std::sync::Arc;
+/// use starlight::clock::Clock;
+/// use starlight::JwtHandler;
+///
+/// #[derive(Debug)]
+/// struct FixedClock(AtomicI64);
+///
+/// impl Clock for FixedClock {
+///     fn now(&self) -> i64 {
+///         self.0.load(Ordering::SeqCst)
+///     }
+/// }
+///
+/// let handler = JwtHandler::new("secret")
+///     .with_clock(Arc::new(FixedClock(AtomicI64::new(1_700_000_000))));
+/// # }
+/// ```
+pub trait Clock: Debug + Send + Sync {
+    /// Seconds since the Unix epoch.
+    fn now(&self) -> i64;
+}
+
+/// Reads the system clock. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+}
+
+/// Waits for a duration, e.g. between reconnect attempts.
+#[async_trait]
+pub trait Delay: Debug + Send + Sync {
+    /// Complete after `duration`.
+    async fn sleep(&self, duration: Duration);
+}
+
+/// Waits with tokio's timer. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioDelay;
+
+#[async_trait]
+impl Delay for TokioDelay {
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
@@ -0,0 +1,119 @@
+//! Wire encodings for protocol messages.
+
+use serde::Serialize;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::error::Result;
+#[cfg(feature = "msgpack")]
+use crate::messages::RawMessage;
+
+/// How messages are encoded on the WebSocket.
+///
+/// JSON text frames are the default and what the Hub speaks out of the box.
+/// With the `msgpack` feature, [`Codec::MessagePack`] sends binary frames
+/// instead, which are considerably smaller for screenshots and large
+/// context maps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// JSON text frames
+    #[default]
+    Json,
+
+    /// MessagePack binary frames (field names kept, via `rmp-serde`)
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl Codec {
+    /// Encode a message as a WebSocket frame.
+    #[allow(clippy::result_large_err)]
+    pub fn encode<T: Serialize + ?Sized>(&self, message: &T) -> Result<Message> {
+        match self {
+            Codec::Json => Ok(Message::Text(serde_json::to_string(message)?)),
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => Ok(Message::Binary(rmp_serde::to_vec_named(message)?)),
+        }
+    }
+
+    /// Re-encode a frame produced as JSON text by the Sentinel.
+    ///
+    /// Frames that aren't JSON text are passed through unchanged.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn transcode(&self, message: Message) -> Result<Message> {
+        match (self, message) {
+            #[cfg(feature = "msgpack")]
+            (Codec::MessagePack, Message::Text(text)) => {
+                let value: serde_json::Value = serde_json::from_str(&text)?;
+                self.encode(&value)
+            }
+            (_, message) => Ok(message),
+        }
+    }
+}
+
+/// Decode a MessagePack binary frame holding a single message or a batch.
+#[cfg(feature = "msgpack")]
+#[allow(clippy::result_large_err)]
+pub(crate) fn decode_binary(data: &[u8]) -> Result<Vec<RawMessage>> {
+    match rmp_serde::from_slice(data)? {
+        serde_json::Value::Array(items) => items
+            .into_iter()
+            .map(|item| Ok(serde_json::from_value(item)?))
+            .collect(),
+        value => Ok(vec![serde_json::from_value(value)?]),
+    }
+}
+
+#[cfg(all(test, feature = "msgpack"))]
+mod tests {
+    use super::*;
+    use crate::messages::{methods, JsonRpcRequest, PreCheckParams};
+    use serde_json::json;
+
+    #[test]
+    fn test_pre_check_round_trip_through_message_pack() {
+        let params: PreCheckParams = serde_json::from_value(json!({
+            "url": "https://example.com/checkout",
+            "command": "click",
+            "selector": "#pay",
+            "blocking": [{ "selector": ".modal", "element_type": "modal" }],
+            "screenshot": "aGVsbG8=",
+            "context": { "step": 3, "tags": ["a", "b"] }
+        }))
+        .unwrap();
+        let request = JsonRpcRequest::new(methods::PRE_CHECK, params.clone(), "pc-1");
+
+        let frame = Codec::MessagePack.encode(&request).unwrap();
+        let Message::Binary(data) = frame else {
+            panic!("expected a binary frame, got {:?}", frame);
+        };
+        let json_len = serde_json::to_vec(&request).unwrap().len();
+        assert!(data.len() < json_len);
+
+        let mut messages = decode_binary(&data).unwrap();
+        assert_eq!(messages.len(), 1);
+        let msg = messages.remove(0);
+        assert_eq!(msg.method, methods::PRE_CHECK);
+        assert_eq!(msg.id, Some("pc-1".into()));
+
+        let decoded: PreCheckParams = serde_json::from_value(msg.params).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&params).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_transcode_sentinel_frames() {
+        let text =
+            Message::Text(r#"[{"jsonrpc":"2.0","method":"starlight.clear","params":{}}]"#.into());
+
+        assert_eq!(Codec::Json.transcode(text.clone()).unwrap(), text);
+
+        let Message::Binary(data) = Codec::MessagePack.transcode(text).unwrap() else {
+            panic!("expected a binary frame");
+        };
+        let messages = decode_binary(&data).unwrap();
+        assert_eq!(messages[0].method, methods::CLEAR);
+    }
+}
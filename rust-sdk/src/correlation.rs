@@ -0,0 +1,50 @@
+//! Matching Hub responses to the requests that caused them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+use crate::messages::{Id, RawMessage};
+
+/// Requests waiting for a response, keyed by JSON-RPC id.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PendingRequests {
+    waiting: Arc<Mutex<HashMap<Id, oneshot::Sender<RawMessage>>>>,
+}
+
+impl PendingRequests {
+    /// Start waiting for the response to request `id`.
+    pub(crate) fn register(&self, id: Id) -> oneshot::Receiver<RawMessage> {
+        let (tx, rx) = oneshot::channel();
+        self.lock().insert(id, tx);
+        rx
+    }
+
+    /// Stop waiting for `id`, e.g. after a timeout.
+    pub(crate) fn forget(&self, id: &Id) {
+        self.lock().remove(id);
+    }
+
+    /// Hand a response to whoever is waiting for it.
+    ///
+    /// Returns the message back if it isn't a response to a pending request.
+    pub(crate) fn resolve(&self, msg: RawMessage) -> Option<RawMessage> {
+        if !msg.is_response() {
+            return Some(msg);
+        }
+
+        let waiter = msg.id.as_ref().and_then(|id| self.lock().remove(id));
+        match waiter {
+            Some(waiter) => {
+                let _ = waiter.send(msg);
+                None
+            }
+            None => Some(msg),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Id, oneshot::Sender<RawMessage>>> {
+        self.waiting.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
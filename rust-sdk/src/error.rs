@@ -1,6 +1,9 @@
 //! Error types for the Starlight SDK.
 
+use std::time::Duration;
+
 use thiserror::Error;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 
 /// Result type for Starlight SDK operations.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -16,17 +19,50 @@ pub enum Error {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    /// Base64 decoding error
+    #[error("Base64 decode error: {0}")]
+    Base64(#[from] base64::DecodeError),
+
+    /// MessagePack encoding error
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack encode error: {0}")]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
+
+    /// MessagePack decoding error
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack decode error: {0}")]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
+
     /// JWT error
+    #[cfg(feature = "jwt")]
     #[error("JWT error: {0}")]
     Jwt(#[from] jsonwebtoken::errors::Error),
 
+    /// JWT was valid but has been revoked
+    #[error("JWT has been revoked")]
+    TokenRevoked,
+
     /// Protocol error from Hub
-    #[error("Protocol error: {message}")]
+    #[error("Protocol error {code}: {message}")]
     Protocol { code: i32, message: String },
 
+    /// Protocol error from Hub asking us to wait before trying again
+    #[error("Protocol error {code}: {message} (retry after {retry_after:?})")]
+    RetryAfter {
+        code: i32,
+        message: String,
+        retry_after: Duration,
+    },
+
     /// Connection closed unexpectedly
-    #[error("Connection closed: {0}")]
-    ConnectionClosed(String),
+    ///
+    /// `code` is the WebSocket close code when the Hub sent a close frame.
+    #[error("Connection closed{}: {reason}", code.map(|c| format!(" ({})", c)).unwrap_or_default())]
+    ConnectionClosed { code: Option<u16>, reason: String },
+
+    /// Registration with the Hub failed, e.g. incompatible protocol versions
+    #[error("Handshake failed: {0}")]
+    Handshake(String),
 
     /// Timeout waiting for response
     #[error("Timeout waiting for response")]
@@ -44,7 +80,307 @@ pub enum Error {
     #[error("Sentinel already running")]
     AlreadyRunning,
 
+    /// Proxy tunnel error
+    #[error("Proxy error: {0}")]
+    Proxy(String),
+
+    /// Outbound message exceeded its rate limit (holds the method)
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+
     /// Channel send error
     #[error("Internal channel error")]
     ChannelError,
 }
+
+impl Error {
+    /// Create a `ConnectionClosed` error without a close code.
+    pub(crate) fn closed(reason: impl Into<String>) -> Self {
+        Error::ConnectionClosed {
+            code: None,
+            reason: reason.into(),
+        }
+    }
+
+    /// Create a `ConnectionClosed` error from the close frame sent by the Hub.
+    pub(crate) fn closed_by_hub(frame: Option<CloseFrame<'_>>) -> Self {
+        match frame {
+            Some(frame) => Error::ConnectionClosed {
+                code: Some(frame.code.into()),
+                reason: if frame.reason.is_empty() {
+                    "Closed by Hub".to_string()
+                } else {
+                    frame.reason.into_owned()
+                },
+            },
+            None => Error::closed("Closed by Hub"),
+        }
+    }
+
+    /// Check if the operation that produced this error may succeed if retried.
+    ///
+    /// Transport failures and timeouts are retryable. Authentication,
+    /// configuration, and serialization errors are not. Protocol errors are
+    /// retryable only for server-side codes (HTTP-style 5xx, JSON-RPC
+    /// internal error, and the JSON-RPC server error range); client-side
+    /// codes such as 4xx indicate the request itself was rejected. Closes
+    /// with code 1008 (policy violation) or an application code in the
+    /// 4000 range signal an authentication or policy failure and are not
+    /// retryable either.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::ConnectionClosed { code, .. } => !matches!(code, Some(1008 | 4000..=4999)),
+            Error::Connection(_) | Error::Timeout | Error::RateLimited(_) => true,
+            Error::RetryAfter { .. } => true,
+            Error::Proxy(_) => true,
+            Error::Protocol { code, .. } => {
+                matches!(code, 500..=599 | -32603 | -32099..=-32000)
+            }
+            Error::Json(_)
+            | Error::Base64(_)
+            | Error::TokenRevoked
+            | Error::Handshake(_)
+            | Error::NotConnected
+            | Error::InvalidConfig(_)
+            | Error::AlreadyRunning
+            | Error::ChannelError => false,
+            #[cfg(feature = "jwt")]
+            Error::Jwt(_) => false,
+            #[cfg(feature = "msgpack")]
+            Error::MessagePackEncode(_) | Error::MessagePackDecode(_) => false,
+        }
+    }
+
+    /// Get the protocol error code, if this is a protocol error.
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Error::Protocol { code, .. } | Error::RetryAfter { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// How long the Hub asked us to wait before trying again, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Error::RetryAfter { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    /// Get the protocol error code as a [`ProtocolErrorCode`], for matching
+    /// on known codes.
+    ///
+    /// # Example
+    /// ```
+    /// use starlight::error::{Error, ProtocolErrorCode};
+    ///
+    /// let err = Error::Protocol { code: 4001, message: "bad token".into() };
+    /// assert!(matches!(err.protocol_code(), Some(ProtocolErrorCode::AuthRejected)));
+    /// ```
+    pub fn protocol_code(&self) -> Option<ProtocolErrorCode> {
+        self.code().map(ProtocolErrorCode::from)
+    }
+
+    /// Get the WebSocket close code, if the Hub closed the connection with one.
+    pub fn close_code(&self) -> Option<u16> {
+        match self {
+            Error::ConnectionClosed { code, .. } => *code,
+            _ => None,
+        }
+    }
+}
+
+/// Known codes carried by [`Error::Protocol`].
+///
+/// Covers the JSON-RPC 2.0 standard codes and the codes defined by the
+/// Starlight Protocol spec. Anything else is kept as `Other`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProtocolErrorCode {
+    /// `-32700`: the Hub could not parse the message
+    ParseError,
+    /// `-32600`: not a valid JSON-RPC request
+    InvalidRequest,
+    /// `-32601`: the Hub doesn't know the method
+    MethodNotFound,
+    /// `-32602`: invalid method parameters
+    InvalidParams,
+    /// `-32603`: internal JSON-RPC error
+    InternalError,
+    /// `-32099..=-32000`: implementation-defined server error
+    ServerError(i32),
+    /// `4001`: the auth token was rejected
+    AuthRejected,
+    /// `4002`: the registration was malformed or rejected
+    InvalidRegistration,
+    /// `4003`: the message format is invalid
+    MalformedMessage,
+    /// Any other code
+    Other(i32),
+}
+
+impl ProtocolErrorCode {
+    /// The numeric code.
+    pub fn code(self) -> i32 {
+        match self {
+            Self::ParseError => -32700,
+            Self::InvalidRequest => -32600,
+            Self::MethodNotFound => -32601,
+            Self::InvalidParams => -32602,
+            Self::InternalError => -32603,
+            Self::AuthRejected => 4001,
+            Self::InvalidRegistration => 4002,
+            Self::MalformedMessage => 4003,
+            Self::ServerError(code) | Self::Other(code) => code,
+        }
+    }
+}
+
+impl From<i32> for ProtocolErrorCode {
+    fn from(code: i32) -> Self {
+        match code {
+            -32700 => Self::ParseError,
+            -32600 => Self::InvalidRequest,
+            -32601 => Self::MethodNotFound,
+            -32602 => Self::InvalidParams,
+            -32603 => Self::InternalError,
+            -32099..=-32000 => Self::ServerError(code),
+            4001 => Self::AuthRejected,
+            4002 => Self::InvalidRegistration,
+            4003 => Self::MalformedMessage,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<ProtocolErrorCode> for i32 {
+    fn from(code: ProtocolErrorCode) -> Self {
+        code.code()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol(code: i32) -> Error {
+        Error::Protocol {
+            code,
+            message: "rejected".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_retryable_classification() {
+        use tokio_tungstenite::tungstenite::Error as WsError;
+
+        assert!(Error::Connection(WsError::ConnectionClosed).is_retryable());
+        assert!(Error::closed("gone").is_retryable());
+        assert!(Error::Timeout.is_retryable());
+        assert!(Error::Proxy("unreachable".to_string()).is_retryable());
+
+        #[cfg(feature = "jwt")]
+        {
+            let jwt = jsonwebtoken::errors::ErrorKind::InvalidToken.into();
+            assert!(!Error::Jwt(jwt).is_retryable());
+        }
+        assert!(!Error::TokenRevoked.is_retryable());
+        assert!(!Error::Handshake("protocol version mismatch".into()).is_retryable());
+        assert!(!Error::InvalidConfig("bad".to_string()).is_retryable());
+        assert!(!Error::NotConnected.is_retryable());
+        assert!(!Error::AlreadyRunning.is_retryable());
+        assert!(!Error::ChannelError.is_retryable());
+
+        let json = serde_json::from_str::<u32>("x").unwrap_err();
+        assert!(!Error::Json(json).is_retryable());
+    }
+
+    #[test]
+    fn test_protocol_retryable_by_code() {
+        assert!(!protocol(401).is_retryable());
+        assert!(!protocol(429).is_retryable());
+        assert!(!protocol(-32602).is_retryable());
+        assert!(protocol(503).is_retryable());
+        assert!(protocol(-32603).is_retryable());
+        assert!(protocol(-32001).is_retryable());
+    }
+
+    #[test]
+    fn test_code_and_display() {
+        let err = protocol(401);
+        assert_eq!(err.code(), Some(401));
+        assert_eq!(err.to_string(), "Protocol error 401: rejected");
+        assert_eq!(Error::Timeout.code(), None);
+    }
+
+    #[test]
+    fn test_protocol_error_codes() {
+        use ProtocolErrorCode::*;
+
+        let known = [
+            (-32700, ParseError),
+            (-32600, InvalidRequest),
+            (-32601, MethodNotFound),
+            (-32602, InvalidParams),
+            (-32603, InternalError),
+            (-32050, ServerError(-32050)),
+            (4001, AuthRejected),
+            (4002, InvalidRegistration),
+            (4003, MalformedMessage),
+        ];
+        for (code, expected) in known {
+            assert_eq!(ProtocolErrorCode::from(code), expected);
+            assert_eq!(i32::from(expected), code);
+        }
+
+        for code in [0, 401, 503, -32100, 4999] {
+            assert_eq!(ProtocolErrorCode::from(code), Other(code));
+            assert_eq!(Other(code).code(), code);
+        }
+
+        assert_eq!(protocol(4001).protocol_code(), Some(AuthRejected));
+        assert_eq!(Error::Timeout.protocol_code(), None);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let err = Error::RetryAfter {
+            code: 429,
+            message: "slow down".to_string(),
+            retry_after: Duration::from_millis(5000),
+        };
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(5)));
+        assert_eq!(err.code(), Some(429));
+        assert!(err.is_retryable());
+        assert_eq!(
+            err.to_string(),
+            "Protocol error 429: slow down (retry after 5s)"
+        );
+        assert_eq!(protocol(429).retry_after(), None);
+    }
+
+    #[test]
+    fn test_close_codes() {
+        let policy = Error::ConnectionClosed {
+            code: Some(1008),
+            reason: "invalid token".to_string(),
+        };
+        assert_eq!(policy.close_code(), Some(1008));
+        assert_eq!(
+            policy.to_string(),
+            "Connection closed (1008): invalid token"
+        );
+        assert!(!policy.is_retryable());
+
+        let going_away = Error::ConnectionClosed {
+            code: Some(1001),
+            reason: "restarting".to_string(),
+        };
+        assert!(going_away.is_retryable());
+
+        assert_eq!(
+            Error::closed("Stream ended").to_string(),
+            "Connection closed: Stream ended"
+        );
+        assert_eq!(Error::closed("Stream ended").close_code(), None);
+    }
+}
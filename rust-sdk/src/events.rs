@@ -0,0 +1,29 @@
+//! Connection lifecycle events published by a running Sentinel.
+
+/// Something that happened to a Sentinel's connection.
+///
+/// Subscribe with [`Sentinel::events`](crate::Sentinel::events). Unlike the
+/// [`SentinelHandler`](crate::SentinelHandler) callbacks, any number of
+/// places (metrics, UI, alerting) can listen at once. Subscribers that fall
+/// behind miss the oldest events and get
+/// [`RecvError::Lagged`](tokio::sync::broadcast::error::RecvError::Lagged).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SentinelEvent {
+    /// The transport connected to the Hub (initially or after a reconnect)
+    Connected,
+
+    /// The connection to the Hub was lost or closed
+    Disconnected,
+
+    /// Attempting to reconnect after losing the connection
+    Reconnecting,
+
+    /// Registration was sent and the handler notified
+    HandshakeComplete,
+
+    /// A message from the Hub is about to be handled
+    MessageReceived {
+        /// The message's JSON-RPC method
+        method: String,
+    },
+}
@@ -0,0 +1,38 @@
+//! Point-in-time health of a Sentinel, for liveness and readiness probes.
+
+use serde::Serialize;
+
+/// A snapshot returned by [`Sentinel::health`](crate::Sentinel::health).
+///
+/// Serializes to JSON so it can be returned directly from an HTTP probe:
+///
+/// ```json
+/// {"connected":true,"running":true,"last_message_ms_ago":1200,"reconnects":0,"hub_url":"ws://localhost:8080","connected_for_ms":60000}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthStatus {
+    /// Whether the transport reports an open connection
+    pub connected: bool,
+
+    /// Whether the message loop is running
+    pub running: bool,
+
+    /// Milliseconds since the last message from the Hub (`None` if none yet)
+    pub last_message_ms_ago: Option<u64>,
+
+    /// Successful reconnections
+    pub reconnects: u64,
+
+    /// The Hub endpoint in use, if the transport has one
+    pub hub_url: Option<String>,
+
+    /// Milliseconds since the current connection's handshake (`None` while disconnected)
+    pub connected_for_ms: Option<u64>,
+}
+
+impl HealthStatus {
+    /// Connected and running.
+    pub fn is_healthy(&self) -> bool {
+        self.connected && self.running
+    }
+}
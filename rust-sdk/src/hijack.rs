@@ -0,0 +1,116 @@
+//! RAII guard for a hijack session.
+
+use std::sync::Arc;
+
+use tracing::warn;
+
+use crate::error::Result;
+use crate::interceptor::{send_intercepted, Interceptors};
+use crate::messages::{methods, ActionCommand, JsonRpcNotification, ResumeParams};
+use crate::sentinel::SentinelConfig;
+use crate::transport::Transport;
+
+/// An active hijack, created by [`Sentinel::begin_hijack`](crate::Sentinel::begin_hijack).
+///
+/// Actions sent through the session are forwarded to the Hub, validated
+/// and resent like [`Sentinel::action`](crate::Sentinel::action). Call
+/// [`finish`](Self::finish) to resume normal execution; if the session is
+/// dropped without finishing (e.g. on an early return), a best-effort
+/// `resume` with a re-check is sent in the background so the browser is
+/// never left hijacked.
+///
+/// # Example
+/// ```rust,no_run
+/// # use starlight::{ActionCommand, DefaultHandler, Sentinel};
+/// # async fn example(sentinel: &Sentinel<DefaultHandler>) -> starlight::Result<()> {
+/// let session = sentinel.begin_hijack("Dismissing cookie banner").await?;
+/// session.action(ActionCommand::Click, "#accept-cookies", None).await?;
+/// session.finish(true).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct HijackSession {
+    transport: Arc<dyn Transport>,
+    interceptors: Interceptors,
+    config: Arc<SentinelConfig>,
+    finished: bool,
+}
+
+impl HijackSession {
+    pub(crate) fn new(
+        transport: Arc<dyn Transport>,
+        interceptors: Interceptors,
+        config: Arc<SentinelConfig>,
+    ) -> Self {
+        Self {
+            transport,
+            interceptors,
+            config,
+            finished: false,
+        }
+    }
+
+    /// Send an action during the hijack.
+    pub async fn action(
+        &self,
+        cmd: ActionCommand,
+        selector: impl Into<String>,
+        text: Option<String>,
+    ) -> Result<()> {
+        let params = self.config.action_params(cmd, selector, text)?;
+        let notification = JsonRpcNotification::new(methods::ACTION, params);
+        self.config
+            .send_with_retry(self.transport.as_ref(), &self.interceptors, &notification)
+            .await
+    }
+
+    /// End the hijack and resume normal execution.
+    pub async fn finish(mut self, request_recheck: bool) -> Result<()> {
+        self.finished = true;
+        send_notification(
+            &self.transport,
+            &self.interceptors,
+            methods::RESUME,
+            ResumeParams { request_recheck },
+        )
+        .await
+    }
+}
+
+impl Drop for HijackSession {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        warn!("HijackSession dropped without finish(); sending resume");
+
+        let transport = Arc::clone(&self.transport);
+        let interceptors = Arc::clone(&self.interceptors);
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    let params = ResumeParams {
+                        request_recheck: true,
+                    };
+                    let sent =
+                        send_notification(&transport, &interceptors, methods::RESUME, params).await;
+                    if let Err(e) = sent {
+                        warn!("Best-effort resume failed: {}", e);
+                    }
+                });
+            }
+            Err(_) => warn!("No tokio runtime available; resume not sent"),
+        }
+    }
+}
+
+async fn send_notification<P: serde::Serialize>(
+    transport: &Arc<dyn Transport>,
+    interceptors: &Interceptors,
+    method: &str,
+    params: P,
+) -> Result<()> {
+    let notification = JsonRpcNotification::new(method, params);
+    send_intercepted(transport, interceptors, &notification).await
+}
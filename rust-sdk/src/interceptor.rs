@@ -0,0 +1,72 @@
+//! Hooks for observing and rewriting every protocol message.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::messages::RawMessage;
+use crate::transport::{Message, Transport};
+
+/// Middleware that sees every message a Sentinel sends or receives.
+///
+/// Use it for cross-cutting concerns such as audit logging, signing, or
+/// redaction, without touching the [`SentinelHandler`](crate::SentinelHandler).
+/// Interceptors run in the order they were added.
+///
+/// # Example
+/// ```rust
+/// use async_trait::async_trait;
+/// use starlight::interceptor::MessageInterceptor;
+/// use starlight::messages::RawMessage;
+///
+/// struct Audit;
+///
+/// #[async_trait]
+/// impl MessageInterceptor for Audit {
+///     async fn on_inbound(&self, msg: &RawMessage) {
+///         println!("<- {}", msg.method);
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait MessageInterceptor: Send + Sync {
+    /// Called with each outbound frame before it is sent. May modify it.
+    async fn on_outbound(&self, _frame: &mut Value) {}
+
+    /// Called with each inbound message before it is handled.
+    async fn on_inbound(&self, _msg: &RawMessage) {}
+}
+
+/// Interceptors shared between the Sentinel, its dispatcher, and hijack sessions.
+pub(crate) type Interceptors = Arc<[Arc<dyn MessageInterceptor>]>;
+
+/// Run `message` through the outbound interceptors and send it.
+pub(crate) async fn send_intercepted<T: serde::Serialize>(
+    transport: &Arc<dyn Transport>,
+    interceptors: &[Arc<dyn MessageInterceptor>],
+    message: &T,
+) -> Result<()> {
+    transport
+        .send(intercept(interceptors, message).await?)
+        .await
+}
+
+/// Run `message` through the outbound interceptors into a text frame.
+pub(crate) async fn intercept<T: serde::Serialize>(
+    interceptors: &[Arc<dyn MessageInterceptor>],
+    message: &T,
+) -> Result<Message> {
+    let json = if interceptors.is_empty() {
+        serde_json::to_string(message)?
+    } else {
+        let mut frame = serde_json::to_value(message)?;
+        for interceptor in interceptors {
+            interceptor.on_outbound(&mut frame).await;
+        }
+        serde_json::to_string(&frame)?
+    };
+
+    Ok(Message::Text(json))
+}
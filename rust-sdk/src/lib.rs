@@ -28,21 +28,59 @@
 //! - **JWT Authentication**: Secure token-based authentication
 //! - **Protocol Compliant**: Full JSON-RPC 2.0 and Starlight Protocol support
 
+#[cfg(feature = "jwt")]
 pub mod auth;
+pub mod backoff;
+mod cache;
+pub mod cancel;
+pub mod chunk;
 pub mod client;
+pub mod clock;
+pub mod codec;
+mod correlation;
 pub mod error;
+pub mod events;
+pub mod health;
+pub mod hijack;
+pub mod interceptor;
 pub mod messages;
+pub mod metrics;
+pub mod mock;
+pub mod mux;
+pub mod proxy;
+pub mod ratelimit;
+pub mod record;
 pub mod sentinel;
+pub mod signal;
+pub mod stream;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod transport;
 
 // Re-export main types for convenience
+#[cfg(feature = "jwt")]
 pub use auth::JwtHandler;
-pub use client::WebSocketClient;
-pub use error::{Error, Result};
+pub use cancel::CancelToken;
+pub use client::{ConnectionStats, WebSocketClient};
+pub use codec::Codec;
+pub use error::{Error, ProtocolErrorCode, Result};
+pub use events::SentinelEvent;
+pub use health::HealthStatus;
+pub use hijack::HijackSession;
+pub use interceptor::MessageInterceptor;
 pub use messages::{
-    ActionCommand, ActionParams, EntropyParams, HijackParams, JsonRpcRequest, JsonRpcResponse,
-    PreCheckParams, PreCheckResponse, RegistrationParams,
+    Ack, ActionCommand, ActionParams, BlockingElement, BlockingKind, ContextExt, EntropyParams,
+    HijackParams, HubInfo, Id, IntentParams, JsonRpcError, JsonRpcRequest, JsonRpcResponse,
+    PreCheckParams, PreCheckResponse, RegistrationParams, StandardContext,
 };
-pub use sentinel::{DefaultHandler, Sentinel, SentinelConfig, SentinelHandler};
+pub use metrics::SentinelMetrics;
+pub use mux::SentinelMux;
+pub use sentinel::{
+    DefaultHandler, IdGenerator, ResponseDialect, Sentinel, SentinelConfig, SentinelHandler,
+};
+pub use signal::ShutdownSignal;
+pub use stream::{PreCheckRequest, PreCheckResponder};
+pub use transport::Transport;
 
 /// Protocol version
 pub const PROTOCOL_VERSION: &str = "1.0.0";
@@ -1,7 +1,11 @@
 //! JSON-RPC 2.0 and Starlight Protocol message types.
 
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
 /// JSON-RPC 2.0 version constant.
 pub const JSONRPC_VERSION: &str = "2.0";
@@ -10,18 +14,59 @@ pub const JSONRPC_VERSION: &str = "2.0";
 // Base JSON-RPC Types
 // =============================================================================
 
+/// A JSON-RPC 2.0 request id, which may be a string or an integer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Id {
+    Str(String),
+    Num(i64),
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Id::Str(s) => f.write_str(s),
+            Id::Num(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl From<String> for Id {
+    fn from(id: String) -> Self {
+        Id::Str(id)
+    }
+}
+
+impl From<&str> for Id {
+    fn from(id: &str) -> Self {
+        Id::Str(id.to_string())
+    }
+}
+
+impl From<&String> for Id {
+    fn from(id: &String) -> Self {
+        Id::Str(id.clone())
+    }
+}
+
+impl From<i64> for Id {
+    fn from(id: i64) -> Self {
+        Id::Num(id)
+    }
+}
+
 /// A JSON-RPC 2.0 request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct JsonRpcRequest<T> {
     pub jsonrpc: String,
     pub method: String,
     pub params: T,
-    pub id: String,
+    pub id: Id,
 }
 
 impl<T> JsonRpcRequest<T> {
     /// Create a new JSON-RPC request.
-    pub fn new(method: impl Into<String>, params: T, id: impl Into<String>) -> Self {
+    pub fn new(method: impl Into<String>, params: T, id: impl Into<Id>) -> Self {
         Self {
             jsonrpc: JSONRPC_VERSION.to_string(),
             method: method.into(),
@@ -32,16 +77,40 @@ impl<T> JsonRpcRequest<T> {
 }
 
 /// A JSON-RPC 2.0 response.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct JsonRpcResponse<T> {
     pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<JsonRpcError>,
-    pub id: String,
+    pub id: Id,
+}
+
+impl<T> JsonRpcResponse<T> {
+    /// Create a successful response.
+    pub fn success(id: impl Into<Id>, result: T) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            result: Some(result),
+            error: None,
+            id: id.into(),
+        }
+    }
+
+    /// Create an error response.
+    pub fn error(id: impl Into<Id>, error: JsonRpcError) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            result: None,
+            error: Some(error),
+            id: id.into(),
+        }
+    }
 }
 
 /// A JSON-RPC 2.0 error.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct JsonRpcError {
     pub code: i32,
     pub message: String,
@@ -49,8 +118,71 @@ pub struct JsonRpcError {
     pub data: Option<serde_json::Value>,
 }
 
+impl JsonRpcError {
+    /// Invalid JSON was received.
+    pub const PARSE_ERROR: i32 = -32700;
+    /// The JSON sent is not a valid request object.
+    pub const INVALID_REQUEST: i32 = -32600;
+    /// The method does not exist or is not available.
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    /// Invalid method parameters.
+    pub const INVALID_PARAMS: i32 = -32602;
+    /// Internal JSON-RPC error.
+    pub const INTERNAL_ERROR: i32 = -32603;
+
+    /// Create an error with any code.
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// `-32700 Parse error`
+    pub fn parse_error() -> Self {
+        Self::new(Self::PARSE_ERROR, "Parse error")
+    }
+
+    /// `-32600 Invalid Request`
+    pub fn invalid_request() -> Self {
+        Self::new(Self::INVALID_REQUEST, "Invalid Request")
+    }
+
+    /// `-32601 Method not found`
+    pub fn method_not_found() -> Self {
+        Self::new(Self::METHOD_NOT_FOUND, "Method not found")
+    }
+
+    /// `-32602` with a description of what was wrong with the params.
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(Self::INVALID_PARAMS, message)
+    }
+
+    /// `-32603` with a description of the failure.
+    pub fn internal_error(message: impl Into<String>) -> Self {
+        Self::new(Self::INTERNAL_ERROR, message)
+    }
+
+    /// Attach additional information about the error.
+    pub fn with_data(mut self, data: serde_json::Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// How long the Hub asked us to wait before retrying, from
+    /// `data.retry_after_ms`.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.data
+            .as_ref()?
+            .get("retry_after_ms")?
+            .as_u64()
+            .map(Duration::from_millis)
+    }
+}
+
 /// A JSON-RPC 2.0 notification (no id field).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct JsonRpcNotification<T> {
     pub jsonrpc: String,
     pub method: String,
@@ -73,7 +205,7 @@ impl<T> JsonRpcNotification<T> {
 // =============================================================================
 
 /// Registration parameters for Sentinel → Hub.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RegistrationParams {
     /// Sentinel layer name (e.g., "JanitorSentinel")
     pub layer: String,
@@ -89,9 +221,31 @@ pub struct RegistrationParams {
     #[serde(default)]
     pub selectors: Vec<String>,
 
+    /// Per-selector priorities (1-10) overriding `priority`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub selector_priorities: HashMap<String, u8>,
+
     /// Optional JWT authentication token
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auth_token: Option<String>,
+
+    /// Starlight Protocol version spoken by this Sentinel
+    ///
+    /// Sent as `version`, the key the Hub reads.
+    #[serde(
+        rename = "version",
+        alias = "protocol_version",
+        default = "default_protocol_version"
+    )]
+    pub protocol_version: String,
+
+    /// Session to resume, from an earlier [`RegistrationResult::session_id`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_session: Option<String>,
+}
+
+fn default_protocol_version() -> String {
+    crate::PROTOCOL_VERSION.to_string()
 }
 
 impl RegistrationParams {
@@ -102,7 +256,10 @@ impl RegistrationParams {
             priority,
             capabilities: Vec::new(),
             selectors: Vec::new(),
+            selector_priorities: HashMap::new(),
             auth_token: None,
+            protocol_version: default_protocol_version(),
+            resume_session: None,
         }
     }
 
@@ -118,15 +275,96 @@ impl RegistrationParams {
         self
     }
 
+    /// Set per-selector priority overrides.
+    pub fn with_selector_priorities(mut self, priorities: HashMap<String, u8>) -> Self {
+        self.selector_priorities = priorities;
+        self
+    }
+
     /// Set auth token.
     pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
         self.auth_token = Some(token.into());
         self
     }
+
+    /// Ask the Hub to reattach to an earlier session.
+    pub fn with_resume_session(mut self, session_id: impl Into<String>) -> Self {
+        self.resume_session = Some(session_id.into());
+        self
+    }
+}
+
+/// Result of a registration request, sent by Hubs that acknowledge it.
+///
+/// Every field is optional and unknown fields are ignored, so newer Hubs
+/// can extend the result without breaking the handshake.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistrationResult {
+    /// Starlight Protocol version spoken by the Hub
+    #[serde(default, alias = "version", skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<String>,
+
+    /// Build version of the Hub software
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hub_version: Option<String>,
+
+    /// Identifies the Hub instance, e.g. behind a load balancer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hub_instance_id: Option<String>,
+
+    /// Session the Hub keeps for this Sentinel, sent by Hubs that can
+    /// resume it after a reconnect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+/// What the Hub reported about itself when acknowledging registration.
+///
+/// Available from [`Sentinel::hub_info`](crate::Sentinel::hub_info).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HubInfo {
+    /// Starlight Protocol version spoken by the Hub
+    pub protocol_version: Option<String>,
+
+    /// Build version of the Hub software
+    pub version: Option<String>,
+
+    /// Identifies the Hub instance
+    pub instance_id: Option<String>,
+}
+
+impl From<RegistrationResult> for HubInfo {
+    fn from(result: RegistrationResult) -> Self {
+        Self {
+            protocol_version: result.protocol_version,
+            version: result.hub_version,
+            instance_id: result.hub_instance_id,
+        }
+    }
+}
+
+/// Check that the Hub's protocol version is compatible with this SDK.
+///
+/// Versions are compatible when their major components match.
+#[allow(clippy::result_large_err)]
+pub fn check_protocol_version(remote: &str) -> crate::error::Result<()> {
+    fn major(version: &str) -> Option<u64> {
+        version.trim().split('.').next()?.parse().ok()
+    }
+
+    match (major(crate::PROTOCOL_VERSION), major(remote)) {
+        (Some(local), Some(hub)) if local == hub => Ok(()),
+        _ => Err(crate::error::Error::Handshake(format!(
+            "protocol version mismatch: SDK speaks {}, Hub speaks {}",
+            crate::PROTOCOL_VERSION,
+            remote
+        ))),
+    }
 }
 
 /// Pre-check parameters from Hub → Sentinel.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PreCheckParams {
     /// Current page URL
     #[serde(default)]
@@ -156,8 +394,128 @@ pub struct PreCheckParams {
     pub context: HashMap<String, serde_json::Value>,
 }
 
+impl PreCheckParams {
+    /// Check if the Hub detected any blocking elements.
+    pub fn has_blocking(&self) -> bool {
+        !self.blocking.is_empty()
+    }
+
+    /// Selectors of all detected blocking elements.
+    pub fn blocking_selectors(&self) -> Vec<&str> {
+        self.blocking.iter().map(|b| b.selector.as_str()).collect()
+    }
+
+    /// Blocking elements whose `element_type` equals `ty`.
+    pub fn blocking_of_type(&self, ty: &str) -> Vec<&BlockingElement> {
+        self.blocking
+            .iter()
+            .filter(|b| b.element_type.as_deref() == Some(ty))
+            .collect()
+    }
+
+    /// Check if any blocking element is of the given kind.
+    pub fn has_blocking_kind(&self, kind: BlockingKind) -> bool {
+        self.blocking.iter().any(|b| b.kind() == kind)
+    }
+
+    /// Decode the base64 `screenshot`, if one was sent.
+    ///
+    /// Accepts the standard and URL-safe alphabets, with or without
+    /// padding, and strips a `data:image/...;base64,` prefix.
+    pub fn decode_screenshot(&self) -> Option<crate::error::Result<Vec<u8>>> {
+        use base64::alphabet;
+        use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+        use base64::Engine;
+
+        const CONFIG: GeneralPurposeConfig =
+            GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+        const STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, CONFIG);
+        const URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, CONFIG);
+
+        let data = self.screenshot.as_deref()?;
+        let data = match data.strip_prefix("data:") {
+            Some(uri) => uri
+                .split_once(";base64,")
+                .map_or(uri, |(_, payload)| payload),
+            None => data,
+        };
+
+        let engine = if data.contains(['-', '_']) {
+            &URL_SAFE
+        } else {
+            &STANDARD
+        };
+        Some(engine.decode(data).map_err(Into::into))
+    }
+
+    /// Get a typed value from the context map.
+    ///
+    /// Returns `None` if the key is absent or the value doesn't deserialize as `T`.
+    pub fn context_get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.context.get_as(key)
+    }
+
+    /// The well-known context keys as typed fields; see [`StandardContext`].
+    pub fn standard_context(&self) -> StandardContext {
+        StandardContext::from_context(&self.context)
+    }
+}
+
+/// The keys the Hub standardizes in a pre-check's `context`.
+///
+/// | Key          | Field        | Value                                  |
+/// |--------------|--------------|----------------------------------------|
+/// | `session_id` | `session_id` | string identifying the automation run  |
+/// | `tab_id`     | `tab_id`     | string or number identifying the tab   |
+/// | `step_index` | `step_index` | position of the command in the run, from 0 |
+///
+/// A standard key whose value has a different type stays in `extra`, as
+/// does every other key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StandardContext {
+    /// `session_id`
+    pub session_id: Option<String>,
+
+    /// `tab_id`, as a string even if the Hub sent a number
+    pub tab_id: Option<String>,
+
+    /// `step_index`
+    pub step_index: Option<u64>,
+
+    /// Every other key
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl StandardContext {
+    /// Split `context` into the standard keys and the rest.
+    pub fn from_context(context: &HashMap<String, serde_json::Value>) -> Self {
+        let mut extra = context.clone();
+        Self {
+            session_id: take_context(&mut extra, "session_id", |v| v.as_str().map(String::from)),
+            tab_id: take_context(&mut extra, "tab_id", |v| match v {
+                serde_json::Value::String(s) => Some(s.clone()),
+                serde_json::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            }),
+            step_index: take_context(&mut extra, "step_index", serde_json::Value::as_u64),
+            extra,
+        }
+    }
+}
+
+/// Remove `key` from `context` if `convert` accepts its value.
+fn take_context<T>(
+    context: &mut HashMap<String, serde_json::Value>,
+    key: &str,
+    convert: impl Fn(&serde_json::Value) -> Option<T>,
+) -> Option<T> {
+    let value = convert(context.get(key)?)?;
+    context.remove(key);
+    Some(value)
+}
+
 /// A blocking element detected by the Hub.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BlockingElement {
     pub selector: String,
     #[serde(default)]
@@ -166,8 +524,58 @@ pub struct BlockingElement {
     pub element_type: Option<String>,
 }
 
+impl BlockingElement {
+    /// Classify the element by its `element_type`.
+    ///
+    /// An element without a type is `Other("")`.
+    pub fn kind(&self) -> BlockingKind {
+        self.element_type
+            .as_deref()
+            .map_or_else(|| BlockingKind::Other(String::new()), BlockingKind::from)
+    }
+}
+
+/// What kind of obstacle a [`BlockingElement`] is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BlockingKind {
+    /// A modal dialog or popup
+    Modal,
+    /// A cookie or privacy consent banner
+    CookieBanner,
+    /// A newsletter or signup prompt
+    Newsletter,
+    /// A captcha or bot check
+    Captcha,
+    /// A generic overlay covering the page
+    Overlay,
+    /// Any other `element_type`, as sent
+    Other(String),
+}
+
+impl From<&str> for BlockingKind {
+    /// Parse an `element_type` case-insensitively, accepting common
+    /// synonyms such as `"dialog"` or `"consent"`.
+    fn from(element_type: &str) -> Self {
+        let normalized = element_type
+            .trim()
+            .to_ascii_lowercase()
+            .replace([' ', '-'], "_");
+        match normalized.as_str() {
+            "modal" | "dialog" | "popup" | "pop_up" | "lightbox" => BlockingKind::Modal,
+            "cookie" | "cookies" | "cookie_banner" | "cookiebanner" | "cookie_consent"
+            | "consent" | "gdpr" => BlockingKind::CookieBanner,
+            "newsletter" | "subscribe" | "signup" | "sign_up" | "email_capture" => {
+                BlockingKind::Newsletter
+            }
+            "captcha" | "recaptcha" | "hcaptcha" | "bot_check" => BlockingKind::Captcha,
+            "overlay" | "backdrop" | "interstitial" => BlockingKind::Overlay,
+            _ => BlockingKind::Other(element_type.to_string()),
+        }
+    }
+}
+
 /// Pre-check response types.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "response")]
 pub enum PreCheckResponse {
     /// All clear - proceed with command
@@ -185,17 +593,63 @@ pub enum PreCheckResponse {
 
     /// Hijack - Sentinel takes browser control
     #[serde(rename = "hijack")]
-    Hijack { reason: String },
+    Hijack {
+        reason: String,
+        /// How sure the Sentinel is that hijacking is needed (0.0-1.0)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        confidence: Option<f32>,
+        /// Selectors the Sentinel intends to act on
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        targets: Vec<String>,
+    },
+
+    /// Defer - no opinion; let another (lower-priority) Sentinel decide
+    ///
+    /// Unlike `Clear`, which asserts the command is safe to run, `Defer`
+    /// means this Sentinel is not in a position to judge. `to_layer`
+    /// optionally names the layer that should make the call.
+    #[serde(rename = "defer")]
+    Defer {
+        #[serde(rename = "toLayer", default, skip_serializing_if = "Option::is_none")]
+        to_layer: Option<String>,
+    },
+}
+
+impl PreCheckResponse {
+    /// Ask the Hub to retry after `retry_after`.
+    pub fn wait(retry_after: Duration) -> Self {
+        PreCheckResponse::Wait {
+            retry_after_ms: retry_after.as_millis() as u64,
+            reason: None,
+        }
+    }
+
+    /// Ask the Hub to retry after `retry_after`, explaining why.
+    pub fn wait_with_reason(retry_after: Duration, reason: impl Into<String>) -> Self {
+        PreCheckResponse::Wait {
+            retry_after_ms: retry_after.as_millis() as u64,
+            reason: Some(reason.into()),
+        }
+    }
+
+    /// Take control of the browser, without a confidence or targets.
+    pub fn hijack(reason: impl Into<String>) -> Self {
+        PreCheckResponse::Hijack {
+            reason: reason.into(),
+            confidence: None,
+            targets: Vec::new(),
+        }
+    }
 }
 
 /// Hijack parameters (Sentinel → Hub).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HijackParams {
     pub reason: String,
 }
 
 /// Action command during hijack (Sentinel → Hub).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ActionParams {
     pub cmd: ActionCommand,
     pub selector: String,
@@ -203,8 +657,74 @@ pub struct ActionParams {
     pub text: Option<String>,
 }
 
+impl ActionParams {
+    /// Create params for any command, failing if it requires `text` and
+    /// none is given (see [`validate`](Self::validate)).
+    ///
+    /// Prefer the per-command constructors when the command is known.
+    #[allow(clippy::result_large_err)]
+    pub fn new(
+        cmd: ActionCommand,
+        selector: impl Into<String>,
+        text: Option<String>,
+    ) -> crate::error::Result<Self> {
+        let params = Self {
+            cmd,
+            selector: selector.into(),
+            text,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Click the element matching `selector`.
+    pub fn click(selector: impl Into<String>) -> Self {
+        Self::without_text(ActionCommand::Click, selector)
+    }
+
+    /// Type `text` into the element matching `selector`.
+    pub fn fill(selector: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            cmd: ActionCommand::Fill,
+            selector: selector.into(),
+            text: Some(text.into()),
+        }
+    }
+
+    /// Hide the element matching `selector`.
+    pub fn hide(selector: impl Into<String>) -> Self {
+        Self::without_text(ActionCommand::Hide, selector)
+    }
+
+    /// Remove the element matching `selector` from the page.
+    pub fn remove(selector: impl Into<String>) -> Self {
+        Self::without_text(ActionCommand::Remove, selector)
+    }
+
+    fn without_text(cmd: ActionCommand, selector: impl Into<String>) -> Self {
+        Self {
+            cmd,
+            selector: selector.into(),
+            text: None,
+        }
+    }
+
+    /// Check that `text` is set when the command requires it, since the
+    /// Hub rejects e.g. a `fill` without text.
+    #[allow(clippy::result_large_err)]
+    pub fn validate(&self) -> crate::error::Result<()> {
+        if self.cmd.requires_text() && self.text.is_none() {
+            return Err(crate::error::Error::InvalidConfig(format!(
+                "Action '{}' on '{}' requires text",
+                self.cmd, self.selector
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// Available action commands during hijack.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ActionCommand {
     Click,
@@ -213,8 +733,57 @@ pub enum ActionCommand {
     Remove,
 }
 
+impl ActionCommand {
+    /// The lowercase name used on the wire.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionCommand::Click => "click",
+            ActionCommand::Fill => "fill",
+            ActionCommand::Hide => "hide",
+            ActionCommand::Remove => "remove",
+        }
+    }
+
+    /// Whether the command needs `text`, like the value typed by `Fill`.
+    pub fn requires_text(&self) -> bool {
+        matches!(self, ActionCommand::Fill)
+    }
+}
+
+impl fmt::Display for ActionCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ActionCommand {
+    type Err = crate::error::Error;
+
+    /// Parse a command name case-insensitively, e.g. `"click"` or `"Fill"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "click" => Ok(ActionCommand::Click),
+            "fill" => Ok(ActionCommand::Fill),
+            "hide" => Ok(ActionCommand::Hide),
+            "remove" => Ok(ActionCommand::Remove),
+            _ => Err(crate::error::Error::InvalidConfig(format!(
+                "Unknown action command '{}' (expected click, fill, hide, or remove)",
+                s
+            ))),
+        }
+    }
+}
+
+impl TryFrom<&str> for ActionCommand {
+    type Error = crate::error::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
 /// Resume parameters after hijack.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ResumeParams {
     /// Request re-check after resume
     #[serde(default = "default_true")]
@@ -225,14 +794,177 @@ fn default_true() -> bool {
     true
 }
 
+/// The Hub's acknowledgement of a hijack, action, or resume request.
+///
+/// Unknown fields are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ack {
+    /// Whether the Hub accepted the request
+    #[serde(default = "default_true")]
+    pub accepted: bool,
+    /// Why the Hub rejected it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// How long the Hub asked us to wait before trying again
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
+}
+
+impl Ack {
+    /// Read an acknowledgement from the Hub's response.
+    ///
+    /// A JSON-RPC error response counts as a rejection. A boolean result
+    /// is taken as `accepted`, and any other non-object result as a plain
+    /// acknowledgement.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn from_response(msg: RawMessage) -> crate::error::Result<Self> {
+        if let Some(error) = msg.error {
+            return Ok(Self {
+                accepted: false,
+                retry_after: error.retry_after(),
+                error: Some(error.message),
+            });
+        }
+
+        match msg.result {
+            Some(result @ serde_json::Value::Object(_)) => Ok(serde_json::from_value(result)?),
+            Some(serde_json::Value::Bool(accepted)) => Ok(Self {
+                accepted,
+                error: None,
+                retry_after: None,
+            }),
+            _ => Ok(Self {
+                accepted: true,
+                error: None,
+                retry_after: None,
+            }),
+        }
+    }
+}
+
 /// Context update from Sentinel.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContextUpdateParams {
     pub context: HashMap<String, serde_json::Value>,
 }
 
+/// Change to the set of selectors a Sentinel monitors, sent at runtime.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateSelectorsParams {
+    /// Selectors to start monitoring
+    #[serde(default)]
+    pub add: Vec<String>,
+    /// Selectors to stop monitoring
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Sent as `starlight.deregister` when a Sentinel stops, so the Hub can free
+/// its priority slot right away instead of waiting for a timeout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeregisterParams {
+    /// Sentinel layer name, as sent at registration
+    pub layer: String,
+}
+
+/// One slice of a chunked message, sent as `starlight.chunk` (see [`crate::chunk`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkParams {
+    /// Shared by every chunk of the same message
+    pub chunk_id: String,
+
+    /// Position of this chunk, from 0
+    pub chunk_index: u32,
+
+    /// Number of chunks in the message
+    pub total_chunks: u32,
+
+    /// This chunk's slice of the message's params, as JSON text
+    pub data: String,
+}
+
+/// Ends a chunked message, sent as `starlight.chunk_end` after its chunks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkEndParams {
+    /// The `chunk_id` of the chunks to join
+    pub chunk_id: String,
+
+    /// Method of the reassembled message
+    pub method: String,
+
+    /// Number of chunks sent
+    pub total_chunks: u32,
+}
+
+/// A command or semantic goal, sent as `starlight.intent`.
+///
+/// The same method is used in both directions: the Intent layer (and the
+/// Hub relaying it) sends intents to say what is about to happen, and a
+/// Sentinel can send one to the Hub to declare what it will do next, e.g.
+/// "dismiss this modal, then expect a navigation". At least one of `cmd`
+/// or `goal` should be set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IntentParams {
+    /// Command type (e.g. "goto", "click", "fill")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<String>,
+
+    /// Semantic goal (e.g. "Accept Cookies")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal: Option<String>,
+
+    /// Target element selector
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+
+    /// Target URL for "goto"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// Text for "fill"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    /// Expected stability delay in milliseconds
+    #[serde(
+        default,
+        rename = "stabilityHint",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stability_hint_ms: Option<u64>,
+
+    /// Additional metadata
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub context: HashMap<String, serde_json::Value>,
+}
+
+impl IntentParams {
+    /// An intent to run `cmd` against `selector`.
+    pub fn command(cmd: impl Into<String>, selector: impl Into<String>) -> Self {
+        Self {
+            cmd: Some(cmd.into()),
+            selector: Some(selector.into()),
+            ..Self::default()
+        }
+    }
+
+    /// An intent described by a semantic goal.
+    pub fn goal(goal: impl Into<String>) -> Self {
+        Self {
+            goal: Some(goal.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Add a context entry, e.g. what is expected to happen next.
+    pub fn with_context(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.context.insert(key.into(), value);
+        self
+    }
+}
+
 /// Entropy (page state) update from Hub.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EntropyParams {
     pub url: String,
     #[serde(default)]
@@ -245,6 +977,13 @@ pub struct EntropyParams {
     pub context: HashMap<String, serde_json::Value>,
 }
 
+impl EntropyParams {
+    /// Whether the page has settled: no pending mutations or network requests.
+    pub fn is_stable(&self) -> bool {
+        self.mutations == 0 && self.network_pending == 0
+    }
+}
+
 // =============================================================================
 // Protocol Method Names
 // =============================================================================
@@ -256,22 +995,782 @@ pub mod methods {
     pub const CLEAR: &str = "starlight.clear";
     pub const WAIT: &str = "starlight.wait";
     pub const HIJACK: &str = "starlight.hijack";
+    pub const DEFER: &str = "starlight.defer";
     pub const ACTION: &str = "starlight.action";
     pub const RESUME: &str = "starlight.resume";
     pub const ENTROPY: &str = "starlight.entropy";
     pub const CONTEXT_UPDATE: &str = "starlight.context_update";
     pub const INTENT: &str = "starlight.intent";
+    pub const UPDATE_SELECTORS: &str = "starlight.update_selectors";
+    pub const DEREGISTER: &str = "starlight.deregister";
+    pub const CHUNK: &str = "starlight.chunk";
+    pub const CHUNK_END: &str = "starlight.chunk_end";
 }
 
 // =============================================================================
 // Helper Types
 // =============================================================================
 
-/// Raw incoming message that can be either a request or notification.
-#[derive(Debug, Clone, Deserialize)]
+/// Typed getters for the `context` maps sent by the Hub.
+///
+/// Implemented for `HashMap<String, Value>`, so it works on
+/// [`PreCheckParams::context`], [`EntropyParams::context`], and the map
+/// passed to `on_context_update`. Every getter returns `None` when the key
+/// is absent or holds a value of a different type.
+///
+/// # Example
+/// ```
+/// use std::collections::HashMap;
+/// use starlight::messages::ContextExt;
+///
+/// let context: HashMap<String, serde_json::Value> =
+///     serde_json::from_str(r#"{ "step": 3, "user": "qa" }"#).unwrap();
+/// assert_eq!(context.get_i64("step"), Some(3));
+/// assert_eq!(context.get_str("user"), Some("qa"));
+/// assert_eq!(context.get_bool("step"), None);
+/// ```
+pub trait ContextExt {
+    /// Get a string value.
+    fn get_str(&self, key: &str) -> Option<&str>;
+
+    /// Get an integer value.
+    fn get_i64(&self, key: &str) -> Option<i64>;
+
+    /// Get a boolean value.
+    fn get_bool(&self, key: &str) -> Option<bool>;
+
+    /// Deserialize a value as `T`.
+    fn get_as<T: DeserializeOwned>(&self, key: &str) -> Option<T>;
+}
+
+impl ContextExt for HashMap<String, serde_json::Value> {
+    fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key)?.as_str()
+    }
+
+    fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get(key)?.as_i64()
+    }
+
+    fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key)?.as_bool()
+    }
+
+    fn get_as<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        T::deserialize(self.get(key)?).ok()
+    }
+}
+
+/// Raw incoming message: a request, a notification, or a response.
+///
+/// Responses have an empty `method` and carry `result` or `error` instead
+/// of `params`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RawMessage {
     pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub method: String,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
     pub params: serde_json::Value,
-    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl RawMessage {
+    /// Whether this message is a response to a request we sent.
+    pub fn is_response(&self) -> bool {
+        self.method.is_empty() && self.id.is_some()
+    }
+}
+
+/// Decode a text frame holding either a single message or a JSON-RPC batch.
+///
+/// Batch elements are returned in the order they appear in the frame.
+#[allow(clippy::result_large_err)]
+pub(crate) fn decode_frame(text: &str) -> crate::error::Result<Vec<RawMessage>> {
+    if text.trim_start().starts_with('[') {
+        Ok(serde_json::from_str(text)?)
+    } else {
+        Ok(vec![serde_json::from_str(text)?])
+    }
+}
+
+/// Deserialize message params, reporting failures as `-32602` protocol
+/// errors that name the offending field (e.g. `params.blocking[0].selector`).
+#[allow(clippy::result_large_err)]
+pub(crate) fn from_params<T: DeserializeOwned>(
+    value: serde_json::Value,
+) -> crate::error::Result<T> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let field = match e.path().to_string().as_str() {
+            "." => "params".to_string(),
+            path => format!("params.{}", path),
+        };
+        crate::error::Error::Protocol {
+            code: JsonRpcError::INVALID_PARAMS,
+            message: format!("Invalid {}: {}", field, e.inner()),
+        }
+    })
+}
+
+/// Deserialize message params, rejecting fields `T` doesn't know about.
+///
+/// Unknown fields are found by comparing the input against `T` serialized
+/// back to JSON, so nested structures are checked too. Free-form maps such
+/// as `context` round-trip unchanged and never report unknown keys.
+#[allow(clippy::result_large_err)]
+pub(crate) fn from_value_strict<T>(value: serde_json::Value) -> crate::error::Result<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let parsed: T = from_params(value.clone())?;
+    let known = serde_json::to_value(&parsed)?;
+
+    let mut unknown = Vec::new();
+    collect_unknown_fields(&value, &known, "params", &mut unknown);
+    if !unknown.is_empty() {
+        return Err(crate::error::Error::Protocol {
+            code: JsonRpcError::INVALID_PARAMS,
+            message: format!("Unknown fields: {}", unknown.join(", ")),
+        });
+    }
+
+    Ok(parsed)
+}
+
+fn collect_unknown_fields(
+    input: &serde_json::Value,
+    known: &serde_json::Value,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    use serde_json::Value;
+
+    match (input, known) {
+        (Value::Object(input), Value::Object(known)) => {
+            for (key, value) in input {
+                let field = format!("{}.{}", path, key);
+                match known.get(key) {
+                    Some(known) => collect_unknown_fields(value, known, &field, unknown),
+                    // Optional fields skipped on serialization come back absent.
+                    None if value.is_null() => {}
+                    None => unknown.push(field),
+                }
+            }
+        }
+        (Value::Array(input), Value::Array(known)) => {
+            for (i, (value, known)) in input.iter().zip(known).enumerate() {
+                collect_unknown_fields(value, known, &format!("{}[{}]", path, i), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_equality() {
+        assert_eq!(PreCheckResponse::Clear, PreCheckResponse::Clear);
+        assert_eq!(
+            PreCheckResponse::wait(Duration::from_millis(500)),
+            PreCheckResponse::Wait {
+                retry_after_ms: 500,
+                reason: None
+            }
+        );
+        assert_ne!(
+            PreCheckResponse::hijack("popup"),
+            PreCheckResponse::hijack("banner")
+        );
+
+        let params = json!({
+            "command": "click",
+            "selector": "#submit",
+            "blocking": [{ "selector": ".modal", "element_type": "modal" }],
+            "context": { "step": 3 }
+        });
+        assert_eq!(pre_check(params.clone()), pre_check(params));
+        assert_eq!(
+            pre_check(json!({ "command": "click", "blocking": [{ "selector": ".a" }] })).blocking,
+            vec![BlockingElement {
+                selector: ".a".to_string(),
+                text: None,
+                element_type: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_pre_check_response_constructors() {
+        assert_eq!(
+            serde_json::to_value(PreCheckResponse::wait(Duration::from_millis(1500))).unwrap(),
+            json!({ "response": "wait", "retryAfterMs": 1500, "reason": null })
+        );
+        assert_eq!(
+            serde_json::to_value(PreCheckResponse::wait_with_reason(
+                Duration::from_secs(2),
+                "Spinner visible"
+            ))
+            .unwrap(),
+            json!({ "response": "wait", "retryAfterMs": 2000, "reason": "Spinner visible" })
+        );
+        assert_eq!(
+            serde_json::to_value(PreCheckResponse::hijack("Cookie banner")).unwrap(),
+            json!({ "response": "hijack", "reason": "Cookie banner" })
+        );
+    }
+
+    #[test]
+    fn test_standard_error_codes() {
+        let codes: Vec<i32> = [
+            JsonRpcError::parse_error(),
+            JsonRpcError::invalid_request(),
+            JsonRpcError::method_not_found(),
+            JsonRpcError::invalid_params("missing url"),
+            JsonRpcError::internal_error("handler panicked"),
+        ]
+        .iter()
+        .map(|e| e.code)
+        .collect();
+        assert_eq!(codes, vec![-32700, -32600, -32601, -32602, -32603]);
+
+        let error =
+            JsonRpcError::invalid_params("missing url").with_data(json!({ "field": "url" }));
+        let response = JsonRpcResponse::<()>::error("pc-1", error);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32602, "message": "missing url", "data": { "field": "url" } },
+                "id": "pc-1"
+            })
+        );
+
+        let plain = serde_json::to_value(JsonRpcError::method_not_found()).unwrap();
+        assert_eq!(
+            plain,
+            json!({ "code": -32601, "message": "Method not found" })
+        );
+    }
+
+    fn pre_check(value: serde_json::Value) -> PreCheckParams {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_blocking_helpers_empty() {
+        let params = pre_check(json!({ "command": "click" }));
+
+        assert!(!params.has_blocking());
+        assert!(params.blocking_selectors().is_empty());
+        assert!(params.blocking_of_type("modal").is_empty());
+    }
+
+    #[test]
+    fn test_blocking_helpers_single() {
+        let params = pre_check(json!({
+            "command": "click",
+            "blocking": [{ "selector": ".modal", "element_type": "modal" }]
+        }));
+
+        assert!(params.has_blocking());
+        assert_eq!(params.blocking_selectors(), vec![".modal"]);
+        assert_eq!(params.blocking_of_type("modal").len(), 1);
+        assert!(params.blocking_of_type("cookie").is_empty());
+    }
+
+    #[test]
+    fn test_blocking_kinds() {
+        let element = |ty: Option<&str>| BlockingElement {
+            selector: ".x".to_string(),
+            text: None,
+            element_type: ty.map(str::to_string),
+        };
+
+        let recognized = [
+            ("modal", BlockingKind::Modal),
+            ("Dialog", BlockingKind::Modal),
+            ("cookie-banner", BlockingKind::CookieBanner),
+            ("CONSENT", BlockingKind::CookieBanner),
+            ("newsletter", BlockingKind::Newsletter),
+            ("sign up", BlockingKind::Newsletter),
+            ("reCAPTCHA", BlockingKind::Captcha),
+            (" overlay ", BlockingKind::Overlay),
+        ];
+        for (ty, kind) in recognized {
+            assert_eq!(element(Some(ty)).kind(), kind, "{}", ty);
+        }
+
+        assert_eq!(
+            element(Some("Paywall")).kind(),
+            BlockingKind::Other("Paywall".to_string())
+        );
+        assert_eq!(element(None).kind(), BlockingKind::Other(String::new()));
+
+        let params = pre_check(json!({
+            "command": "click",
+            "blocking": [
+                { "selector": "#consent", "element_type": "cookie" },
+                { "selector": ".x" }
+            ]
+        }));
+        assert!(params.has_blocking_kind(BlockingKind::CookieBanner));
+        assert!(!params.has_blocking_kind(BlockingKind::Captcha));
+    }
+
+    #[test]
+    fn test_context_get_typed() {
+        let params = pre_check(json!({
+            "command": "click",
+            "context": { "step": 3, "tags": ["a", "b"], "session": "abc" }
+        }));
+
+        assert_eq!(params.context_get::<u32>("step"), Some(3));
+        assert_eq!(
+            params.context_get::<Vec<String>>("tags"),
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(params.context_get::<u32>("session"), None);
+        assert_eq!(params.context_get::<String>("missing"), None);
+    }
+
+    #[test]
+    fn test_standard_context() {
+        let params = pre_check(json!({
+            "command": "click",
+            "context": {
+                "session_id": "run-42",
+                "tab_id": 7,
+                "step_index": 3,
+                "user": "qa",
+                "retries": 2
+            }
+        }));
+
+        let context = params.standard_context();
+        assert_eq!(context.session_id.as_deref(), Some("run-42"));
+        assert_eq!(context.tab_id.as_deref(), Some("7"));
+        assert_eq!(context.step_index, Some(3));
+        assert_eq!(
+            context.extra,
+            HashMap::from([("user".into(), json!("qa")), ("retries".into(), json!(2))])
+        );
+
+        // A standard key of the wrong type stays in extra.
+        let params = pre_check(json!({
+            "command": "click",
+            "context": { "step_index": "third" }
+        }));
+        let context = params.standard_context();
+        assert_eq!(context.step_index, None);
+        assert_eq!(context.extra["step_index"], "third");
+    }
+
+    #[test]
+    fn test_context_ext_getters() {
+        let entropy: EntropyParams = serde_json::from_value(json!({
+            "url": "https://example.com",
+            "context": {
+                "user": "qa",
+                "step": 3,
+                "ratio": 0.5,
+                "logged_in": true,
+                "viewport": { "width": 1280, "height": 720 }
+            }
+        }))
+        .unwrap();
+        let context = &entropy.context;
+
+        assert_eq!(context.get_str("user"), Some("qa"));
+        assert_eq!(context.get_i64("step"), Some(3));
+        assert_eq!(context.get_bool("logged_in"), Some(true));
+        assert_eq!(
+            context.get_as::<HashMap<String, u32>>("viewport"),
+            Some(HashMap::from([
+                ("width".into(), 1280),
+                ("height".into(), 720)
+            ]))
+        );
+
+        // Absent keys
+        assert_eq!(context.get_str("missing"), None);
+        assert_eq!(context.get_as::<u32>("missing"), None);
+
+        // Type mismatches
+        assert_eq!(context.get_str("step"), None);
+        assert_eq!(context.get_i64("ratio"), None);
+        assert_eq!(context.get_bool("user"), None);
+        assert_eq!(context.get_as::<Vec<String>>("viewport"), None);
+    }
+
+    #[test]
+    fn test_pre_check_response_serialization_unchanged() {
+        assert_eq!(
+            serde_json::to_value(PreCheckResponse::Clear).unwrap(),
+            json!({ "response": "clear" })
+        );
+        assert_eq!(
+            serde_json::to_value(PreCheckResponse::Wait {
+                retry_after_ms: 500,
+                reason: None
+            })
+            .unwrap(),
+            json!({ "response": "wait", "retryAfterMs": 500, "reason": null })
+        );
+        assert_eq!(
+            serde_json::to_value(PreCheckResponse::Hijack {
+                reason: "popup".to_string(),
+                confidence: None,
+                targets: Vec::new(),
+            })
+            .unwrap(),
+            json!({ "response": "hijack", "reason": "popup" })
+        );
+    }
+
+    #[test]
+    fn test_defer_round_trip() {
+        let defer = PreCheckResponse::Defer {
+            to_layer: Some("CaptchaSentinel".to_string()),
+        };
+        let value = serde_json::to_value(&defer).unwrap();
+        assert_eq!(
+            value,
+            json!({ "response": "defer", "toLayer": "CaptchaSentinel" })
+        );
+
+        let parsed: PreCheckResponse = serde_json::from_value(value).unwrap();
+        assert!(matches!(
+            parsed,
+            PreCheckResponse::Defer { to_layer: Some(ref layer) } if layer == "CaptchaSentinel"
+        ));
+
+        let bare: PreCheckResponse =
+            serde_json::from_value(json!({ "response": "defer" })).unwrap();
+        assert!(matches!(bare, PreCheckResponse::Defer { to_layer: None }));
+        assert_eq!(
+            serde_json::to_value(&bare).unwrap(),
+            json!({ "response": "defer" })
+        );
+    }
+
+    #[test]
+    fn test_hijack_metadata() {
+        let hijack = PreCheckResponse::Hijack {
+            reason: "captcha".to_string(),
+            confidence: Some(0.75),
+            targets: vec!["#captcha".to_string()],
+        };
+        assert_eq!(
+            serde_json::to_value(&hijack).unwrap(),
+            json!({
+                "response": "hijack",
+                "reason": "captcha",
+                "confidence": 0.75,
+                "targets": ["#captcha"]
+            })
+        );
+
+        let legacy: PreCheckResponse =
+            serde_json::from_value(json!({ "response": "hijack", "reason": "x" })).unwrap();
+        match legacy {
+            PreCheckResponse::Hijack {
+                reason,
+                confidence,
+                targets,
+            } => {
+                assert_eq!(reason, "x");
+                assert_eq!(confidence, None);
+                assert!(targets.is_empty());
+            }
+            other => panic!("expected hijack, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_string_and_numeric_ids() {
+        let raw: RawMessage = serde_json::from_value(json!({
+            "jsonrpc": "2.0", "method": "starlight.pre_check", "params": {}, "id": "abc"
+        }))
+        .unwrap();
+        assert_eq!(raw.id, Some(Id::Str("abc".to_string())));
+
+        let raw: RawMessage = serde_json::from_value(json!({
+            "jsonrpc": "2.0", "method": "starlight.pre_check", "params": {}, "id": 42
+        }))
+        .unwrap();
+        assert_eq!(raw.id, Some(Id::Num(42)));
+        assert_eq!(raw.id.unwrap().to_string(), "42");
+
+        let request = JsonRpcRequest::new("starlight.registration", json!({}), "reg-1");
+        assert_eq!(serde_json::to_value(&request).unwrap()["id"], "reg-1");
+
+        let request = JsonRpcRequest::new("starlight.registration", json!({}), 7);
+        assert_eq!(serde_json::to_value(&request).unwrap()["id"], 7);
+
+        let response: JsonRpcResponse<serde_json::Value> = serde_json::from_value(
+            json!({ "jsonrpc": "2.0", "result": {}, "error": null, "id": 9 }),
+        )
+        .unwrap();
+        assert_eq!(response.id, Id::Num(9));
+    }
+
+    #[test]
+    fn test_decode_batch_frame() {
+        let frame = r#"[
+            {"jsonrpc": "2.0", "result": {"ok": true}, "id": 1},
+            {"jsonrpc": "2.0", "method": "starlight.entropy", "params": {}},
+            {"jsonrpc": "2.0", "error": {"code": -32601, "message": "nope"}, "id": "b"}
+        ]"#;
+        let messages = decode_frame(frame).unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0].is_response());
+        assert_eq!(messages[0].result, Some(json!({"ok": true})));
+        assert!(!messages[1].is_response());
+        assert_eq!(messages[2].error.as_ref().unwrap().code, -32601);
+
+        let single = decode_frame(r#"{"jsonrpc": "2.0", "method": "m", "params": {}}"#).unwrap();
+        assert_eq!(single.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_params_name_the_field() {
+        let message = |value: serde_json::Value| match from_params::<PreCheckParams>(value) {
+            Err(crate::error::Error::Protocol { code, message }) => {
+                assert_eq!(code, -32602);
+                message
+            }
+            other => panic!("expected invalid params, got {:?}", other),
+        };
+
+        let null = message(serde_json::Value::Null);
+        assert!(
+            null.starts_with("Invalid params: invalid type: null"),
+            "{}",
+            null
+        );
+
+        let missing = message(json!({ "selector": "#a" }));
+        assert!(missing.contains("missing field `command`"), "{}", missing);
+
+        let wrong = message(json!({ "command": "click", "blocking": [{ "selector": 7 }] }));
+        assert!(
+            wrong.starts_with("Invalid params.blocking[0].selector: invalid type"),
+            "{}",
+            wrong
+        );
+    }
+
+    #[test]
+    fn test_strict_params_report_unknown_paths() {
+        let strict = from_value_strict::<PreCheckParams>(json!({
+            "command": "click",
+            "priority": 3,
+            "blocking": [{ "selector": ".modal", "zIndex": 10 }],
+            "context": { "anything": "goes" },
+        }));
+
+        match strict {
+            Err(crate::error::Error::Protocol { code, message }) => {
+                assert_eq!(code, -32602);
+                assert!(message.contains("params.priority"), "{}", message);
+                assert!(message.contains("params.blocking[0].zIndex"), "{}", message);
+                assert!(!message.contains("anything"), "{}", message);
+            }
+            other => panic!("expected unknown field error, got {:?}", other),
+        }
+
+        let params: PreCheckParams = from_value_strict(json!({
+            "command": "click",
+            "blocking": [{ "selector": ".modal" }],
+            "context": { "anything": "goes" },
+        }))
+        .unwrap();
+        assert!(params.has_blocking());
+    }
+
+    #[test]
+    fn test_action_command_parse_and_display() {
+        assert_eq!(
+            "click".parse::<ActionCommand>().unwrap(),
+            ActionCommand::Click
+        );
+        assert_eq!(
+            "FILL".parse::<ActionCommand>().unwrap(),
+            ActionCommand::Fill
+        );
+        assert_eq!(
+            ActionCommand::try_from("Hide").unwrap(),
+            ActionCommand::Hide
+        );
+        assert_eq!(
+            ActionCommand::try_from("remove").unwrap(),
+            ActionCommand::Remove
+        );
+
+        for cmd in [
+            ActionCommand::Click,
+            ActionCommand::Fill,
+            ActionCommand::Hide,
+            ActionCommand::Remove,
+        ] {
+            let wire = serde_json::to_value(&cmd).unwrap();
+            assert_eq!(wire, cmd.to_string());
+            assert_eq!(cmd.to_string().parse::<ActionCommand>().unwrap(), cmd);
+        }
+
+        let err = "scroll".parse::<ActionCommand>().unwrap_err();
+        assert!(matches!(err, crate::error::Error::InvalidConfig(_)));
+        assert!(err.to_string().contains("'scroll'"));
+    }
+
+    #[test]
+    fn test_action_params_constructors() {
+        let fill = ActionParams::fill("#email", "me@example.com");
+        assert_eq!(fill.cmd, ActionCommand::Fill);
+        assert_eq!(fill.text.as_deref(), Some("me@example.com"));
+        assert!(fill.validate().is_ok());
+        for (params, cmd) in [
+            (ActionParams::click(".ok"), ActionCommand::Click),
+            (ActionParams::hide(".ok"), ActionCommand::Hide),
+            (ActionParams::remove(".ok"), ActionCommand::Remove),
+        ] {
+            assert_eq!(params.cmd, cmd);
+            assert_eq!(params.selector, ".ok");
+            assert_eq!(params.text, None);
+            assert!(params.validate().is_ok());
+        }
+
+        let err = ActionParams::new(ActionCommand::Fill, "#email", None).unwrap_err();
+        assert!(matches!(err, crate::error::Error::InvalidConfig(_)));
+        assert!(err.to_string().contains("'fill' on '#email' requires text"));
+        assert_eq!(
+            ActionParams::new(ActionCommand::Fill, "#email", Some("x".into())).unwrap(),
+            ActionParams::fill("#email", "x")
+        );
+        assert_eq!(
+            ActionParams::new(ActionCommand::Click, ".ok", None).unwrap(),
+            ActionParams::click(".ok")
+        );
+    }
+
+    #[test]
+    fn test_retry_after_from_error_data() {
+        let error: JsonRpcError = serde_json::from_value(json!({
+            "code": 429,
+            "message": "slow down",
+            "data": { "retry_after_ms": 5000 }
+        }))
+        .unwrap();
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(5)));
+        assert_eq!(JsonRpcError::new(429, "slow down").retry_after(), None);
+        let unusable =
+            JsonRpcError::new(429, "slow down").with_data(json!({ "retry_after_ms": "soon" }));
+        assert_eq!(unusable.retry_after(), None);
+
+        let ack = Ack::from_response(RawMessage {
+            jsonrpc: "2.0".to_string(),
+            method: String::new(),
+            params: serde_json::Value::Null,
+            id: Some(Id::from("1")),
+            result: None,
+            error: Some(error),
+        })
+        .unwrap();
+        assert!(!ack.accepted);
+        assert_eq!(ack.retry_after, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_hub_results_tolerate_new_fields() {
+        let result: RegistrationResult = serde_json::from_value(json!({
+            "hub_version": "4.0.0",
+            "session": { "id": "abc", "ttl": 30 },
+            "features": ["batching"]
+        }))
+        .unwrap();
+        assert_eq!(result.hub_version.as_deref(), Some("4.0.0"));
+        assert_eq!(result.protocol_version, None);
+        assert_eq!(result.hub_instance_id, None);
+
+        let ack = |result: serde_json::Value| {
+            Ack::from_response(RawMessage {
+                jsonrpc: "2.0".to_string(),
+                method: String::new(),
+                params: serde_json::Value::Null,
+                id: Some(Id::from("1")),
+                result: Some(result),
+                error: None,
+            })
+            .unwrap()
+        };
+        let extended = ack(json!({ "accepted": false, "retry_in_ms": 100 }));
+        assert!(!extended.accepted);
+        assert!(ack(json!({ "queued": true })).accepted);
+        assert!(!ack(json!(false)).accepted);
+        assert!(ack(json!("ok")).accepted);
+    }
+
+    #[test]
+    fn test_registration_sends_protocol_version() {
+        let value = serde_json::to_value(RegistrationParams::new("Janitor", 5)).unwrap();
+        assert_eq!(value["version"], crate::PROTOCOL_VERSION);
+        assert!(value.get("selector_priorities").is_none());
+
+        let result: RegistrationResult =
+            serde_json::from_value(json!({ "protocol_version": "1.2.0" })).unwrap();
+        assert_eq!(result.protocol_version.as_deref(), Some("1.2.0"));
+    }
+
+    #[test]
+    fn test_protocol_version_compatibility() {
+        // Matching and compatible-minor versions are accepted.
+        assert!(check_protocol_version(crate::PROTOCOL_VERSION).is_ok());
+        assert!(check_protocol_version("1.4.2").is_ok());
+
+        // Incompatible major or garbage versions are rejected.
+        for remote in ["2.0.0", "0.9.0", "latest"] {
+            let err = check_protocol_version(remote).unwrap_err();
+            assert!(matches!(err, crate::error::Error::Handshake(_)));
+            assert!(err.to_string().contains("protocol version mismatch"));
+        }
+    }
+
+    #[test]
+    fn test_decode_screenshot() {
+        const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+        let with_screenshot =
+            |screenshot: &str| pre_check(json!({ "command": "click", "screenshot": screenshot }));
+
+        assert!(pre_check(json!({ "command": "click" }))
+            .decode_screenshot()
+            .is_none());
+
+        let plain = with_screenshot("iVBORw0KGgo=");
+        assert_eq!(plain.decode_screenshot().unwrap().unwrap(), PNG_MAGIC);
+
+        let data_uri = with_screenshot("data:image/png;base64,iVBORw0KGgo");
+        assert_eq!(data_uri.decode_screenshot().unwrap().unwrap(), PNG_MAGIC);
+
+        let url_safe = with_screenshot("-_-_");
+        assert_eq!(
+            url_safe.decode_screenshot().unwrap().unwrap(),
+            vec![0xfb, 0xff, 0xbf]
+        );
+
+        let invalid = with_screenshot("not base64!");
+        assert!(matches!(
+            invalid.decode_screenshot(),
+            Some(Err(crate::error::Error::Base64(_)))
+        ));
+    }
 }
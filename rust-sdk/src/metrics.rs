@@ -0,0 +1,270 @@
+//! Counters and latency histograms collected by a running Sentinel.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::messages::PreCheckResponse;
+
+/// Upper bounds (in seconds) of the pre-check latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Live metrics, updated from the message loop.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    pre_checks: AtomicU64,
+    clears: AtomicU64,
+    waits: AtomicU64,
+    hijacks: AtomicU64,
+    defers: AtomicU64,
+    reconnects: AtomicU64,
+    latency: Histogram,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Per-bucket (non-cumulative) counts; the last slot is `+Inf`.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Metrics {
+    /// Record a received pre-check, however it ends up answered.
+    pub(crate) fn record_pre_check(&self) {
+        self.pre_checks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record how long the handler took to decide a pre-check.
+    pub(crate) fn record_latency(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let index = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.latency.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.latency.count.fetch_add(1, Ordering::Relaxed);
+        self.latency
+            .sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Record the decision sent for a pre-check.
+    pub(crate) fn record_response(&self, response: &PreCheckResponse) {
+        let counter = match response {
+            PreCheckResponse::Clear => &self.clears,
+            PreCheckResponse::Wait { .. } => &self.waits,
+            PreCheckResponse::Hijack { .. } => &self.hijacks,
+            PreCheckResponse::Defer { .. } => &self.defers,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a successful reconnection.
+    pub(crate) fn record_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Take a point-in-time copy of all metrics.
+    pub(crate) fn snapshot(&self) -> SentinelMetrics {
+        let mut cumulative = 0;
+        let buckets = LATENCY_BUCKETS
+            .iter()
+            .zip(&self.latency.buckets)
+            .map(|(bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (*bound, cumulative)
+            })
+            .collect();
+
+        SentinelMetrics {
+            pre_checks: self.pre_checks.load(Ordering::Relaxed),
+            clears: self.clears.load(Ordering::Relaxed),
+            waits: self.waits.load(Ordering::Relaxed),
+            hijacks: self.hijacks.load(Ordering::Relaxed),
+            defers: self.defers.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            pre_check_latency: LatencyHistogram {
+                buckets,
+                count: self.latency.count.load(Ordering::Relaxed),
+                sum: Duration::from_micros(self.latency.sum_micros.load(Ordering::Relaxed)),
+            },
+        }
+    }
+}
+
+/// A snapshot of Sentinel metrics, returned by [`Sentinel::metrics`](crate::Sentinel::metrics).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentinelMetrics {
+    /// Pre-checks handled, including those answered without calling the
+    /// handler (while paused, from a cache, ...)
+    pub pre_checks: u64,
+
+    /// Pre-checks answered with `Clear`
+    pub clears: u64,
+
+    /// Pre-checks answered with `Wait`
+    pub waits: u64,
+
+    /// Pre-checks answered with `Hijack`
+    pub hijacks: u64,
+
+    /// Pre-checks answered with `Defer`
+    pub defers: u64,
+
+    /// Successful reconnections
+    pub reconnects: u64,
+
+    /// Duration of `on_pre_check` handler calls
+    pub pre_check_latency: LatencyHistogram,
+}
+
+/// A latency histogram with cumulative buckets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyHistogram {
+    /// `(upper bound in seconds, observations <= bound)` for each bucket
+    /// in [`LATENCY_BUCKETS`]. Observations above the last bound are only
+    /// reflected in `count`.
+    pub buckets: Vec<(f64, u64)>,
+
+    /// Total observations
+    pub count: u64,
+
+    /// Sum of all observations
+    pub sum: Duration,
+}
+
+impl LatencyHistogram {
+    /// Mean observed latency, or `None` if nothing was observed.
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0)
+            .then(|| Duration::from_secs_f64(self.sum.as_secs_f64() / self.count as f64))
+    }
+
+    /// Estimate the `q` quantile (0.0-1.0) as the upper bound of the
+    /// bucket containing it.
+    ///
+    /// Returns `None` if nothing was observed or the quantile falls above
+    /// the last bucket.
+    pub fn percentile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        self.buckets
+            .iter()
+            .find(|(_, cumulative)| *cumulative >= rank)
+            .map(|(bound, _)| Duration::from_secs_f64(*bound))
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl SentinelMetrics {
+    /// Encode the metrics in the Prometheus text exposition format.
+    ///
+    /// Requires the `prometheus` feature.
+    pub fn encode_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        let counters = [
+            (
+                "starlight_pre_checks_total",
+                "Pre-checks handled by the Sentinel.",
+                self.pre_checks,
+            ),
+            (
+                "starlight_hijacks_total",
+                "Pre-checks answered with a hijack.",
+                self.hijacks,
+            ),
+            (
+                "starlight_reconnects_total",
+                "Successful reconnections to the Hub.",
+                self.reconnects,
+            ),
+        ];
+
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        let name = "starlight_pre_check_duration_seconds";
+        let latency = &self.pre_check_latency;
+        let _ = writeln!(out, "# HELP {} Duration of pre-check handler calls.", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, cumulative) in &latency.buckets {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, latency.count);
+        let _ = writeln!(out, "{}_sum {}", name, latency.sum.as_secs_f64());
+        let _ = writeln!(out, "{}_count {}", name, latency.count);
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_percentiles() {
+        let metrics = Metrics::default();
+        for _ in 0..9 {
+            metrics.record_latency(Duration::from_micros(500));
+        }
+        metrics.record_latency(Duration::from_millis(200));
+
+        let latency = metrics.snapshot().pre_check_latency;
+        assert_eq!(latency.count, 10);
+        assert_eq!(
+            latency.percentile(0.5),
+            Some(Duration::from_secs_f64(0.001))
+        );
+        assert_eq!(
+            latency.percentile(0.99),
+            Some(Duration::from_secs_f64(0.25))
+        );
+        assert_eq!(latency.buckets.last(), Some(&(5.0, 10)));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_encode_prometheus() {
+        let metrics = Metrics::default();
+        metrics.record_pre_check();
+        metrics.record_latency(Duration::from_millis(3));
+        metrics.record_response(&PreCheckResponse::Hijack {
+            reason: "popup".to_string(),
+            confidence: None,
+            targets: Vec::new(),
+        });
+        metrics.record_reconnect();
+
+        let text = metrics.snapshot().encode_prometheus();
+
+        // Every sample line is `name[{labels}] value` with a numeric value.
+        for line in text.lines().filter(|l| !l.starts_with('#')) {
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            assert!(value.parse::<f64>().is_ok(), "bad value in {:?}", line);
+            let name = series.split('{').next().unwrap();
+            assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        }
+
+        assert!(text.contains("# TYPE starlight_pre_checks_total counter"));
+        assert!(text.contains("starlight_pre_checks_total 1\n"));
+        assert!(text.contains("starlight_hijacks_total 1\n"));
+        assert!(text.contains("starlight_reconnects_total 1\n"));
+        assert!(text.contains("# TYPE starlight_pre_check_duration_seconds histogram"));
+        assert!(text.contains("starlight_pre_check_duration_seconds_bucket{le=\"0.0025\"} 0\n"));
+        assert!(text.contains("starlight_pre_check_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("starlight_pre_check_duration_seconds_bucket{le=\"+Inf\"} 1\n"));
+        assert!(text.contains("starlight_pre_check_duration_seconds_count 1\n"));
+    }
+}
@@ -0,0 +1,175 @@
+//! In-memory transport for testing Sentinels without a real Hub.
+//!
+//! # Example
+//! ```
+//! use starlight::mock::MockTransport;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let (transport, mut hub) = MockTransport::pair();
+//! hub.send_json(&serde_json::json!({
+//!     "jsonrpc": "2.0",
+//!     "method": "starlight.entropy",
+//!     "params": { "url": "https://example.com" }
+//! }));
+//! # drop(transport);
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::error::{Error, Result};
+use crate::messages::{decode_frame, RawMessage};
+use crate::transport::{Message, Transport};
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+/// A [`Transport`] backed by two in-memory channels.
+///
+/// Frames sent by the Sentinel are delivered to the paired [`MockHub`], and
+/// frames pushed by the `MockHub` are received by the Sentinel.
+pub struct MockTransport {
+    inbound: Mutex<mpsc::UnboundedReceiver<Message>>,
+    pending: Mutex<VecDeque<RawMessage>>,
+    outbound: mpsc::UnboundedSender<Message>,
+    connected: AtomicBool,
+    reconnects: AtomicU32,
+}
+
+/// The Hub side of a [`MockTransport`].
+pub struct MockHub {
+    to_sentinel: mpsc::UnboundedSender<Message>,
+    from_sentinel: mpsc::UnboundedReceiver<Message>,
+}
+
+impl MockTransport {
+    /// Create a connected transport and the Hub end used to script it.
+    pub fn pair() -> (Self, MockHub) {
+        let (to_sentinel, inbound) = mpsc::unbounded_channel();
+        let (outbound, from_sentinel) = mpsc::unbounded_channel();
+
+        let transport = Self {
+            inbound: Mutex::new(inbound),
+            pending: Mutex::new(VecDeque::new()),
+            outbound,
+            connected: AtomicBool::new(true),
+            reconnects: AtomicU32::new(0),
+        };
+        let hub = MockHub {
+            to_sentinel,
+            from_sentinel,
+        };
+
+        (transport, hub)
+    }
+
+    /// Number of times `reconnect()` has been called.
+    pub fn reconnect_count(&self) -> u32 {
+        self.reconnects.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn send(&self, message: Message) -> Result<()> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(Error::NotConnected);
+        }
+
+        self.outbound
+            .send(message)
+            .map_err(|_| Error::closed("Mock Hub dropped"))
+    }
+
+    async fn recv(&self) -> Result<Option<RawMessage>> {
+        let mut pending = self.pending.lock().await;
+        if let Some(msg) = pending.pop_front() {
+            return Ok(Some(msg));
+        }
+
+        match self.inbound.lock().await.recv().await {
+            Some(Message::Text(text)) => {
+                pending.extend(decode_frame(&text)?);
+                Ok(pending.pop_front())
+            }
+            Some(Message::Close(frame)) => {
+                self.connected.store(false, Ordering::SeqCst);
+                Err(Error::closed_by_hub(frame))
+            }
+            Some(_) => Ok(None),
+            None => {
+                self.connected.store(false, Ordering::SeqCst);
+                Err(Error::closed("Stream ended"))
+            }
+        }
+    }
+
+    async fn reconnect(&self) -> Result<()> {
+        self.reconnects.fetch_add(1, Ordering::SeqCst);
+        self.connected.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<()> {
+        if self.connected.swap(false, Ordering::SeqCst) {
+            let _ = self.outbound.send(Message::Close(None));
+        }
+        Ok(())
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+}
+
+impl MockHub {
+    /// Push a raw frame to the Sentinel.
+    pub fn send(&self, message: Message) {
+        let _ = self.to_sentinel.send(message);
+    }
+
+    /// Push a JSON message to the Sentinel as a text frame.
+    pub fn send_json<T: serde::Serialize>(&self, message: &T) {
+        let json = serde_json::to_string(message).expect("mock message must serialize");
+        self.send(Message::Text(json));
+    }
+
+    /// Simulate the Hub closing the connection.
+    ///
+    /// The Sentinel sees a close frame; a subsequent `reconnect()` resumes
+    /// the same channels.
+    pub fn disconnect(&self) {
+        self.send(Message::Close(None));
+    }
+
+    /// Simulate the Hub closing the connection with a close code and reason.
+    pub fn close(&self, code: u16, reason: &str) {
+        self.send(Message::Close(Some(CloseFrame {
+            code: code.into(),
+            reason: reason.to_string().into(),
+        })));
+    }
+
+    /// Wait for the next frame sent by the Sentinel.
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.from_sentinel.recv().await
+    }
+
+    /// Wait for the next text frame sent by the Sentinel, parsed as JSON.
+    pub async fn recv_json(&mut self) -> Option<serde_json::Value> {
+        loop {
+            match self.recv().await? {
+                Message::Text(text) => return serde_json::from_str(&text).ok(),
+                _ => continue,
+            }
+        }
+    }
+
+    /// Take a frame already sent by the Sentinel without waiting.
+    pub fn try_recv(&mut self) -> Option<Message> {
+        self.from_sentinel.try_recv().ok()
+    }
+}
@@ -0,0 +1,529 @@
+//! Several Sentinel layers sharing one connection to the Hub.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use futures_util::future::join_all;
+use serde_json::Value;
+use tokio::sync::RwLock;
+use tracing::{debug, error, info, warn};
+
+#[cfg(feature = "jwt")]
+use crate::auth::JwtHandler;
+use crate::cancel::CancelToken;
+use crate::client::{ClientConfig, WebSocketClient};
+use crate::error::{Error, Result};
+use crate::messages::{
+    check_protocol_version, from_params, methods, ContextUpdateParams, DeregisterParams,
+    EntropyParams, Id, JsonRpcNotification, JsonRpcRequest, PreCheckParams, PreCheckResponse,
+    RawMessage, RegistrationParams, RegistrationResult,
+};
+use crate::sentinel::{SentinelConfig, SentinelHandler};
+use crate::stream::PreCheckResponder;
+use crate::transport::{Message, Transport};
+
+/// One registered layer.
+struct Layer {
+    config: SentinelConfig,
+    handler: Arc<dyn SentinelHandler>,
+}
+
+/// Runs several Sentinel layers over a single connection.
+///
+/// Each layer registers separately with its own [`SentinelConfig`], and
+/// every response it sends carries its `layer` name so the Hub can tell
+/// them apart. A pre-check goes to the layer named by its `layer` param;
+/// without one, it goes to the layers monitoring one of its blocking
+/// selectors, or to every layer if none does, as if each had its own
+/// connection. Entropy and context updates reach every layer.
+///
+/// # Example
+/// ```rust,no_run
+/// use starlight::{DefaultHandler, SentinelConfig, SentinelMux};
+///
+/// # async fn example() -> starlight::Result<()> {
+/// let mut mux = SentinelMux::new()
+///     .with_layer(SentinelConfig::new("Janitor", 5).with_selectors(vec![".modal"]), DefaultHandler)
+///     .with_layer(SentinelConfig::new("Consent", 3).with_selectors(vec!["#cookies"]), DefaultHandler);
+/// mux.connect("ws://localhost:8080").await?;
+/// mux.run().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SentinelMux {
+    layers: Vec<Layer>,
+    transport: Option<Arc<dyn Transport>>,
+    auto_reconnect: bool,
+    running: RwLock<bool>,
+    /// Pending registration ids, mapped to the layer that sent them
+    registrations: StdMutex<HashMap<Id, usize>>,
+}
+
+impl Default for SentinelMux {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SentinelMux {
+    /// Create a mux with no layers.
+    pub fn new() -> Self {
+        Self {
+            layers: Vec::new(),
+            transport: None,
+            auto_reconnect: true,
+            running: RwLock::new(false),
+            registrations: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a mux over an existing transport, e.g. a
+    /// [`MockTransport`](crate::mock::MockTransport) in tests.
+    pub fn new_with_transport(transport: impl Transport + 'static) -> Self {
+        let mut mux = Self::new();
+        mux.transport = Some(Arc::new(transport));
+        mux
+    }
+
+    /// Add a layer, registered with `config` and answered by `handler`.
+    pub fn with_layer(
+        mut self,
+        config: SentinelConfig,
+        handler: impl SentinelHandler + 'static,
+    ) -> Self {
+        self.layers.push(Layer {
+            config,
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    /// Disable auto-reconnect.
+    pub fn without_auto_reconnect(mut self) -> Self {
+        self.auto_reconnect = false;
+        self
+    }
+
+    /// Connect to the Starlight Hub with the default client settings.
+    pub async fn connect(&mut self, url: &str) -> Result<()> {
+        self.connect_with(ClientConfig::new(url)).await
+    }
+
+    /// Connect to the Starlight Hub with a fully configured client and
+    /// register every layer.
+    pub async fn connect_with(&mut self, client_config: ClientConfig) -> Result<()> {
+        let client = WebSocketClient::new(client_config);
+        client.connect().await?;
+        self.transport = Some(Arc::new(client));
+
+        self.handshake().await
+    }
+
+    /// Register every layer over the current transport and notify the handlers.
+    pub async fn handshake(&self) -> Result<()> {
+        for (index, layer) in self.layers.iter().enumerate() {
+            let params = RegistrationParams::new(&layer.config.name, layer.config.priority)
+                .with_capabilities(layer.config.capabilities.clone())
+                .with_selectors(layer.config.selectors.clone())
+                .with_selector_priorities(layer.config.selector_priorities.clone());
+            #[cfg(feature = "jwt")]
+            let params = match layer.config.jwt_secret {
+                Some(ref secret) => params
+                    .with_auth_token(JwtHandler::new(secret).generate_token(&layer.config.name)?),
+                None => params,
+            };
+
+            let id = layer.config.next_id(methods::REGISTRATION);
+            self.registrations
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(id.clone(), index);
+            self.send_json(&JsonRpcRequest::new(methods::REGISTRATION, params, id))
+                .await?;
+            info!("{} registered with Hub", layer.config.name);
+        }
+
+        for layer in &self.layers {
+            layer.handler.on_connect().await;
+        }
+        Ok(())
+    }
+
+    /// Run the message loop for every layer.
+    ///
+    /// Blocks until the mux is stopped or disconnected. A registration
+    /// result reporting an incompatible protocol version returns
+    /// [`Error::Handshake`].
+    pub async fn run(&self) -> Result<()> {
+        let transport = self.transport()?;
+        // Cancelled when the connection is lost, replaced after reconnecting.
+        let mut cancel = CancelToken::new();
+
+        *self.running.write().await = true;
+        loop {
+            if !*self.running.read().await {
+                break;
+            }
+
+            match transport.recv().await {
+                Ok(Some(msg)) => {
+                    if let Err(e) = self.check_registration_result(&msg) {
+                        error!("{}", e);
+                        self.disconnected(&cancel).await;
+                        let _ = transport.close().await;
+                        return Err(e);
+                    }
+                    if let Err(e) = self.dispatch(msg, &cancel).await {
+                        warn!("Failed to handle message: {}", e);
+                        for layer in &self.layers {
+                            layer.handler.on_error(&e).await;
+                        }
+                    }
+                }
+                Ok(None) => continue,
+                Err(e @ Error::ConnectionClosed { .. })
+                    if self.auto_reconnect && transport.should_reconnect(&e) =>
+                {
+                    self.disconnected(&cancel).await;
+                    warn!("Connection lost, attempting reconnect...");
+                    if let Err(e) = transport.reconnect().await {
+                        error!("Reconnection failed: {}", e);
+                        break;
+                    }
+                    cancel = CancelToken::new();
+                    if let Err(e) = self.handshake().await {
+                        error!("Re-registration failed: {}", e);
+                        break;
+                    }
+                }
+                Err(e @ Error::ConnectionClosed { .. }) if !transport.should_reconnect(&e) => {
+                    error!("Hub closed the connection, not reconnecting: {}", e);
+                    self.disconnected(&cancel).await;
+                    *self.running.write().await = false;
+                    return Err(e);
+                }
+                Err(e) => {
+                    error!("Error: {}", e);
+                    self.disconnected(&cancel).await;
+                    break;
+                }
+            }
+        }
+
+        *self.running.write().await = false;
+        Ok(())
+    }
+
+    /// Deregister every layer and close the connection.
+    pub async fn stop(&self) {
+        *self.running.write().await = false;
+
+        if let Some(ref transport) = self.transport {
+            for layer in &self.layers {
+                let params = DeregisterParams {
+                    layer: layer.config.name.clone(),
+                };
+                let notification = JsonRpcNotification::new(methods::DEREGISTER, params);
+                if let Err(e) = self.send_json(&notification).await {
+                    warn!("{} could not deregister: {}", layer.config.name, e);
+                }
+            }
+            let _ = transport.close().await;
+        }
+    }
+
+    /// Get the transport, or fail if not connected.
+    #[allow(clippy::result_large_err)]
+    fn transport(&self) -> Result<&Arc<dyn Transport>> {
+        self.transport.as_ref().ok_or(Error::NotConnected)
+    }
+
+    async fn send_json<T: serde::Serialize>(&self, message: &T) -> Result<()> {
+        let json = serde_json::to_string(message)?;
+        self.transport()?.send(Message::Text(json)).await
+    }
+
+    /// Cancel in-flight decisions and notify every handler of a lost connection.
+    async fn disconnected(&self, cancel: &CancelToken) {
+        cancel.cancel();
+        for layer in &self.layers {
+            layer.handler.on_disconnect().await;
+        }
+    }
+
+    /// Check the protocol version in a registration result.
+    #[allow(clippy::result_large_err)]
+    fn check_registration_result(&self, msg: &RawMessage) -> Result<()> {
+        let Some(ref id) = msg.id else {
+            return Ok(());
+        };
+        if !msg.is_response() {
+            return Ok(());
+        }
+        let layer = self
+            .registrations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+        let (Some(layer), Some(Value::Object(result))) = (layer, &msg.result) else {
+            return Ok(());
+        };
+
+        let result: RegistrationResult = serde_json::from_value(Value::Object(result.clone()))?;
+        if let Some(ref version) = result.protocol_version {
+            check_protocol_version(version)?;
+        }
+        debug!(
+            "{} registration acknowledged",
+            self.layers[layer].config.name
+        );
+        Ok(())
+    }
+
+    /// Route an inbound message to the layers it concerns.
+    async fn dispatch(&self, msg: RawMessage, cancel: &CancelToken) -> Result<()> {
+        match msg.method.as_str() {
+            methods::PRE_CHECK => {
+                let targets = self.route(&msg.params);
+                let params: PreCheckParams = from_params(msg.params)?;
+                let id = msg.id.as_ref().map(Id::to_string).unwrap_or_default();
+
+                let decisions = targets.iter().map(|&index| {
+                    let layer = &self.layers[index];
+                    let (id, params, cancel) = (id.clone(), params.clone(), cancel.clone());
+                    async move { (layer, decide(layer, id, params, cancel).await) }
+                });
+                for (layer, response) in join_all(decisions).await {
+                    self.respond(layer, response).await?;
+                }
+            }
+            methods::ENTROPY => {
+                let params: EntropyParams = from_params(msg.params)?;
+                for layer in &self.layers {
+                    if params.is_stable() {
+                        layer.handler.on_entropy_stable(params.clone()).await;
+                    } else if layer.config.entropy_stable_only {
+                        continue;
+                    }
+                    layer.handler.on_entropy(params.clone()).await;
+                }
+            }
+            methods::CONTEXT_UPDATE => {
+                let params: ContextUpdateParams = from_params(msg.params)?;
+                for layer in &self.layers {
+                    layer
+                        .handler
+                        .on_context_update(params.context.clone())
+                        .await;
+                }
+            }
+            _ if msg.is_response() => {
+                debug!("Response to request {:?}", msg.id);
+            }
+            _ => {
+                for layer in &self.layers {
+                    layer
+                        .handler
+                        .on_unknown_method(&msg.method, msg.params.clone())
+                        .await;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Indices of the layers a pre-check should go to.
+    ///
+    /// A pre-check naming an unknown layer is routed as if it named none,
+    /// so the Hub still gets an answer.
+    fn route(&self, params: &Value) -> Vec<usize> {
+        if let Some(name) = params.get("layer").and_then(Value::as_str) {
+            match self
+                .layers
+                .iter()
+                .position(|layer| layer.config.name == name)
+            {
+                Some(index) => return vec![index],
+                None => warn!("Pre-check for unknown layer {}, routing by selector", name),
+            }
+        }
+
+        let blocking: Vec<&str> = params
+            .get("blocking")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|element| element.get("selector")?.as_str())
+            .collect();
+        let matching: Vec<usize> = self
+            .layers
+            .iter()
+            .enumerate()
+            .filter(|(_, layer)| {
+                layer
+                    .config
+                    .selectors
+                    .iter()
+                    .any(|selector| blocking.contains(&selector.as_str()))
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        if matching.is_empty() {
+            (0..self.layers.len()).collect()
+        } else {
+            matching
+        }
+    }
+
+    /// Send a layer's decision, tagged with its name.
+    async fn respond(&self, layer: &Layer, response: PreCheckResponse) -> Result<()> {
+        let method = match response {
+            PreCheckResponse::Clear => methods::CLEAR,
+            PreCheckResponse::Wait { .. } => methods::WAIT,
+            PreCheckResponse::Hijack { .. } => methods::HIJACK,
+            PreCheckResponse::Defer { .. } => methods::DEFER,
+        };
+        let mut params = serde_json::to_value(response)?;
+        params["layer"] = Value::String(layer.config.name.clone());
+
+        debug!("{} responding with {}", layer.config.name, method);
+        let json = serde_json::to_string(&JsonRpcNotification::new(method, params))?;
+        self.transport()?.send_decision(Message::Text(json)).await
+    }
+}
+
+/// Ask a layer's handler for a decision, honoring its `pre_check_timeout`.
+async fn decide(
+    layer: &Layer,
+    id: String,
+    params: PreCheckParams,
+    cancel: CancelToken,
+) -> PreCheckResponse {
+    let decision = async {
+        let (responder, response) = PreCheckResponder::new(cancel);
+        layer
+            .handler
+            .on_pre_check_deferred(id, params, responder)
+            .await;
+        response.await.ok()
+    };
+    let decided = match layer.config.pre_check_timeout {
+        Some(limit) => tokio::time::timeout(limit, decision).await.ok().flatten(),
+        None => decision.await,
+    };
+
+    decided.unwrap_or_else(|| {
+        warn!("{} did not decide, sending fallback", layer.config.name);
+        layer.config.pre_check_fallback.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+    use serde_json::json;
+
+    /// Hijacks, naming the layer it was registered for.
+    struct Named(&'static str);
+
+    #[async_trait::async_trait]
+    impl SentinelHandler for Named {
+        async fn on_pre_check(
+            &self,
+            _params: PreCheckParams,
+            _cancel: CancelToken,
+        ) -> PreCheckResponse {
+            PreCheckResponse::hijack(self.0)
+        }
+    }
+
+    fn pre_check(id: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "method": "starlight.pre_check", "params": params, "id": id })
+    }
+
+    #[tokio::test]
+    async fn test_routes_pre_checks_to_matching_layer() {
+        let (transport, mut hub) = MockTransport::pair();
+        let mux = Arc::new(
+            SentinelMux::new_with_transport(transport)
+                .with_layer(
+                    SentinelConfig::new("Janitor", 5).with_selectors(vec![".modal"]),
+                    Named("janitor"),
+                )
+                .with_layer(
+                    SentinelConfig::new("Consent", 3).with_selectors(vec!["#cookies"]),
+                    Named("consent"),
+                )
+                .without_auto_reconnect(),
+        );
+
+        mux.handshake().await.unwrap();
+        let janitor = hub.recv_json().await.unwrap();
+        let consent = hub.recv_json().await.unwrap();
+        assert_eq!(janitor["params"]["layer"], "Janitor");
+        assert_eq!(consent["params"]["layer"], "Consent");
+        assert_ne!(janitor["id"], consent["id"]);
+
+        let runner = tokio::spawn({
+            let mux = Arc::clone(&mux);
+            async move { mux.run().await }
+        });
+
+        // By blocking selector.
+        hub.send_json(&pre_check(
+            "pc-1",
+            json!({ "command": "click", "blocking": [{ "selector": "#cookies" }] }),
+        ));
+        let response = hub.recv_json().await.unwrap();
+        assert_eq!(response["method"], "starlight.hijack");
+        assert_eq!(response["params"]["layer"], "Consent");
+        assert_eq!(response["params"]["reason"], "consent");
+
+        // By explicit layer.
+        hub.send_json(&pre_check(
+            "pc-2",
+            json!({ "command": "click", "layer": "Janitor" }),
+        ));
+        let response = hub.recv_json().await.unwrap();
+        assert_eq!(response["params"]["layer"], "Janitor");
+        assert_eq!(response["params"]["reason"], "janitor");
+
+        // Unknown layer: routed by selector instead.
+        hub.send_json(&pre_check(
+            "pc-3",
+            json!({ "command": "click", "layer": "Ghost", "blocking": [{ "selector": ".modal" }] }),
+        ));
+        let response = hub.recv_json().await.unwrap();
+        assert_eq!(response["params"]["layer"], "Janitor");
+
+        // Unmatched: every layer answers for itself.
+        hub.send_json(&pre_check("pc-4", json!({ "command": "click" })));
+        let mut layers = vec![
+            hub.recv_json().await.unwrap()["params"]["layer"].clone(),
+            hub.recv_json().await.unwrap()["params"]["layer"].clone(),
+        ];
+        layers.sort_by_key(|layer| layer.to_string());
+        assert_eq!(layers, vec![json!("Consent"), json!("Janitor")]);
+        assert!(hub.try_recv().is_none());
+
+        hub.disconnect();
+        runner.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stop_deregisters_every_layer() {
+        let (transport, mut hub) = MockTransport::pair();
+        let mux = SentinelMux::new_with_transport(transport)
+            .with_layer(SentinelConfig::new("Janitor", 5), Named("janitor"))
+            .with_layer(SentinelConfig::new("Consent", 3), Named("consent"));
+
+        mux.stop().await;
+        for layer in ["Janitor", "Consent"] {
+            let deregister = hub.recv_json().await.unwrap();
+            assert_eq!(deregister["method"], "starlight.deregister");
+            assert_eq!(deregister["params"]["layer"], layer);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Outbound proxy support (HTTP `CONNECT` and SOCKS5).
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::http::Uri;
+use tracing::debug;
+
+use crate::error::{Error, Result};
+
+/// Proxy through which the Hub connection is tunneled.
+///
+/// Supported URL schemes are `http://` (tunneled with `CONNECT`) and
+/// `socks5://` / `socks5h://` (the Hub hostname is resolved by the proxy).
+///
+/// # Example
+/// ```
+/// use starlight::client::ClientConfig;
+/// use starlight::proxy::ProxyConfig;
+///
+/// let proxy = ProxyConfig::new("http://proxy.corp:3128").with_credentials("ci", "hunter2");
+/// let config = ClientConfig::new("ws://hub.internal:8080").with_proxy(proxy);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    /// Proxy URL (e.g., "http://proxy:3128" or "socks5://proxy:1080")
+    pub url: String,
+
+    /// Optional username for proxy authentication
+    pub username: Option<String>,
+
+    /// Optional password for proxy authentication
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProxyKind {
+    Http,
+    Socks5,
+}
+
+impl ProxyConfig {
+    /// Create a proxy config without credentials.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            username: None,
+            password: None,
+        }
+    }
+
+    /// Set proxy credentials.
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Open a TCP stream to `host:port` tunneled through the proxy.
+    pub(crate) async fn connect(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let (kind, proxy_host, proxy_port) = self.parse()?;
+        debug!("Connecting to {}:{} via proxy {}", host, port, self.url);
+
+        let mut stream = TcpStream::connect((proxy_host.as_str(), proxy_port))
+            .await
+            .map_err(|e| Error::Proxy(format!("Cannot reach proxy {}: {}", self.url, e)))?;
+
+        match kind {
+            ProxyKind::Http => self.http_connect(&mut stream, host, port).await?,
+            ProxyKind::Socks5 => self.socks5_connect(&mut stream, host, port).await?,
+        }
+
+        Ok(stream)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn parse(&self) -> Result<(ProxyKind, String, u16)> {
+        let uri: Uri = self
+            .url
+            .parse()
+            .map_err(|e| Error::InvalidConfig(format!("Invalid proxy URL {}: {}", self.url, e)))?;
+
+        let (kind, default_port) = match uri.scheme_str() {
+            Some("http") => (ProxyKind::Http, 80),
+            Some("socks5") | Some("socks5h") => (ProxyKind::Socks5, 1080),
+            other => {
+                return Err(Error::InvalidConfig(format!(
+                    "Unsupported proxy scheme: {}",
+                    other.unwrap_or("<none>")
+                )))
+            }
+        };
+
+        let host = uri
+            .host()
+            .ok_or_else(|| Error::InvalidConfig(format!("Proxy URL has no host: {}", self.url)))?;
+
+        Ok((
+            kind,
+            host.to_string(),
+            uri.port_u16().unwrap_or(default_port),
+        ))
+    }
+
+    async fn http_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> Result<()> {
+        let target = format!("{}:{}", host, port);
+        let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+
+        if let Some(ref username) = self.username {
+            let password = self.password.as_deref().unwrap_or_default();
+            let token = STANDARD.encode(format!("{}:{}", username, password));
+            request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+        }
+        request.push_str("\r\n");
+
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(io_error)?;
+
+        // Read the response head byte by byte so nothing past it is consumed.
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() > 8192 {
+                return Err(Error::Proxy("Proxy response header too large".to_string()));
+            }
+            let byte = stream.read_u8().await.map_err(io_error)?;
+            head.push(byte);
+        }
+
+        let head = String::from_utf8_lossy(&head);
+        let status_line = head.lines().next().unwrap_or_default();
+        let status = status_line.split_whitespace().nth(1);
+
+        if status != Some("200") {
+            return Err(Error::Proxy(format!("CONNECT rejected: {}", status_line)));
+        }
+
+        Ok(())
+    }
+
+    async fn socks5_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> Result<()> {
+        const VERSION: u8 = 0x05;
+        const NO_AUTH: u8 = 0x00;
+        const USER_PASS: u8 = 0x02;
+
+        // Greeting: offer username/password only when credentials are set.
+        let greeting: &[u8] = if self.username.is_some() {
+            &[VERSION, 2, NO_AUTH, USER_PASS]
+        } else {
+            &[VERSION, 1, NO_AUTH]
+        };
+        stream.write_all(greeting).await.map_err(io_error)?;
+
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await.map_err(io_error)?;
+
+        match choice {
+            [VERSION, NO_AUTH] => {}
+            [VERSION, USER_PASS] => {
+                let username = self.username.as_deref().unwrap_or_default();
+                let password = self.password.as_deref().unwrap_or_default();
+                if username.len() > 255 || password.len() > 255 {
+                    return Err(Error::Proxy("SOCKS5 credentials too long".to_string()));
+                }
+
+                let mut auth = vec![0x01, username.len() as u8];
+                auth.extend_from_slice(username.as_bytes());
+                auth.push(password.len() as u8);
+                auth.extend_from_slice(password.as_bytes());
+                stream.write_all(&auth).await.map_err(io_error)?;
+
+                let mut status = [0u8; 2];
+                stream.read_exact(&mut status).await.map_err(io_error)?;
+                if status[1] != 0x00 {
+                    return Err(Error::Proxy("SOCKS5 authentication failed".to_string()));
+                }
+            }
+            _ => {
+                return Err(Error::Proxy(
+                    "SOCKS5 proxy offered no usable auth method".to_string(),
+                ))
+            }
+        }
+
+        if host.len() > 255 {
+            return Err(Error::Proxy("Hub hostname too long for SOCKS5".to_string()));
+        }
+
+        // CONNECT with a domain name address so the proxy resolves it.
+        let mut request = vec![VERSION, 0x01, 0x00, 0x03, host.len() as u8];
+        request.extend_from_slice(host.as_bytes());
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await.map_err(io_error)?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await.map_err(io_error)?;
+        if reply[1] != 0x00 {
+            return Err(Error::Proxy(format!(
+                "SOCKS5 CONNECT failed (reply {})",
+                reply[1]
+            )));
+        }
+
+        // Skip the bound address the proxy reports.
+        let addr_len = match reply[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => stream.read_u8().await.map_err(io_error)? as usize,
+            other => {
+                return Err(Error::Proxy(format!(
+                    "SOCKS5 invalid address type {}",
+                    other
+                )))
+            }
+        };
+        let mut bound = vec![0u8; addr_len + 2];
+        stream.read_exact(&mut bound).await.map_err(io_error)?;
+
+        Ok(())
+    }
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::Proxy(format!("Proxy I/O error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proxy_urls() {
+        let (kind, host, port) = ProxyConfig::new("http://proxy.corp:3128").parse().unwrap();
+        assert_eq!(
+            (kind, host.as_str(), port),
+            (ProxyKind::Http, "proxy.corp", 3128)
+        );
+
+        let (kind, _, port) = ProxyConfig::new("socks5://proxy.corp").parse().unwrap();
+        assert_eq!((kind, port), (ProxyKind::Socks5, 1080));
+
+        assert!(matches!(
+            ProxyConfig::new("ftp://proxy.corp").parse(),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+//! Outbound rate limiting, so a misbehaving handler can't flood the Hub.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::messages::methods;
+
+/// A token bucket limit for one protocol method.
+///
+/// Allows bursts of up to `burst` messages, then one more every `per`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Messages that may be sent back-to-back
+    pub burst: u32,
+
+    /// Time to earn back one message
+    pub per: Duration,
+}
+
+impl RateLimit {
+    /// Allow `burst` messages at once, refilling one every `per`.
+    pub fn new(burst: u32, per: Duration) -> Self {
+        Self { burst, per }
+    }
+}
+
+/// What to do with a message that exceeds its rate limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Drop the message and log a warning; the send still succeeds
+    #[default]
+    Drop,
+
+    /// Fail the send with [`Error::RateLimited`](crate::Error::RateLimited)
+    Error,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Tracks the state of every limit configured on a client.
+#[derive(Debug, Default)]
+pub(crate) struct OutboundLimiter {
+    buckets: HashMap<String, Bucket>,
+    last_hijack: Option<(String, Instant)>,
+}
+
+impl OutboundLimiter {
+    /// Decide whether a message may be sent, consuming a token if so.
+    ///
+    /// A hijack whose reason matches the previous hijack sent less than
+    /// `coalesce` ago is rejected as a duplicate.
+    pub(crate) fn admit(
+        &mut self,
+        method: &str,
+        hijack_reason: Option<&str>,
+        limit: Option<RateLimit>,
+        coalesce: Option<Duration>,
+    ) -> bool {
+        let now = Instant::now();
+
+        if method == methods::HIJACK {
+            if let (Some(window), Some(reason), Some((last, at))) =
+                (coalesce, hijack_reason, &self.last_hijack)
+            {
+                if reason == last && now.duration_since(*at) < window {
+                    return false;
+                }
+            }
+        }
+
+        if let Some(limit) = limit {
+            let bucket = self
+                .buckets
+                .entry(method.to_string())
+                .or_insert_with(|| Bucket {
+                    tokens: f64::from(limit.burst),
+                    refilled: now,
+                });
+
+            let earned = now.duration_since(bucket.refilled).as_secs_f64()
+                / limit.per.as_secs_f64().max(f64::EPSILON);
+            bucket.tokens = (bucket.tokens + earned).min(f64::from(limit.burst));
+            bucket.refilled = now;
+
+            if bucket.tokens < 1.0 {
+                return false;
+            }
+            bucket.tokens -= 1.0;
+        }
+
+        if method == methods::HIJACK {
+            if let Some(reason) = hijack_reason {
+                self.last_hijack = Some((reason.to_string(), now));
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,155 @@
+//! Recording sessions to a file and replaying them against a handler.
+//!
+//! With [`SentinelConfig::with_record_to`](crate::SentinelConfig::with_record_to),
+//! every message the Sentinel handles or sends is appended to a JSONL file,
+//! one [`RecordedFrame`] per line. [`replay`] later feeds the recorded
+//! inbound messages through the same dispatch logic, without a Hub, which
+//! makes protocol issues reproducible offline.
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::error::Result;
+use crate::interceptor::MessageInterceptor;
+use crate::messages::RawMessage;
+use crate::mock::MockTransport;
+use crate::sentinel::{Sentinel, SentinelConfig, SentinelHandler};
+use crate::transport::Message;
+
+/// Which way a recorded frame travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Hub → Sentinel
+    Inbound,
+    /// Sentinel → Hub
+    Outbound,
+}
+
+/// One line of a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub direction: Direction,
+    pub timestamp: DateTime<Utc>,
+    pub frame: Value,
+}
+
+/// Appends every frame it sees to a JSONL file.
+///
+/// Runs as the last interceptor, so outbound frames are recorded as sent.
+pub(crate) struct Recorder {
+    path: PathBuf,
+    file: Mutex<Option<tokio::fs::File>>,
+}
+
+impl Recorder {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            file: Mutex::new(None),
+        }
+    }
+
+    async fn record(&self, direction: Direction, frame: Value) {
+        let record = RecordedFrame {
+            direction,
+            timestamp: Utc::now(),
+            frame,
+        };
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Cannot record frame: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        if file.is_none() {
+            let opened = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await;
+            match opened {
+                Ok(opened) => *file = Some(opened),
+                Err(e) => {
+                    warn!("Cannot open {}: {}", self.path.display(), e);
+                    return;
+                }
+            }
+        }
+        if let Some(file) = file.as_mut() {
+            if let Err(e) = file.write_all(&line).await {
+                warn!("Cannot write to {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl MessageInterceptor for Recorder {
+    async fn on_outbound(&self, frame: &mut Value) {
+        self.record(Direction::Outbound, frame.clone()).await;
+    }
+
+    async fn on_inbound(&self, msg: &RawMessage) {
+        match serde_json::to_value(msg) {
+            Ok(frame) => self.record(Direction::Inbound, frame).await,
+            Err(e) => warn!("Cannot record frame: {}", e),
+        }
+    }
+}
+
+/// Read a recording made with `record_to`.
+pub async fn read_recording(path: impl AsRef<Path>) -> Result<Vec<RecordedFrame>> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| crate::error::Error::InvalidConfig(format!("Cannot read recording: {}", e)))?;
+
+    let frames = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect::<serde_json::Result<_>>()?;
+    Ok(frames)
+}
+
+/// Replay the inbound frames of a recording against `handler`.
+///
+/// The frames are dispatched one at a time, as a running Sentinel would,
+/// with responses going to an in-memory transport. Returns the frames the Sentinel sent in response,
+/// which can be compared with the recording's outbound frames.
+pub async fn replay<H: SentinelHandler + 'static>(
+    path: impl AsRef<Path>,
+    handler: H,
+) -> Result<Vec<Value>> {
+    let inbound = read_recording(path)
+        .await?
+        .into_iter()
+        .filter(|record| record.direction == Direction::Inbound)
+        .map(|record| serde_json::from_value(record.frame))
+        .collect::<serde_json::Result<Vec<RawMessage>>>()?;
+
+    let (transport, mut hub) = MockTransport::pair();
+    let config = SentinelConfig::new("Replay", 5);
+    Sentinel::new_with_transport(config, handler, transport)
+        .dispatch_all(inbound)
+        .await?;
+
+    let mut sent = Vec::new();
+    while let Some(frame) = hub.try_recv() {
+        if let Message::Text(text) = frame {
+            sent.push(serde_json::from_str(&text)?);
+        }
+    }
+    Ok(sent)
+}
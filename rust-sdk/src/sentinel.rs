@@ -1,20 +1,54 @@
 //! Sentinel implementation for the Starlight Protocol.
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
 
-use tokio::sync::RwLock;
-use tracing::{debug, error, info, warn};
+use futures_util::Stream;
+use tokio::sync::{broadcast, mpsc, Mutex, RwLock, Semaphore};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
 use uuid::Uuid;
 
+#[cfg(feature = "jwt")]
 use crate::auth::JwtHandler;
+use crate::backoff::{BackoffStrategy, ConstantBackoff};
+use crate::cache::{DecisionCache, DecisionKey};
+use crate::cancel::CancelToken;
 use crate::client::{ClientConfig, WebSocketClient};
+use crate::clock::Delay;
+use crate::correlation::PendingRequests;
 use crate::error::{Error, Result};
+use crate::events::SentinelEvent;
+use crate::health::HealthStatus;
+use crate::hijack::HijackSession;
+use crate::interceptor::{intercept, send_intercepted, Interceptors, MessageInterceptor};
 use crate::messages::{
-    methods, ActionCommand, ActionParams, ContextUpdateParams, EntropyParams, HijackParams,
-    JsonRpcNotification, JsonRpcRequest, PreCheckParams, PreCheckResponse, RawMessage,
-    RegistrationParams, ResumeParams,
+    check_protocol_version, from_params, from_value_strict, methods, Ack, ActionCommand,
+    ActionParams, ContextUpdateParams, DeregisterParams, EntropyParams, HijackParams, HubInfo, Id,
+    IntentParams, JsonRpcError, JsonRpcNotification, JsonRpcRequest, JsonRpcResponse,
+    PreCheckParams, PreCheckResponse, RawMessage, RegistrationParams, RegistrationResult,
+    ResumeParams, UpdateSelectorsParams,
 };
+use crate::metrics::{Metrics, SentinelMetrics};
+use crate::record::Recorder;
+use crate::signal::{self, ShutdownSignal};
+use crate::stream::{self, PreCheckRequest, PreCheckResponder, PreCheckSubscriber};
+use crate::transport::{self, DryRunTransport, Transport};
+
+/// How pre-check decisions are sent, depending on what the Hub expects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponseDialect {
+    /// A JSON-RPC response to the pre-check request, with the decision as
+    /// its `result`
+    Response,
+
+    /// A separate `starlight.clear`/`wait`/`hijack`/`defer` notification,
+    /// as specified by protocol 1.0 and understood by every Hub
+    #[default]
+    Notification,
+}
 
 /// Sentinel configuration.
 #[derive(Debug, Clone)]
@@ -25,6 +59,9 @@ pub struct SentinelConfig {
     /// Priority (1-10, lower = higher priority)
     pub priority: u8,
 
+    /// Priorities (1-10) for specific selectors, overriding `priority`
+    pub selector_priorities: HashMap<String, u8>,
+
     /// Capabilities
     pub capabilities: Vec<String>,
 
@@ -32,10 +69,104 @@ pub struct SentinelConfig {
     pub selectors: Vec<String>,
 
     /// JWT secret for authentication (optional)
+    #[cfg(feature = "jwt")]
     pub jwt_secret: Option<String>,
 
+    /// Hub to connect to, when read by [`SentinelConfig::from_env`]
+    pub hub_url: Option<String>,
+
     /// Auto-reconnect on disconnect
     pub auto_reconnect: bool,
+
+    /// Maximum number of messages handled at once (1 = sequentially)
+    pub max_concurrent_handlers: usize,
+
+    /// Maximum pre-checks being decided at once (0 = unlimited); the rest
+    /// are answered with `Wait`
+    pub max_in_flight_pre_checks: usize,
+
+    /// `retry_after` sent with the `Wait` for pre-checks over the limit
+    pub in_flight_retry_after: Duration,
+
+    /// Reject incoming params with fields this SDK doesn't know
+    pub strict_messages: bool,
+
+    /// Maximum time `on_pre_check` may take (unbounded when `None`)
+    pub pre_check_timeout: Option<Duration>,
+
+    /// Decision sent when `on_pre_check` exceeds `pre_check_timeout`
+    pub pre_check_fallback: PreCheckResponse,
+
+    /// Whether decisions are sent as responses or notifications
+    pub response_dialect: ResponseDialect,
+
+    /// Only call `on_entropy` once the page is stable
+    pub entropy_stable_only: bool,
+
+    /// Answer `Clear` without calling `on_pre_check` while the last entropy
+    /// update was stable and nothing is blocking
+    pub auto_clear_when_stable: bool,
+
+    /// Log each pre-check decision at info level for audit trails
+    pub log_decisions: bool,
+
+    /// Log outbound messages instead of sending them, except registration
+    pub dry_run: bool,
+
+    /// Check actions with [`ActionParams::validate`] before sending them
+    pub validate_actions: bool,
+
+    /// How long `*_and_wait` calls wait for the Hub's acknowledgement
+    pub request_timeout: Duration,
+
+    /// Append every handled and sent frame to this JSONL file (see [`crate::record`])
+    pub record_to: Option<PathBuf>,
+
+    /// Signals that stop [`Sentinel::run_until_signal`]
+    pub shutdown_signals: Vec<ShutdownSignal>,
+
+    /// Call `on_idle` when no message arrives for this long (never when `None`)
+    pub idle_timeout: Option<Duration>,
+
+    /// Maximum number of cached pre-check decisions (0 = no cache)
+    pub decision_cache_size: usize,
+
+    /// How long a cached pre-check decision stays valid
+    pub decision_cache_ttl: Duration,
+
+    /// How long to keep decisions lost to a disconnect for the Hub's
+    /// repeated pre-check (not kept when `None`)
+    pub unsent_decision_ttl: Option<Duration>,
+
+    /// Generates the ids of requests sent to the Hub (UUIDs when `None`)
+    pub id_generator: Option<IdGenerator>,
+
+    /// Put in front of generated UUID ids to tell instances apart in Hub
+    /// logs (ignored when `id_generator` is set)
+    pub id_prefix: Option<String>,
+
+    /// Waits out the Hub's `retry_after_ms` (tokio's timer when `None`)
+    pub delay: Option<Arc<dyn Delay>>,
+
+    /// Stop `run` after this many messages fail in a row (never when `None`)
+    pub max_consecutive_errors: Option<u32>,
+
+    /// Times `action` and `hijack` resend after a transient write error
+    pub send_retries: u32,
+
+    /// Wait between those resends
+    pub send_retry_delay: Duration,
+}
+
+/// Generates request ids from the request's method, for
+/// [`SentinelConfig::with_id_generator`].
+#[derive(Clone)]
+pub struct IdGenerator(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl std::fmt::Debug for IdGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("IdGenerator")
+    }
 }
 
 impl SentinelConfig {
@@ -56,11 +187,132 @@ impl SentinelConfig {
         Self {
             name: name.into(),
             priority: priority.clamp(1, 10),
+            selector_priorities: HashMap::new(),
             capabilities: Vec::new(),
             selectors: Vec::new(),
+            #[cfg(feature = "jwt")]
             jwt_secret: None,
+            hub_url: None,
             auto_reconnect: true,
+            max_concurrent_handlers: 1,
+            max_in_flight_pre_checks: 0,
+            in_flight_retry_after: Duration::from_millis(250),
+            strict_messages: false,
+            pre_check_timeout: None,
+            pre_check_fallback: PreCheckResponse::Clear,
+            response_dialect: ResponseDialect::default(),
+            entropy_stable_only: false,
+            auto_clear_when_stable: false,
+            log_decisions: false,
+            dry_run: false,
+            validate_actions: false,
+            request_timeout: Duration::from_secs(10),
+            record_to: None,
+            shutdown_signals: ShutdownSignal::defaults(),
+            idle_timeout: None,
+            decision_cache_size: 0,
+            decision_cache_ttl: Duration::from_secs(5),
+            unsent_decision_ttl: None,
+            id_generator: None,
+            id_prefix: None,
+            delay: None,
+            max_consecutive_errors: None,
+            send_retries: 0,
+            send_retry_delay: Duration::from_millis(100),
+        }
+    }
+
+    /// Build a configuration from `{prefix}_*` environment variables.
+    ///
+    /// * `{prefix}_NAME` - layer name (required)
+    /// * `{prefix}_PRIORITY` - priority 1-10 (required)
+    /// * `{prefix}_SELECTORS` - comma-separated CSS selectors
+    /// * `{prefix}_CAPABILITIES` - comma-separated capabilities
+    /// * `{prefix}_JWT_SECRET` - JWT secret (needs the `jwt` feature)
+    /// * `{prefix}_HUB_URL` - `ws://` or `wss://` URL, kept in `hub_url`
+    ///
+    /// Empty variables count as unset. Fails with `Error::InvalidConfig`
+    /// naming the variable if a required one is missing or a value is
+    /// invalid. Everything else keeps its default and can be changed with
+    /// the usual builders.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use starlight::SentinelConfig;
+    ///
+    /// // JANITOR_NAME=Janitor JANITOR_PRIORITY=5 JANITOR_SELECTORS=".modal, .popup"
+    /// let config = SentinelConfig::from_env("JANITOR")?.without_auto_reconnect();
+    /// # Ok::<(), starlight::Error>(())
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn from_env(prefix: &str) -> Result<Self> {
+        let key = |name: &str| format!("{}_{}", prefix, name);
+        let var = |name: &str| {
+            std::env::var(key(name))
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let required = |name: &str| {
+            var(name).ok_or_else(|| Error::InvalidConfig(format!("{} is not set", key(name))))
+        };
+        let list = |name: &str| -> Vec<String> {
+            var(name)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let name = required("NAME")?;
+        let priority = required("PRIORITY")?;
+        let priority = match priority.parse::<u8>() {
+            Ok(priority @ 1..=10) => priority,
+            _ => {
+                return Err(Error::InvalidConfig(format!(
+                    "{} must be a number from 1 to 10, got {:?}",
+                    key("PRIORITY"),
+                    priority
+                )))
+            }
+        };
+
+        let mut config = Self::new(name, priority)
+            .with_selectors(list("SELECTORS"))
+            .with_capabilities(list("CAPABILITIES"));
+
+        if let Some(url) = var("HUB_URL") {
+            if !url.starts_with("ws://") && !url.starts_with("wss://") {
+                return Err(Error::InvalidConfig(format!(
+                    "{} must be a ws:// or wss:// URL, got {:?}",
+                    key("HUB_URL"),
+                    url
+                )));
+            }
+            config.hub_url = Some(url);
+        }
+
+        if let Some(secret) = var("JWT_SECRET") {
+            #[cfg(feature = "jwt")]
+            {
+                config = config.with_jwt_secret(secret);
+            }
+            #[cfg(not(feature = "jwt"))]
+            {
+                drop(secret);
+                return Err(Error::InvalidConfig(format!(
+                    "{} is set but the jwt feature is disabled",
+                    key("JWT_SECRET")
+                )));
+            }
         }
+
+        Ok(config)
     }
 
     /// Add capabilities.
@@ -75,17 +327,357 @@ impl SentinelConfig {
         self
     }
 
+    /// Give `selector` its own priority (1-10, lower = higher priority).
+    ///
+    /// Lets urgent obstacles such as captchas outrank cosmetic ones while
+    /// other selectors keep the Sentinel's `priority`.
+    pub fn with_selector_priority(mut self, selector: impl Into<String>, priority: u8) -> Self {
+        self.selector_priorities
+            .insert(selector.into(), priority.clamp(1, 10));
+        self
+    }
+
     /// Set JWT secret for authentication.
+    #[cfg(feature = "jwt")]
     pub fn with_jwt_secret(mut self, secret: impl Into<String>) -> Self {
         self.jwt_secret = Some(secret.into());
         self
     }
 
+    /// Read the JWT secret from an environment variable.
+    ///
+    /// Fails with `Error::InvalidConfig` if the variable is unset or empty.
+    #[cfg(feature = "jwt")]
+    #[allow(clippy::result_large_err)]
+    pub fn with_jwt_secret_from_env(self, var: &str) -> Result<Self> {
+        match std::env::var(var) {
+            Ok(secret) if !secret.is_empty() => Ok(self.with_jwt_secret(secret)),
+            _ => Err(Error::InvalidConfig(format!(
+                "JWT secret environment variable {} is not set",
+                var
+            ))),
+        }
+    }
+
+    /// Read the JWT secret from a file, ignoring trailing newlines.
+    ///
+    /// Fails with `Error::InvalidConfig` if the file can't be read or is empty.
+    #[cfg(feature = "jwt")]
+    #[allow(clippy::result_large_err)]
+    pub fn with_jwt_secret_from_file(self, path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::InvalidConfig(format!(
+                "Cannot read JWT secret file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        let secret = contents.trim_end_matches(['\r', '\n']);
+        if secret.is_empty() {
+            return Err(Error::InvalidConfig(format!(
+                "JWT secret file {} is empty",
+                path.display()
+            )));
+        }
+
+        Ok(self.with_jwt_secret(secret))
+    }
+
     /// Disable auto-reconnect.
     pub fn without_auto_reconnect(mut self) -> Self {
         self.auto_reconnect = false;
         self
     }
+
+    /// Fail on unknown fields in Hub messages instead of ignoring them.
+    ///
+    /// Useful for catching protocol drift between the Hub and this SDK;
+    /// offending messages are reported to `on_error` as `Error::Protocol`.
+    pub fn with_strict_messages(mut self) -> Self {
+        self.strict_messages = true;
+        self
+    }
+
+    /// Handle up to `limit` messages concurrently.
+    ///
+    /// By default messages are handled one at a time, so a slow handler
+    /// delays the next message. With a limit above 1, each message runs in
+    /// its own task and the loop stops reading once `limit` are in flight.
+    pub fn with_max_concurrent_handlers(mut self, limit: usize) -> Self {
+        self.max_concurrent_handlers = limit.max(1);
+        self
+    }
+
+    /// Decide at most `limit` pre-checks at once.
+    ///
+    /// Pre-checks arriving while `limit` are outstanding are answered with
+    /// `Wait { retry_after }` straight away, without calling the handler.
+    /// Only has an effect together with `with_max_concurrent_handlers`,
+    /// since sequential handling never has more than one in flight.
+    pub fn with_max_in_flight_pre_checks(mut self, limit: usize, retry_after: Duration) -> Self {
+        self.max_in_flight_pre_checks = limit;
+        self.in_flight_retry_after = retry_after;
+        self
+    }
+
+    /// Skip `on_entropy` for updates where the page is still changing.
+    ///
+    /// `on_entropy_stable` is called for stable updates either way.
+    pub fn with_entropy_stable_only(mut self) -> Self {
+        self.entropy_stable_only = true;
+        self
+    }
+
+    /// Clear pre-checks automatically once the page has settled.
+    ///
+    /// While the most recent entropy update showed no mutations and no
+    /// pending network requests, pre-checks without blocking elements are
+    /// answered with `Clear` without calling `on_pre_check`.
+    pub fn with_auto_clear_when_stable(mut self) -> Self {
+        self.auto_clear_when_stable = true;
+        self
+    }
+
+    /// Log every pre-check decision with its command, selector, and number
+    /// of blocking elements as one structured `info` event.
+    pub fn with_log_decisions(mut self) -> Self {
+        self.log_decisions = true;
+        self
+    }
+
+    /// Choose how pre-check decisions are sent.
+    ///
+    /// Hubs that correlate decisions with their requests expect
+    /// [`ResponseDialect::Response`]: a JSON-RPC response carrying the
+    /// pre-check's id, with the decision (tagged by `response`) as its
+    /// `result`. The default, [`ResponseDialect::Notification`], sends
+    /// the `starlight.clear`-style notifications that protocol 1.0 Hubs
+    /// expect. [`SentinelMux`](crate::SentinelMux) always uses
+    /// notifications, since several layers may answer one pre-check.
+    pub fn with_response_dialect(mut self, dialect: ResponseDialect) -> Self {
+        self.response_dialect = dialect;
+        self
+    }
+
+    /// Run as a shadow deployment that can't affect live automation.
+    ///
+    /// The Sentinel registers and deregisters as usual and handles every
+    /// message, but pre-check responses, hijacks, actions and everything
+    /// else it would send are logged as `would send: ...` at info level
+    /// instead. `*_and_wait` calls time out, since the Hub never sees them.
+    pub fn with_dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Check actions before sending them, so e.g. a `Fill` without text
+    /// fails with [`Error::InvalidConfig`] instead of being rejected by the
+    /// Hub.
+    pub fn with_action_validation(mut self) -> Self {
+        self.validate_actions = true;
+        self
+    }
+
+    /// Set how long `hijack_and_wait`, `action_and_wait`, and
+    /// `resume_and_wait` wait for the Hub before failing with `Error::Timeout`.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Record every frame the Sentinel handles or sends to `path`, one JSON
+    /// line each, for later [`replay`](crate::record::replay).
+    ///
+    /// The file is appended to, so one file can hold several sessions.
+    pub fn with_record_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record_to = Some(path.into());
+        self
+    }
+
+    /// Generate request ids with `generate` instead of random UUIDs.
+    ///
+    /// `generate` receives the request's method (e.g.
+    /// `starlight.registration`) and is used for every request the
+    /// Sentinel sends, so ids can follow a Hub's format or carry an
+    /// external trace id. Ids should be unique per connection.
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use starlight::SentinelConfig;
+    ///
+    /// let next = AtomicU64::new(1);
+    /// let config = SentinelConfig::new("MySentinel", 5).with_id_generator(move |method| {
+    ///     format!("trace-42/{}/{}", method, next.fetch_add(1, Ordering::Relaxed))
+    /// });
+    /// ```
+    pub fn with_id_generator(
+        mut self,
+        generate: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.id_generator = Some(IdGenerator(Arc::new(generate)));
+        self
+    }
+
+    /// Prefix generated request ids with `prefix`, e.g. to tag a fleet member.
+    ///
+    /// The registration id becomes `"{prefix}-reg-{uuid}"` and other ids
+    /// `"{prefix}-{uuid}"`. A generator set with
+    /// [`with_id_generator`](Self::with_id_generator) takes precedence and
+    /// the prefix is not applied to its ids.
+    pub fn with_id_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.id_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Generate the id for a request, with `id_generator` if configured.
+    pub(crate) fn next_id(&self, method: &str) -> Id {
+        if let Some(IdGenerator(ref generate)) = self.id_generator {
+            return Id::from(generate(method));
+        }
+        let id = match method {
+            methods::REGISTRATION => format!("reg-{}", Uuid::new_v4()),
+            _ => Uuid::new_v4().to_string(),
+        };
+        match self.id_prefix {
+            Some(ref prefix) => Id::from(format!("{}-{}", prefix, id)),
+            None => Id::from(id),
+        }
+    }
+
+    /// Build action params, validated if `validate_actions` is set.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn action_params(
+        &self,
+        cmd: ActionCommand,
+        selector: impl Into<String>,
+        text: Option<String>,
+    ) -> Result<ActionParams> {
+        let params = ActionParams {
+            cmd,
+            selector: selector.into(),
+            text,
+        };
+        if self.validate_actions {
+            params.validate()?;
+        }
+        Ok(params)
+    }
+
+    /// Intercept and send `message`, resending as configured by `send_retries`.
+    pub(crate) async fn send_with_retry<T: serde::Serialize>(
+        &self,
+        transport: &dyn Transport,
+        interceptors: &[Arc<dyn MessageInterceptor>],
+        message: &T,
+    ) -> Result<()> {
+        let frame = intercept(interceptors, message).await?;
+        let mut backoff = ConstantBackoff::new(self.send_retry_delay);
+        transport::send_with_retry(
+            transport,
+            frame,
+            self.send_retries,
+            &mut backoff,
+            false,
+            self.delay.as_deref(),
+        )
+        .await
+    }
+
+    /// Wait out the Hub's `retry_after_ms` with `delay` instead of tokio's
+    /// timer.
+    ///
+    /// Lets tests check that the wait is honored without sleeping.
+    pub fn with_delay(mut self, delay: impl Delay + 'static) -> Self {
+        self.delay = Some(Arc::new(delay));
+        self
+    }
+
+    /// Stop [`Sentinel::run`] once `limit` messages in a row have failed.
+    ///
+    /// Every error passed to `on_error` while handling a message counts,
+    /// including pre-check timeouts. A message handled without one resets
+    /// the count. When it reaches `limit`, the Sentinel stops and `run`
+    /// returns the last error, so a supervisor can restart it instead of
+    /// it failing quietly forever.
+    pub fn with_max_consecutive_errors(mut self, limit: u32) -> Self {
+        self.max_consecutive_errors = Some(limit);
+        self
+    }
+
+    /// Resend actions (including those sent through a [`HijackSession`])
+    /// and hijacks up to `retries` times, `delay` apart, when writing them
+    /// fails with a retryable error.
+    ///
+    /// Keeps a single transient write error from breaking a hijack
+    /// sequence. Reconnecting is left to the run loop, so a resend after
+    /// a lost connection succeeds once it has reconnected within the
+    /// retries.
+    pub fn with_send_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.send_retries = retries;
+        self.send_retry_delay = delay;
+        self
+    }
+
+    /// Choose the signals that stop [`Sentinel::run_until_signal`].
+    ///
+    /// Defaults to Ctrl-C, plus SIGTERM on unix.
+    pub fn with_shutdown_signals(
+        mut self,
+        signals: impl IntoIterator<Item = ShutdownSignal>,
+    ) -> Self {
+        self.shutdown_signals = signals.into_iter().collect();
+        self
+    }
+
+    /// Call `on_idle` whenever the Hub has sent nothing for `timeout`.
+    ///
+    /// Useful as a watchdog for a Hub that stopped talking while the socket
+    /// stays open. While the Hub stays silent, `on_idle` repeats every
+    /// `timeout`.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Reuse decisions for identical pre-checks instead of asking the handler again.
+    ///
+    /// Pre-checks with the same URL, command, and selector within `ttl` get
+    /// the cached decision. Up to `size` decisions are kept, evicting the
+    /// least recently used. Context updates clear the cache.
+    pub fn with_decision_cache(mut self, size: usize, ttl: Duration) -> Self {
+        self.decision_cache_size = size;
+        self.decision_cache_ttl = ttl;
+        self
+    }
+
+    /// Keep decisions that were computed but never reached the Hub
+    /// because the connection dropped, and answer with them when the Hub
+    /// asks again after reconnecting.
+    ///
+    /// Pre-check responses are notifications that aren't correlated with
+    /// the pre-check, so a lost decision is never sent on its own: the Hub
+    /// might apply it to a different command. Instead, the first pre-check
+    /// with the same URL, command, and selector within `ttl` gets the kept
+    /// decision without calling `on_pre_check` again. Decisions the Hub
+    /// doesn't ask for again are dropped after `ttl`, and context updates
+    /// drop them all.
+    pub fn with_unsent_decision_replay(mut self, ttl: Duration) -> Self {
+        self.unsent_decision_ttl = Some(ttl);
+        self
+    }
+
+    /// Bound how long `on_pre_check` may take.
+    ///
+    /// If the handler doesn't decide in time, `fallback` is sent instead and
+    /// `on_error` is called with `Error::Timeout`.
+    pub fn with_pre_check_timeout(mut self, timeout: Duration, fallback: PreCheckResponse) -> Self {
+        self.pre_check_timeout = Some(timeout);
+        self.pre_check_fallback = fallback;
+        self
+    }
 }
 
 /// Trait for handling Sentinel events.
@@ -94,19 +686,21 @@ impl SentinelConfig {
 ///
 /// # Example
 /// ```rust,no_run
-/// use starlight::{SentinelHandler, PreCheckParams, PreCheckResponse};
+/// use starlight::{CancelToken, SentinelHandler, PreCheckParams, PreCheckResponse};
 /// use async_trait::async_trait;
 ///
 /// struct MyHandler;
 ///
 /// #[async_trait]
 /// impl SentinelHandler for MyHandler {
-///     async fn on_pre_check(&self, params: PreCheckParams) -> PreCheckResponse {
+///     async fn on_pre_check(&self, params: PreCheckParams, _cancel: CancelToken) -> PreCheckResponse {
 ///         if params.blocking.is_empty() {
 ///             PreCheckResponse::Clear
 ///         } else {
 ///             PreCheckResponse::Hijack {
-///                 reason: "Detected obstacles".to_string()
+///                 reason: "Detected obstacles".to_string(),
+///                 confidence: Some(0.9),
+///                 targets: params.blocking.iter().map(|b| b.selector.clone()).collect(),
 ///             }
 ///         }
 ///     }
@@ -116,23 +710,58 @@ impl SentinelConfig {
 pub trait SentinelHandler: Send + Sync {
     /// Called when Hub requests a pre-check before executing a command.
     ///
-    /// This is the main decision point for your Sentinel.
-    async fn on_pre_check(&self, params: PreCheckParams) -> PreCheckResponse {
+    /// This is the main decision point for your Sentinel. `cancel` fires
+    /// if the connection is lost before a decision is made, since the
+    /// response could no longer be delivered.
+    async fn on_pre_check(&self, params: PreCheckParams, cancel: CancelToken) -> PreCheckResponse {
         // Default: always clear
-        let _ = params;
+        let _ = (params, cancel);
         PreCheckResponse::Clear
     }
 
+    /// Called for each pre-check in place of `on_pre_check`, to decide it
+    /// later rather than by returning.
+    ///
+    /// Override this when the decision needs slow work such as querying an
+    /// external service: move `responder` into another task and call
+    /// [`respond`](PreCheckResponder::respond) there. `id` is the id of the
+    /// Hub's pre-check. Dropping `responder` without responding sends
+    /// `pre_check_fallback`, and `pre_check_timeout` still applies.
+    ///
+    /// The default calls `on_pre_check` and responds with its decision.
+    async fn on_pre_check_deferred(
+        &self,
+        id: String,
+        params: PreCheckParams,
+        responder: PreCheckResponder,
+    ) {
+        let _ = id;
+        let cancel = responder.cancel().clone();
+        responder.respond(self.on_pre_check(params, cancel).await);
+    }
+
     /// Called when Hub sends entropy (page state) updates.
     async fn on_entropy(&self, params: EntropyParams) {
         debug!("Entropy update: {:?}", params);
     }
 
+    /// Called for entropy updates where the page has settled
+    /// (no pending mutations or network requests).
+    async fn on_entropy_stable(&self, params: EntropyParams) {
+        debug!("Page stable: {}", params.url);
+    }
+
     /// Called when Hub sends context updates.
     async fn on_context_update(&self, context: HashMap<String, serde_json::Value>) {
         debug!("Context update: {:?}", context);
     }
 
+    /// Called when no message has arrived from the Hub for `since`
+    /// (see `SentinelConfig::with_idle_timeout`).
+    async fn on_idle(&self, since: Duration) {
+        warn!("No message from Hub for {:?}", since);
+    }
+
     /// Called when the Sentinel connects to the Hub.
     async fn on_connect(&self) {
         info!("Connected to Hub");
@@ -142,6 +771,18 @@ pub trait SentinelHandler: Send + Sync {
     async fn on_disconnect(&self) {
         warn!("Disconnected from Hub");
     }
+
+    /// Called for inbound methods this SDK doesn't handle.
+    ///
+    /// Lets Sentinels react to protocol additions without an SDK upgrade.
+    async fn on_unknown_method(&self, method: &str, params: serde_json::Value) {
+        debug!("Unhandled method: {} {:?}", method, params);
+    }
+
+    /// Called when handling a message fails or a pre-check times out.
+    async fn on_error(&self, error: &Error) {
+        error!("Error handling message: {}", error);
+    }
 }
 
 /// Default handler that always clears pre-checks.
@@ -170,113 +811,617 @@ impl SentinelHandler for DefaultHandler {}
 /// }
 /// ```
 pub struct Sentinel<H: SentinelHandler> {
-    config: SentinelConfig,
+    config: Arc<SentinelConfig>,
     handler: Arc<H>,
-    client: Option<WebSocketClient>,
+    transport: Option<Arc<dyn Transport>>,
     running: Arc<RwLock<bool>>,
+    paused: Arc<RwLock<bool>>,
+    decision_cache: Arc<StdMutex<DecisionCache>>,
+    unsent_decisions: Arc<StdMutex<DecisionCache>>,
+    last_entropy: Arc<StdMutex<Option<EntropyParams>>>,
+    pending: PendingRequests,
+    #[cfg(feature = "jwt")]
     jwt_handler: Option<JwtHandler>,
+    metrics: Arc<Metrics>,
+    selectors: Arc<RwLock<Vec<String>>>,
+    interceptors: Interceptors,
+    events: broadcast::Sender<SentinelEvent>,
+    last_message: Arc<StdMutex<Option<Instant>>>,
+    pre_check_subscriber: PreCheckSubscriber,
+    hub_info: Arc<StdMutex<Option<HubInfo>>>,
+    registration_id: Arc<StdMutex<Option<Id>>>,
+    retry_after: StdMutex<Option<Duration>>,
+    action_sequence: Mutex<()>,
+    connected_at: StdMutex<Option<Instant>>,
+    session_id: StdMutex<Option<String>>,
+    resuming: AtomicBool,
 }
 
 impl<H: SentinelHandler + 'static> Sentinel<H> {
     /// Create a new Sentinel.
     pub fn new(config: SentinelConfig, handler: H) -> Self {
+        #[cfg(feature = "jwt")]
         let jwt_handler = config.jwt_secret.as_ref().map(JwtHandler::new);
+        let selectors = Arc::new(RwLock::new(config.selectors.clone()));
+        let decision_cache =
+            DecisionCache::new(config.decision_cache_size, config.decision_cache_ttl);
+        let unsent_decisions = match config.unsent_decision_ttl {
+            Some(ttl) => DecisionCache::new(UNSENT_DECISIONS, ttl),
+            None => DecisionCache::new(0, Duration::ZERO),
+        };
+        let (events, _) = broadcast::channel(64);
+        let interceptors: Interceptors = match config.record_to {
+            Some(ref path) => Arc::new([Arc::new(Recorder::new(path.clone())) as _]),
+            None => Arc::new([]),
+        };
 
         Self {
-            config,
+            selectors,
+            config: Arc::new(config),
             handler: Arc::new(handler),
-            client: None,
+            transport: None,
             running: Arc::new(RwLock::new(false)),
+            paused: Arc::new(RwLock::new(false)),
+            decision_cache: Arc::new(StdMutex::new(decision_cache)),
+            unsent_decisions: Arc::new(StdMutex::new(unsent_decisions)),
+            last_entropy: Arc::default(),
+            pending: PendingRequests::default(),
+            #[cfg(feature = "jwt")]
             jwt_handler,
+            metrics: Arc::new(Metrics::default()),
+            interceptors,
+            events,
+            last_message: Arc::new(StdMutex::new(None)),
+            pre_check_subscriber: Arc::default(),
+            hub_info: Arc::default(),
+            registration_id: Arc::default(),
+            retry_after: StdMutex::new(None),
+            action_sequence: Mutex::new(()),
+            connected_at: StdMutex::new(None),
+            session_id: StdMutex::new(None),
+            resuming: AtomicBool::new(false),
         }
     }
 
-    /// Connect to the Starlight Hub.
+    /// Add a [`MessageInterceptor`] that sees every inbound and outbound message.
+    ///
+    /// Interceptors run in the order they were added.
+    pub fn with_interceptor(mut self, interceptor: impl MessageInterceptor + 'static) -> Self {
+        let mut interceptors = self.interceptors.to_vec();
+        // The recorder stays last so it records frames as sent.
+        let at = interceptors.len() - usize::from(self.config.record_to.is_some());
+        interceptors.insert(at, Arc::new(interceptor));
+        self.interceptors = interceptors.into();
+        self
+    }
+
+    /// Create a Sentinel over an already-established transport.
+    ///
+    /// Call [`handshake`](Self::handshake) to register with the Hub before
+    /// [`run`](Self::run).
+    ///
+    /// # Example
+    /// ```
+    /// use starlight::mock::MockTransport;
+    /// use starlight::{DefaultHandler, Sentinel, SentinelConfig};
+    ///
+    /// let (transport, _hub) = MockTransport::pair();
+    /// let sentinel = Sentinel::new_with_transport(
+    ///     SentinelConfig::new("TestSentinel", 5),
+    ///     DefaultHandler,
+    ///     transport,
+    /// );
+    /// ```
+    pub fn new_with_transport(
+        config: SentinelConfig,
+        handler: H,
+        transport: impl Transport + 'static,
+    ) -> Self {
+        let mut sentinel = Self::new(config, handler);
+        sentinel.set_transport(Arc::new(transport));
+        sentinel
+    }
+
+    /// Connect to the Starlight Hub with the default client settings.
     pub async fn connect(&mut self, url: &str) -> Result<()> {
-        info!("Connecting {} to {}", self.config.name, url);
+        self.connect_with(ClientConfig::new(url)).await
+    }
+
+    /// Connect to the Starlight Hub, retrying if the first attempts fail.
+    ///
+    /// Auto-reconnect only applies once connected; this covers startup,
+    /// when the Hub may not be up yet. Makes up to `attempts` tries
+    /// (at least one), waiting `backoff.next_delay(n)` after the `n`th
+    /// failure. Errors that aren't [retryable](Error::is_retryable) are
+    /// returned immediately, otherwise the last error once every attempt
+    /// has failed.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use starlight::{DefaultHandler, Sentinel, SentinelConfig};
+    /// use std::time::Duration;
+    /// use starlight::backoff::ExponentialBackoff;
+    /// # async fn example() -> starlight::Result<()> {
+    /// let mut sentinel = Sentinel::new(SentinelConfig::new("MySentinel", 5), DefaultHandler);
+    /// let backoff = ExponentialBackoff::new(Duration::from_millis(500), Duration::from_secs(5));
+    /// sentinel.connect_with_retry("ws://localhost:8080", 10, backoff).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect_with_retry(
+        &mut self,
+        url: &str,
+        attempts: u32,
+        mut backoff: impl BackoffStrategy,
+    ) -> Result<()> {
+        backoff.reset();
+        let mut attempt = 1;
+        loop {
+            match self.connect(url).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= attempts || !e.is_retryable() => return Err(e),
+                Err(e) => {
+                    let delay = backoff.next_delay(attempt);
+                    warn!(
+                        "Connection attempt {}/{} failed, retrying in {:?}: {}",
+                        attempt, attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Connect to the Starlight Hub with a fully configured client
+    /// (proxy, TLS, backoff, timeouts, ...).
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use starlight::{DefaultHandler, Sentinel, SentinelConfig};
+    /// use starlight::client::ClientConfig;
+    /// # async fn example() -> starlight::Result<()> {
+    /// let mut sentinel = Sentinel::new(SentinelConfig::new("MySentinel", 5), DefaultHandler);
+    /// let client_config = ClientConfig::new("ws://hub.internal:8080")
+    ///     .with_max_reconnect_elapsed(std::time::Duration::from_secs(60));
+    /// sentinel.connect_with(client_config).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect_with(&mut self, client_config: ClientConfig) -> Result<()> {
+        let client_config = client_config.merge_url();
+        info!(
+            "Connecting {} to {}",
+            self.config.name,
+            client_config.urls.join(", ")
+        );
 
-        let client_config = ClientConfig::new(url);
         let client = WebSocketClient::new(client_config);
 
         client.connect().await?;
-        self.client = Some(client);
+        self.set_transport(Arc::new(client));
+        self.emit(SentinelEvent::Connected);
+
+        self.handshake().await
+    }
 
+    /// Register with the Hub over the current transport and notify the handler.
+    pub async fn handshake(&self) -> Result<()> {
         // Send registration
         self.register().await?;
 
         // Notify handler
         self.handler.on_connect().await;
+        self.emit(SentinelEvent::HandshakeComplete);
 
         Ok(())
     }
 
-    /// Send registration message to Hub.
-    async fn register(&self) -> Result<()> {
-        let client = self.client.as_ref().ok_or(Error::NotConnected)?;
-
-        let mut params = RegistrationParams::new(&self.config.name, self.config.priority)
-            .with_capabilities(self.config.capabilities.clone())
-            .with_selectors(self.config.selectors.clone());
+    /// Subscribe to connection lifecycle events.
+    ///
+    /// Only events published after subscribing are received, so subscribe
+    /// before calling [`run`](Self::run) to see everything.
+    pub fn events(&self) -> broadcast::Receiver<SentinelEvent> {
+        self.events.subscribe()
+    }
 
-        // Add JWT token if configured
-        if let Some(ref jwt) = self.jwt_handler {
-            let token = jwt.generate_token(&self.config.name)?;
-            params = params.with_auth_token(token);
+    /// Publish an event to all current subscribers.
+    ///
+    /// Also starts and ends the session timed by [`connected_for`](Self::connected_for).
+    fn emit(&self, event: SentinelEvent) {
+        match event {
+            SentinelEvent::HandshakeComplete => self.set_connected_at(Some(Instant::now())),
+            SentinelEvent::Disconnected => self.set_connected_at(None),
+            _ => {}
         }
+        // Having no subscribers is fine.
+        let _ = self.events.send(event);
+    }
 
-        let request = JsonRpcRequest::new(
-            methods::REGISTRATION,
-            params,
-            format!("reg-{}", Uuid::new_v4()),
-        );
-
-        client.send_json(&request).await?;
-        info!("{} registered with Hub", self.config.name);
-
-        Ok(())
+    fn set_connected_at(&self, at: Option<Instant>) {
+        *self.connected_at.lock().unwrap_or_else(|e| e.into_inner()) = at;
     }
 
-    /// Run the Sentinel message loop.
+    /// How long the current connection has been up, counted from the
+    /// last completed handshake.
     ///
-    /// This method blocks until the Sentinel is stopped or disconnected.
-    pub async fn run(&self) -> Result<()> {
-        let client = self.client.as_ref().ok_or(Error::NotConnected)?;
+    /// Starts over after every reconnect; `None` before the first
+    /// handshake, while disconnected, and after [`stop`](Self::stop).
+    pub fn connected_for(&self) -> Option<Duration> {
+        self.connected_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .map(|at| at.elapsed())
+    }
 
-        *self.running.write().await = true;
-        info!("{} running", self.config.name);
+    /// Use `transport`, behind [`DryRunTransport`] if `dry_run` is set.
+    fn set_transport(&mut self, transport: Arc<dyn Transport>) {
+        self.transport = Some(if self.config.dry_run {
+            Arc::new(DryRunTransport::new(transport))
+        } else {
+            transport
+        });
+    }
 
-        loop {
-            if !*self.running.read().await {
-                break;
-            }
+    /// Get the transport, or fail if not connected.
+    #[allow(clippy::result_large_err)]
+    fn transport(&self) -> Result<&Arc<dyn Transport>> {
+        self.transport.as_ref().ok_or(Error::NotConnected)
+    }
 
-            match client.receive().await {
-                Ok(Some(msg)) => {
-                    if let Err(e) = self.handle_message(msg).await {
-                        error!("Error handling message: {}", e);
+    /// Serialize a message to JSON and send it to the Hub.
+    async fn send_json<T: serde::Serialize>(&self, message: &T) -> Result<()> {
+        send_intercepted(self.transport()?, &self.interceptors, message).await
+    }
+
+    /// Like `send_json`, resending as configured by `send_retries`.
+    async fn send_json_with_retry<T: serde::Serialize>(&self, message: &T) -> Result<()> {
+        self.config
+            .send_with_retry(self.transport()?.as_ref(), &self.interceptors, message)
+            .await
+    }
+
+    /// Send registration message to Hub.
+    ///
+    /// Used for the initial handshake and again after every reconnect, so
+    /// the Hub always sees the full registration: layer, priority,
+    /// capabilities, a fresh auth token, and the current selectors
+    /// (including any changed at runtime). Once the Hub has handed out a
+    /// session id, it is sent back as `resume_session`.
+    async fn register(&self) -> Result<()> {
+        let selectors = self.selectors.read().await.clone();
+        let params = RegistrationParams::new(&self.config.name, self.config.priority)
+            .with_capabilities(self.config.capabilities.clone())
+            .with_selectors(selectors)
+            .with_selector_priorities(self.config.selector_priorities.clone());
+        let params = match self.session_id() {
+            Some(session_id) => {
+                debug!("Resuming session {}", session_id);
+                self.resuming.store(true, Ordering::SeqCst);
+                params.with_resume_session(session_id)
+            }
+            None => {
+                self.resuming.store(false, Ordering::SeqCst);
+                params
+            }
+        };
+
+        // Add JWT token if configured
+        #[cfg(feature = "jwt")]
+        let params = match self.jwt_handler {
+            Some(ref jwt) => params.with_auth_token(jwt.generate_token(&self.config.name)?),
+            None => params,
+        };
+
+        let id = self.next_id(methods::REGISTRATION);
+        *self
+            .registration_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(id.clone());
+        let request = JsonRpcRequest::new(methods::REGISTRATION, params, id);
+
+        self.send_json(&request).await?;
+        info!("{} registered with Hub", self.config.name);
+
+        Ok(())
+    }
+
+    /// Generate the id for a request (see [`SentinelConfig::with_id_prefix`]).
+    fn next_id(&self, method: &str) -> Id {
+        self.config.next_id(method)
+    }
+
+    /// Validate the Hub's response to a registration request, if it sent one.
+    ///
+    /// Hubs that acknowledge registration may report their protocol
+    /// version; an incompatible major version fails the handshake. What
+    /// the Hub reports about itself is kept for [`hub_info`](Self::hub_info).
+    #[allow(clippy::result_large_err)]
+    fn check_registration_result(&self, msg: &RawMessage) -> Result<()> {
+        if !self.is_registration_response(msg) {
+            return Ok(());
+        }
+        self.resuming.store(false, Ordering::SeqCst);
+
+        if let Some(ref error) = msg.error {
+            // Other rejections are left to the Hub to follow up on.
+            return match error.retry_after() {
+                Some(retry_after) => Err(Error::RetryAfter {
+                    code: error.code,
+                    message: error.message.clone(),
+                    retry_after,
+                }),
+                None => Ok(()),
+            };
+        }
+
+        let Some(result) = &msg.result else {
+            return Ok(());
+        };
+        // A bare `true` or similar reports nothing about the Hub.
+        let result: RegistrationResult = match result {
+            serde_json::Value::Object(_) => serde_json::from_value(result.clone())?,
+            _ => RegistrationResult::default(),
+        };
+        if let Some(ref version) = result.protocol_version {
+            check_protocol_version(version)?;
+        }
+        if let Some(ref session_id) = result.session_id {
+            *self.session_id.lock().unwrap_or_else(|e| e.into_inner()) = Some(session_id.clone());
+        }
+
+        let info = HubInfo::from(result);
+        info!(
+            "Hub version: {}, instance: {}",
+            info.version.as_deref().unwrap_or("unknown"),
+            info.instance_id.as_deref().unwrap_or("unknown")
+        );
+        *self.hub_info.lock().unwrap_or_else(|e| e.into_inner()) = Some(info);
+        Ok(())
+    }
+
+    /// Whether `msg` answers the latest registration request.
+    fn is_registration_response(&self, msg: &RawMessage) -> bool {
+        msg.is_response()
+            && msg.id.is_some()
+            && msg.id
+                == *self
+                    .registration_id
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether `msg` rejects an attempt to resume the session.
+    ///
+    /// The stored session is dropped, so the next registration starts
+    /// fresh. A rejection with `retry_after_ms` is left to the usual retry.
+    fn resume_declined(&self, msg: &RawMessage) -> bool {
+        let declined = self.is_registration_response(msg)
+            && msg
+                .error
+                .as_ref()
+                .is_some_and(|e| e.retry_after().is_none())
+            && self.resuming.swap(false, Ordering::SeqCst);
+        if declined {
+            self.session_id
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take();
+        }
+        declined
+    }
+
+    /// Remember a `retry_after_ms` in an error response for the next
+    /// reconnect or re-registration.
+    fn note_retry_after(&self, msg: &RawMessage) {
+        if let Some(retry_after) = msg.error.as_ref().and_then(JsonRpcError::retry_after) {
+            debug!("Hub asked to retry after {:?}", retry_after);
+            *self.retry_after.lock().unwrap_or_else(|e| e.into_inner()) = Some(retry_after);
+        }
+    }
+
+    /// Wait as long as the Hub last asked, if it did.
+    async fn wait_retry_after(&self) {
+        let retry_after = self
+            .retry_after
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let Some(retry_after) = retry_after else {
+            return;
+        };
+
+        info!(
+            "Waiting {:?} before retrying, as asked by the Hub",
+            retry_after
+        );
+        match self.config.delay {
+            Some(ref delay) => delay.sleep(retry_after).await,
+            None => tokio::time::sleep(retry_after).await,
+        }
+    }
+
+    /// Run the Sentinel message loop.
+    ///
+    /// This method blocks until the Sentinel is stopped or disconnected.
+    /// If the Hub closes the connection with a non-retryable close code
+    /// (see [`Transport::should_reconnect`]), the close error is returned
+    /// instead of reconnecting. A registration result reporting an
+    /// incompatible protocol version returns [`Error::Handshake`].
+    ///
+    /// When an error response carries `data.retry_after_ms`, the next
+    /// reconnect or re-registration waits at least that long. A rejected
+    /// registration with a retry-after is retried after the wait if
+    /// `auto_reconnect` is on, and returned as [`Error::RetryAfter`]
+    /// otherwise.
+    pub async fn run(&self) -> Result<()> {
+        let transport = self.transport()?;
+        let dispatcher = self.dispatcher()?;
+        let workers = Arc::new(Semaphore::new(self.config.max_concurrent_handlers));
+
+        // Cancelled when the connection is lost, replaced after reconnecting.
+        let mut cancel = CancelToken::new();
+        // A message read while a handler was running, handled next.
+        let mut lookahead = None;
+        // When the last message arrived, and when to report the Hub as idle.
+        let mut last_message = Instant::now();
+        let mut idle_at = self.config.idle_timeout.map(|idle| last_message + idle);
+
+        *self.running.write().await = true;
+        info!("{} running", self.config.name);
+
+        loop {
+            if !*self.running.read().await {
+                break;
+            }
+            if let Some(e) = dispatcher.errors.take_tripped() {
+                error!("Too many consecutive errors, stopping: {}", e);
+                self.stop().await;
+                return Err(e);
+            }
+
+            let received = match lookahead.take() {
+                Some(received) => received,
+                None => tokio::select! {
+                    received = transport.recv() => received,
+                    _ = sleep_until(idle_at), if idle_at.is_some() => {
+                        let since = last_message.elapsed();
+                        debug!("No message from Hub for {:?}", since);
+                        self.handler.on_idle(since).await;
+                        idle_at = self.config.idle_timeout.map(|idle| Instant::now() + idle);
+                        continue;
+                    }
+                },
+            };
+            if received.is_err() {
+                cancel.cancel();
+            }
+
+            match received {
+                Ok(Some(msg)) => {
+                    last_message = Instant::now();
+                    idle_at = self.config.idle_timeout.map(|idle| last_message + idle);
+                    *self.last_message.lock().unwrap_or_else(|e| e.into_inner()) =
+                        Some(last_message);
+
+                    self.note_retry_after(&msg);
+                    let Some(msg) = self.pending.resolve(msg) else {
+                        continue;
+                    };
+                    self.emit(SentinelEvent::MessageReceived {
+                        method: msg.method.clone(),
+                    });
+
+                    if self.resume_declined(&msg) {
+                        warn!("Hub declined to resume the session, registering fresh");
+                        if let Err(e) = self.register().await {
+                            error!("Re-registration failed: {}", e);
+                            break;
+                        }
+                        continue;
+                    }
+                    match self.check_registration_result(&msg) {
+                        Ok(()) => {}
+                        Err(Error::RetryAfter { .. }) if self.config.auto_reconnect => {
+                            warn!("Registration rejected, registering again");
+                            self.wait_retry_after().await;
+                            if let Err(e) = self.handshake().await {
+                                error!("Re-registration failed: {}", e);
+                                break;
+                            }
+                            continue;
+                        }
+                        Err(e) => {
+                            error!("{}", e);
+                            self.handler.on_disconnect().await;
+                            self.emit(SentinelEvent::Disconnected);
+                            *self.running.write().await = false;
+                            let _ = transport.close().await;
+                            return Err(e);
+                        }
+                    }
+
+                    let span = info_span!(
+                        "message",
+                        id = %msg.id.as_ref().map_or_else(|| "-".to_string(), Id::to_string),
+                        method = %msg.method,
+                        response = tracing::field::Empty,
+                    );
+                    if self.config.max_concurrent_handlers > 1 {
+                        // Wait for a free worker before reading further.
+                        let Ok(permit) = Arc::clone(&workers).acquire_owned().await else {
+                            break;
+                        };
+                        let dispatcher = dispatcher.clone();
+                        let cancel = cancel.clone();
+                        tokio::spawn(
+                            async move {
+                                dispatcher.dispatch(msg, cancel).await;
+                                drop(permit);
+                            }
+                            .instrument(span),
+                        );
+                    } else {
+                        // Keep reading while the handler runs so a lost
+                        // connection cancels it. Stop at the first message,
+                        // which is handled next.
+                        let dispatch = dispatcher.dispatch(msg, cancel.clone()).instrument(span);
+                        tokio::pin!(dispatch);
+                        loop {
+                            tokio::select! {
+                                _ = &mut dispatch => break,
+                                next = transport.recv(), if lookahead.is_none() => match next {
+                                    Ok(None) => {}
+                                    // Responses are delivered right away, since the
+                                    // handler may be the one waiting for them.
+                                    Ok(Some(msg)) => {
+                                        self.note_retry_after(&msg);
+                                        if let Some(msg) = self.pending.resolve(msg) {
+                                            lookahead = Some(Ok(Some(msg)));
+                                        }
+                                    }
+                                    Err(e) => {
+                                        cancel.cancel();
+                                        lookahead = Some(Err(e));
+                                    }
+                                },
+                            }
+                        }
                     }
                 }
                 Ok(None) => continue, // Ping/pong or other non-text message
-                Err(Error::ConnectionClosed(_)) if self.config.auto_reconnect => {
+                Err(e @ Error::ConnectionClosed { .. }) if !transport.should_reconnect(&e) => {
+                    error!("Hub closed the connection, not reconnecting: {}", e);
+                    self.handler.on_disconnect().await;
+                    self.emit(SentinelEvent::Disconnected);
+                    *self.running.write().await = false;
+                    return Err(e);
+                }
+                Err(Error::ConnectionClosed { .. }) if self.config.auto_reconnect => {
                     self.handler.on_disconnect().await;
+                    self.emit(SentinelEvent::Disconnected);
                     warn!("Connection lost, attempting reconnect...");
 
-                    if let Err(e) = client.reconnect().await {
+                    self.emit(SentinelEvent::Reconnecting);
+                    self.wait_retry_after().await;
+                    if let Err(e) = transport.reconnect().await {
                         error!("Reconnection failed: {}", e);
                         break;
                     }
+                    self.metrics.record_reconnect();
+                    self.emit(SentinelEvent::Connected);
+                    cancel = CancelToken::new();
 
                     // Re-register after reconnect
-                    if let Err(e) = self.register().await {
+                    if let Err(e) = self.handshake().await {
                         error!("Re-registration failed: {}", e);
                         break;
                     }
-
-                    self.handler.on_connect().await;
                 }
                 Err(e) => {
                     error!("Error: {}", e);
                     self.handler.on_disconnect().await;
+                    self.emit(SentinelEvent::Disconnected);
                     break;
                 }
             }
@@ -286,59 +1431,100 @@ impl<H: SentinelHandler + 'static> Sentinel<H> {
         Ok(())
     }
 
-    /// Handle an incoming message from the Hub.
-    async fn handle_message(&self, msg: RawMessage) -> Result<()> {
-        debug!("Handling: {}", msg.method);
-
-        match msg.method.as_str() {
-            methods::PRE_CHECK => {
-                let params: PreCheckParams = serde_json::from_value(msg.params)?;
-                let response = self.handler.on_pre_check(params).await;
-
-                if let Some(id) = msg.id {
-                    self.send_pre_check_response(&id, response).await?;
-                }
-            }
-            methods::ENTROPY => {
-                let params: EntropyParams = serde_json::from_value(msg.params)?;
-                self.handler.on_entropy(params).await;
-            }
-            methods::CONTEXT_UPDATE => {
-                let params: ContextUpdateParams = serde_json::from_value(msg.params)?;
-                self.handler.on_context_update(params.context).await;
-            }
-            _ => {
-                debug!("Unhandled method: {}", msg.method);
+    /// Run the message loop until one of `shutdown_signals` arrives.
+    ///
+    /// On a signal the Sentinel is [stopped](Self::stop) and `Ok(())` is
+    /// returned. Otherwise this behaves like [`run`](Self::run).
+    pub async fn run_until_signal(&self) -> Result<()> {
+        tokio::select! {
+            result = self.run() => result,
+            received = signal::wait_for(&self.config.shutdown_signals) => {
+                info!("Received {:?}, shutting down", received?);
+                self.stop().await;
+                Ok(())
             }
         }
-
-        Ok(())
     }
 
-    /// Send pre-check response to Hub.
-    async fn send_pre_check_response(&self, _id: &str, response: PreCheckResponse) -> Result<()> {
-        let client = self.client.as_ref().ok_or(Error::NotConnected)?;
+    /// Build a dispatcher over the current transport.
+    #[allow(clippy::result_large_err)]
+    fn dispatcher(&self) -> Result<Dispatcher<H>> {
+        Ok(Dispatcher {
+            config: Arc::clone(&self.config),
+            handler: Arc::clone(&self.handler),
+            transport: Arc::clone(self.transport()?),
+            metrics: Arc::clone(&self.metrics),
+            interceptors: Arc::clone(&self.interceptors),
+            paused: Arc::clone(&self.paused),
+            decision_cache: Arc::clone(&self.decision_cache),
+            unsent_decisions: Arc::clone(&self.unsent_decisions),
+            last_entropy: Arc::clone(&self.last_entropy),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            pre_check_subscriber: Arc::clone(&self.pre_check_subscriber),
+            errors: Arc::new(ErrorStreak::default()),
+        })
+    }
 
-        let method = match &response {
-            PreCheckResponse::Clear => methods::CLEAR,
-            PreCheckResponse::Wait { .. } => methods::WAIT,
-            PreCheckResponse::Hijack { .. } => methods::HIJACK,
-        };
+    /// Receive pre-checks as a stream instead of through `on_pre_check`.
+    ///
+    /// While the stream is alive every pre-check is sent to it, and the
+    /// Hub gets whatever is passed to [`PreCheckRequest::respond`].
+    /// Timeouts, caching and metrics apply as they would to the handler.
+    /// Once the stream is dropped, pre-checks go back to the handler.
+    /// Calling this again replaces the previous stream.
+    pub fn pre_checks(&self) -> impl Stream<Item = PreCheckRequest> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self
+            .pre_check_subscriber
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(tx);
+        futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|request| (request, rx))
+        })
+    }
 
-        let notification = JsonRpcNotification::new(method, response);
-        client.send_json(&notification).await
+    /// Handle each message in turn, as `run()` would, without reading
+    /// from the transport.
+    pub(crate) async fn dispatch_all(&self, messages: Vec<RawMessage>) -> Result<()> {
+        let dispatcher = self.dispatcher()?;
+        for msg in messages {
+            dispatcher.dispatch(msg, CancelToken::new()).await;
+        }
+        Ok(())
     }
 
     /// Send a hijack request (take control of browser).
     pub async fn hijack(&self, reason: impl Into<String>) -> Result<()> {
-        let client = self.client.as_ref().ok_or(Error::NotConnected)?;
-
         let params = HijackParams {
             reason: reason.into(),
         };
 
         let notification = JsonRpcNotification::new(methods::HIJACK, params);
-        client.send_json(&notification).await
+        self.send_json_with_retry(&notification).await
+    }
+
+    /// Ask the Hub to wait before retrying the current command.
+    ///
+    /// Sends the same `starlight.wait` message as returning
+    /// [`PreCheckResponse::Wait`] from `on_pre_check`.
+    pub async fn wait(&self, retry_after: Duration, reason: Option<String>) -> Result<()> {
+        let params = PreCheckResponse::Wait {
+            retry_after_ms: retry_after.as_millis() as u64,
+            reason,
+        };
+
+        let notification = JsonRpcNotification::new(methods::WAIT, params);
+        self.send_json(&notification).await
+    }
+
+    /// Start a hijack and return a guard that resumes when finished or dropped.
+    pub async fn begin_hijack(&self, reason: impl Into<String>) -> Result<HijackSession> {
+        self.hijack(reason).await?;
+        Ok(HijackSession::new(
+            Arc::clone(self.transport()?),
+            Arc::clone(&self.interceptors),
+            Arc::clone(&self.config),
+        ))
     }
 
     /// Send an action during hijack.
@@ -348,40 +1534,2991 @@ impl<H: SentinelHandler + 'static> Sentinel<H> {
         selector: impl Into<String>,
         text: Option<String>,
     ) -> Result<()> {
-        let client = self.client.as_ref().ok_or(Error::NotConnected)?;
-
-        let params = ActionParams {
-            cmd,
-            selector: selector.into(),
-            text,
-        };
-
+        let params = self.config.action_params(cmd, selector, text)?;
         let notification = JsonRpcNotification::new(methods::ACTION, params);
-        client.send_json(&notification).await
+        self.send_json_with_retry(&notification).await
+    }
+
+    /// Send several actions, which the Hub receives in the given order.
+    ///
+    /// Each action is `(cmd, selector, text)`. Other sequences sent
+    /// concurrently wait until this one is out, so multi-step dismissals
+    /// are never interleaved. Stops at the first action that fails to send.
+    pub async fn actions(
+        &self,
+        actions: Vec<(ActionCommand, String, Option<String>)>,
+    ) -> Result<()> {
+        let _sequence = self.action_sequence.lock().await;
+        for (cmd, selector, text) in actions {
+            self.action(cmd, selector, text).await?;
+        }
+        Ok(())
     }
 
     /// Resume after hijack.
     pub async fn resume(&self, request_recheck: bool) -> Result<()> {
-        let client = self.client.as_ref().ok_or(Error::NotConnected)?;
-
         let params = ResumeParams { request_recheck };
         let notification = JsonRpcNotification::new(methods::RESUME, params);
-        client.send_json(&notification).await
+        self.send_json(&notification).await
+    }
+
+    /// Currently monitored selectors, including runtime changes.
+    pub async fn selectors(&self) -> Vec<String> {
+        self.selectors.read().await.clone()
+    }
+
+    /// Start monitoring additional selectors without reconnecting.
+    ///
+    /// Selectors already monitored are ignored. The new set is kept for
+    /// re-registration after a reconnect, even if the update fails to send.
+    pub async fn add_selectors(&self, sels: Vec<String>) -> Result<()> {
+        let add: Vec<String> = {
+            let mut selectors = self.selectors.write().await;
+            let mut added = Vec::new();
+            for sel in sels {
+                if !selectors.contains(&sel) {
+                    selectors.push(sel.clone());
+                    added.push(sel);
+                }
+            }
+            added
+        };
+
+        if add.is_empty() {
+            return Ok(());
+        }
+
+        let params = UpdateSelectorsParams {
+            add,
+            remove: Vec::new(),
+        };
+        let notification = JsonRpcNotification::new(methods::UPDATE_SELECTORS, params);
+        self.send_json(&notification).await
+    }
+
+    /// Stop monitoring selectors without reconnecting.
+    ///
+    /// Selectors not currently monitored are ignored.
+    pub async fn remove_selectors(&self, sels: Vec<String>) -> Result<()> {
+        let remove: Vec<String> = {
+            let mut selectors = self.selectors.write().await;
+            let removed: Vec<String> = sels
+                .into_iter()
+                .filter(|sel| selectors.contains(sel))
+                .collect();
+            selectors.retain(|sel| !removed.contains(sel));
+            removed
+        };
+
+        if remove.is_empty() {
+            return Ok(());
+        }
+
+        let params = UpdateSelectorsParams {
+            add: Vec::new(),
+            remove,
+        };
+        let notification = JsonRpcNotification::new(methods::UPDATE_SELECTORS, params);
+        self.send_json(&notification).await
+    }
+
+    /// Send a hijack request and wait for the Hub to acknowledge it.
+    ///
+    /// Like all `*_and_wait` methods, this relies on [`run`](Self::run)
+    /// to read the response, and fails with `Error::Timeout` after
+    /// `request_timeout`.
+    pub async fn hijack_and_wait(&self, reason: impl Into<String>) -> Result<Ack> {
+        let params = HijackParams {
+            reason: reason.into(),
+        };
+        self.call(methods::HIJACK, params).await
+    }
+
+    /// Send an action and wait for the Hub to acknowledge it.
+    ///
+    /// Useful for sequencing, e.g. making sure a click registered before
+    /// sending the next action.
+    pub async fn action_and_wait(
+        &self,
+        cmd: ActionCommand,
+        selector: impl Into<String>,
+        text: Option<String>,
+    ) -> Result<Ack> {
+        let params = self.config.action_params(cmd, selector, text)?;
+        self.call(methods::ACTION, params).await
+    }
+
+    /// Send several actions in order, waiting for each to be acknowledged
+    /// before sending the next.
+    ///
+    /// Stops at the first action the Hub rejects, since later steps usually
+    /// depend on it; the returned acks end with that rejection.
+    pub async fn actions_and_wait(
+        &self,
+        actions: Vec<(ActionCommand, String, Option<String>)>,
+    ) -> Result<Vec<Ack>> {
+        let _sequence = self.action_sequence.lock().await;
+        let mut acks = Vec::with_capacity(actions.len());
+        for (cmd, selector, text) in actions {
+            let ack = self.action_and_wait(cmd, selector, text).await?;
+            let accepted = ack.accepted;
+            acks.push(ack);
+            if !accepted {
+                break;
+            }
+        }
+        Ok(acks)
+    }
+
+    /// Resume after hijack and wait for the Hub to acknowledge it.
+    pub async fn resume_and_wait(&self, request_recheck: bool) -> Result<Ack> {
+        let params = ResumeParams { request_recheck };
+        self.call(methods::RESUME, params).await
+    }
+
+    /// Send a request and wait for the Hub's acknowledgement.
+    async fn call<P: serde::Serialize>(&self, method: &str, params: P) -> Result<Ack> {
+        let id = self.next_id(method);
+        let response = self.pending.register(id.clone());
+
+        let request = JsonRpcRequest::new(method, params, id.clone());
+        if let Err(e) = self.send_json(&request).await {
+            self.pending.forget(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(self.config.request_timeout, response).await {
+            Ok(Ok(msg)) => Ack::from_response(msg),
+            Ok(Err(_)) => Err(Error::ChannelError),
+            Err(_) => {
+                self.pending.forget(&id);
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Declare an intent to the Hub ahead of acting on it.
+    ///
+    /// Uses the same `starlight.intent` method the Hub uses to relay
+    /// intents to Sentinels; here it flows Sentinel → Hub.
+    pub async fn send_intent(&self, intent: IntentParams) -> Result<()> {
+        let notification = JsonRpcNotification::new(methods::INTENT, intent);
+        self.send_json(&notification).await
+    }
+
+    /// Answer a Hub request with a JSON-RPC error.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use starlight::{DefaultHandler, Sentinel};
+    /// use starlight::messages::JsonRpcError;
+    /// # async fn example(sentinel: &Sentinel<DefaultHandler>) -> starlight::Result<()> {
+    /// sentinel
+    ///     .send_error("req-7", JsonRpcError::invalid_params("selector is required"))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_error(&self, id: impl Into<Id>, error: JsonRpcError) -> Result<()> {
+        let response = JsonRpcResponse::<()>::error(id, error);
+        self.send_json(&response).await
+    }
+
+    /// Send an arbitrary protocol method not covered by the SDK.
+    ///
+    /// Sends a notification when `as_notification` is set, otherwise a
+    /// request with a generated id, which is returned so the caller can
+    /// match the Hub's response.
+    pub async fn send_raw<P: serde::Serialize>(
+        &self,
+        method: &str,
+        params: P,
+        as_notification: bool,
+    ) -> Result<Option<Id>> {
+        if as_notification {
+            let notification = JsonRpcNotification::new(method, params);
+            self.send_json(&notification).await?;
+            return Ok(None);
+        }
+
+        let id = self.next_id(method);
+        let request = JsonRpcRequest::new(method, params, id.clone());
+        self.send_json(&request).await?;
+        Ok(Some(id))
     }
 
     /// Stop the Sentinel.
+    ///
+    /// Sends `starlight.deregister` before closing the connection so the
+    /// Hub frees the priority slot immediately. That is best-effort: if it
+    /// can't be sent, the Hub falls back to its timeout.
     pub async fn stop(&self) {
         *self.running.write().await = false;
+        self.set_connected_at(None);
+        self.session_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
 
-        if let Some(ref client) = self.client {
-            let _ = client.close().await;
+        if let Some(ref transport) = self.transport {
+            let params = DeregisterParams {
+                layer: self.config.name.clone(),
+            };
+            let notification = JsonRpcNotification::new(methods::DEREGISTER, params);
+            if let Err(e) = self.send_json(&notification).await {
+                warn!("{} could not deregister: {}", self.config.name, e);
+            }
+            let _ = transport.close().await;
         }
 
         info!("{} stopped", self.config.name);
     }
 
+    /// The most recent entropy update from the Hub, if any.
+    pub fn last_entropy(&self) -> Option<EntropyParams> {
+        self.last_entropy
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// What the Hub reported about itself when acknowledging registration.
+    ///
+    /// `None` until the run loop receives the registration result, and for
+    /// Hubs that don't send one.
+    pub fn hub_info(&self) -> Option<HubInfo> {
+        self.hub_info
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// The session id the Hub handed out at registration, if any.
+    ///
+    /// Sent back on reconnect so a Hub that supports it can resume the
+    /// session instead of starting over. Cleared when the Hub declines to
+    /// resume and by [`stop`](Self::stop).
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Get a snapshot of pre-check, decision, and reconnect metrics.
+    pub fn metrics(&self) -> SentinelMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Stop acting on pre-checks while staying connected and registered.
+    ///
+    /// While paused, every pre-check is answered with `Clear` without
+    /// calling `on_pre_check`. Other messages are still handled.
+    pub async fn pause(&self) {
+        *self.paused.write().await = true;
+        info!("{} paused", self.config.name);
+    }
+
+    /// Resume acting on pre-checks after [`pause`](Self::pause).
+    pub async fn resume_processing(&self) {
+        *self.paused.write().await = false;
+        info!("{} resumed processing", self.config.name);
+    }
+
+    /// Forget all cached pre-check decisions.
+    pub fn clear_decision_cache(&self) {
+        lock_cache(&self.decision_cache).clear();
+    }
+
+    /// Check if the Sentinel is paused.
+    pub async fn is_paused(&self) -> bool {
+        *self.paused.read().await
+    }
+
     /// Check if the Sentinel is running.
     pub async fn is_running(&self) -> bool {
         *self.running.read().await
     }
+
+    /// Get a snapshot of connection and loop health, e.g. for a readiness probe.
+    pub async fn health(&self) -> HealthStatus {
+        let (connected, hub_url) = match &self.transport {
+            Some(transport) => (transport.is_connected().await, transport.url().await),
+            None => (false, None),
+        };
+        let last_message = *self.last_message.lock().unwrap_or_else(|e| e.into_inner());
+
+        HealthStatus {
+            connected,
+            running: self.is_running().await,
+            last_message_ms_ago: last_message.map(|at| at.elapsed().as_millis() as u64),
+            reconnects: self.metrics.snapshot().reconnects,
+            hub_url,
+            connected_for_ms: self.connected_for().map(|up| up.as_millis() as u64),
+        }
+    }
+}
+
+/// Errors in a row while handling messages, for `max_consecutive_errors`.
+#[derive(Default)]
+struct ErrorStreak {
+    /// Errors ever recorded, to tell whether a message saw one
+    total: AtomicU64,
+    /// Errors since the last message handled without one
+    consecutive: AtomicU32,
+    /// The error that reached the limit, until `run` takes it
+    tripped: StdMutex<Option<Error>>,
+}
+
+impl ErrorStreak {
+    fn total(&self) -> u64 {
+        self.total.load(Ordering::SeqCst)
+    }
+
+    /// Count `error`, keeping it once the streak reaches `limit`.
+    fn record(&self, error: Error, limit: Option<u32>) {
+        self.total.fetch_add(1, Ordering::SeqCst);
+        let count = self.consecutive.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(limit) = limit.filter(|&limit| count >= limit) {
+            warn!("{} consecutive errors (limit {})", count, limit);
+            *self.tripped.lock().unwrap_or_else(|e| e.into_inner()) = Some(error);
+        }
+    }
+
+    fn reset(&self) {
+        self.consecutive.store(0, Ordering::SeqCst);
+    }
+
+    fn take_tripped(&self) -> Option<Error> {
+        self.tripped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+}
+
+/// Handles messages received by the run loop.
+///
+/// Holds shared handles only, so it can be cloned into worker tasks when
+/// `max_concurrent_handlers` is above 1.
+struct Dispatcher<H> {
+    config: Arc<SentinelConfig>,
+    handler: Arc<H>,
+    transport: Arc<dyn Transport>,
+    metrics: Arc<Metrics>,
+    interceptors: Interceptors,
+    paused: Arc<RwLock<bool>>,
+    decision_cache: Arc<StdMutex<DecisionCache>>,
+    unsent_decisions: Arc<StdMutex<DecisionCache>>,
+    last_entropy: Arc<StdMutex<Option<EntropyParams>>>,
+    in_flight: Arc<AtomicUsize>,
+    pre_check_subscriber: PreCheckSubscriber,
+    errors: Arc<ErrorStreak>,
+}
+
+impl<H> Clone for Dispatcher<H> {
+    fn clone(&self) -> Self {
+        Self {
+            config: Arc::clone(&self.config),
+            handler: Arc::clone(&self.handler),
+            transport: Arc::clone(&self.transport),
+            metrics: Arc::clone(&self.metrics),
+            interceptors: Arc::clone(&self.interceptors),
+            paused: Arc::clone(&self.paused),
+            decision_cache: Arc::clone(&self.decision_cache),
+            unsent_decisions: Arc::clone(&self.unsent_decisions),
+            last_entropy: Arc::clone(&self.last_entropy),
+            in_flight: Arc::clone(&self.in_flight),
+            pre_check_subscriber: Arc::clone(&self.pre_check_subscriber),
+            errors: Arc::clone(&self.errors),
+        }
+    }
+}
+
+impl<H: SentinelHandler + 'static> Dispatcher<H> {
+    /// Deserialize the params of `method`, honoring `strict_messages`.
+    ///
+    /// Malformed params become `Error::Protocol` (`-32602`) naming the
+    /// method and the offending field.
+    #[allow(clippy::result_large_err)]
+    fn params<T>(&self, method: &str, value: serde_json::Value) -> Result<T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let parsed = if self.config.strict_messages {
+            from_value_strict(value)
+        } else {
+            from_params(value)
+        };
+        parsed.map_err(|e| match e {
+            Error::Protocol { code, message } => Error::Protocol {
+                code,
+                message: format!("{}: {}", method, message),
+            },
+            e => e,
+        })
+    }
+
+    /// Handle a message, reporting failures to the handler.
+    async fn dispatch(&self, msg: RawMessage, cancel: CancelToken) {
+        let errors_before = self.errors.total();
+        match self.handle_message(msg, cancel).await {
+            Ok(()) if self.errors.total() == errors_before => self.errors.reset(),
+            Ok(()) => {}
+            Err(e) => self.report_error(e).await,
+        }
+    }
+
+    /// Pass `error` to `on_error` and count it towards `max_consecutive_errors`.
+    async fn report_error(&self, error: Error) {
+        self.handler.on_error(&error).await;
+        self.errors
+            .record(error, self.config.max_consecutive_errors);
+    }
+
+    /// Handle an incoming message from the Hub.
+    async fn handle_message(&self, msg: RawMessage, cancel: CancelToken) -> Result<()> {
+        debug!("Handling: {}", msg.method);
+
+        for interceptor in self.interceptors.iter() {
+            interceptor.on_inbound(&msg).await;
+        }
+
+        match msg.method.as_str() {
+            methods::PRE_CHECK => {
+                let params: PreCheckParams = self.params(&msg.method, msg.params)?;
+                self.metrics.record_pre_check();
+                let summary = self
+                    .config
+                    .log_decisions
+                    .then(|| PreCheckSummary::new(&params));
+
+                // Identifies the decision if it has to be kept for a resend.
+                let unsent_key = self
+                    .config
+                    .unsent_decision_ttl
+                    .map(|_| DecisionKey::new(&params));
+
+                // Held until the response is sent.
+                let in_flight =
+                    InFlight::try_acquire(&self.in_flight, self.config.max_in_flight_pre_checks);
+
+                let response = if in_flight.is_none() {
+                    warn!("Too many pre-checks in flight, asking Hub to wait");
+                    PreCheckResponse::wait_with_reason(
+                        self.config.in_flight_retry_after,
+                        "Too many pre-checks in flight",
+                    )
+                } else if *self.paused.read().await {
+                    debug!("Paused, clearing pre-check");
+                    PreCheckResponse::Clear
+                } else if self.config.auto_clear_when_stable
+                    && !params.has_blocking()
+                    && self.page_is_stable()
+                {
+                    debug!("Page is stable, clearing pre-check");
+                    PreCheckResponse::Clear
+                } else if let Some(response) = unsent_key
+                    .as_ref()
+                    .and_then(|key| lock_cache(&self.unsent_decisions).take(key))
+                {
+                    debug!("Answering with the decision lost to a disconnect");
+                    response
+                } else if self.config.decision_cache_size > 0 {
+                    let key = DecisionKey::new(&params);
+                    let cached = lock_cache(&self.decision_cache).get(&key);
+                    match cached {
+                        Some(response) => {
+                            debug!("Reusing cached decision");
+                            response
+                        }
+                        None => {
+                            let (response, from_handler) =
+                                self.decide(msg.id.as_ref(), params, cancel.clone()).await;
+                            if from_handler {
+                                lock_cache(&self.decision_cache).insert(key, response.clone());
+                            }
+                            response
+                        }
+                    }
+                } else {
+                    self.decide(msg.id.as_ref(), params, cancel.clone()).await.0
+                };
+
+                if let Some(id) = msg.id {
+                    match unsent_key {
+                        // The connection the pre-check came in on is gone.
+                        Some(key) if cancel.is_cancelled() => {
+                            debug!("Connection lost before responding, keeping decision");
+                            lock_cache(&self.unsent_decisions).insert(key, response);
+                        }
+                        Some(key) => {
+                            let sent = self
+                                .send_pre_check_response(&id, summary.as_ref(), response.clone())
+                                .await;
+                            if sent.is_err() {
+                                lock_cache(&self.unsent_decisions).insert(key, response);
+                            }
+                            sent?;
+                        }
+                        None => {
+                            self.send_pre_check_response(&id, summary.as_ref(), response)
+                                .await?;
+                        }
+                    }
+                }
+            }
+            methods::ENTROPY => {
+                let params: EntropyParams = self.params(&msg.method, msg.params)?;
+                *self.last_entropy.lock().unwrap_or_else(|e| e.into_inner()) = Some(params.clone());
+                if params.is_stable() {
+                    self.handler.on_entropy_stable(params.clone()).await;
+                } else if self.config.entropy_stable_only {
+                    return Ok(());
+                }
+                self.handler.on_entropy(params).await;
+            }
+            methods::CONTEXT_UPDATE => {
+                let params: ContextUpdateParams = self.params(&msg.method, msg.params)?;
+                lock_cache(&self.decision_cache).clear();
+                lock_cache(&self.unsent_decisions).clear();
+                self.handler.on_context_update(params.context).await;
+            }
+            _ if msg.is_response() => {
+                debug!("Unsolicited response to request {:?}", msg.id);
+            }
+            _ => {
+                self.handler
+                    .on_unknown_method(&msg.method, msg.params)
+                    .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the last entropy update showed a settled page.
+    fn page_is_stable(&self) -> bool {
+        self.last_entropy
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .is_some_and(EntropyParams::is_stable)
+    }
+
+    /// Ask the handler (or the [`Sentinel::pre_checks`] stream) for a
+    /// pre-check decision, honoring `pre_check_timeout`.
+    ///
+    /// Also returns whether the decision came from the handler rather than
+    /// the fallback.
+    async fn decide(
+        &self,
+        id: Option<&Id>,
+        params: PreCheckParams,
+        cancel: CancelToken,
+    ) -> (PreCheckResponse, bool) {
+        let started = Instant::now();
+        let decision = async {
+            let response = match stream::ask(&self.pre_check_subscriber, params, cancel).await {
+                Ok(response) => response,
+                Err((params, cancel)) => {
+                    let id = id.map(Id::to_string).unwrap_or_default();
+                    let (responder, response) = PreCheckResponder::new(cancel);
+                    self.handler
+                        .on_pre_check_deferred(id, params, responder)
+                        .await;
+                    response.await.ok()
+                }
+            };
+            match response {
+                Some(response) => (response, true),
+                None => {
+                    warn!("Pre-check dropped without a response, sending fallback");
+                    (self.config.pre_check_fallback.clone(), false)
+                }
+            }
+        };
+        let decided = match self.config.pre_check_timeout {
+            Some(limit) => match tokio::time::timeout(limit, decision).await {
+                Ok(decided) => decided,
+                Err(_) => {
+                    warn!("on_pre_check exceeded {:?}, sending fallback", limit);
+                    self.report_error(Error::Timeout).await;
+                    (self.config.pre_check_fallback.clone(), false)
+                }
+            },
+            None => decision.await,
+        };
+        self.metrics.record_latency(started.elapsed());
+        decided
+    }
+
+    /// Send pre-check response to Hub.
+    ///
+    /// `summary` is present when `log_decisions` is enabled.
+    async fn send_pre_check_response(
+        &self,
+        id: &Id,
+        summary: Option<&PreCheckSummary>,
+        response: PreCheckResponse,
+    ) -> Result<()> {
+        self.metrics.record_response(&response);
+
+        let (method, variant) = match &response {
+            PreCheckResponse::Clear => (methods::CLEAR, "clear"),
+            PreCheckResponse::Wait { .. } => (methods::WAIT, "wait"),
+            PreCheckResponse::Hijack { .. } => (methods::HIJACK, "hijack"),
+            PreCheckResponse::Defer { .. } => (methods::DEFER, "defer"),
+        };
+        Span::current().record("response", variant);
+        debug!("Responding with {}", method);
+        if let Some(summary) = summary {
+            summary.log(variant);
+        }
+
+        let frame = match self.config.response_dialect {
+            ResponseDialect::Response => {
+                let reply = JsonRpcResponse::success(id.clone(), response);
+                intercept(&self.interceptors, &reply).await?
+            }
+            ResponseDialect::Notification => {
+                let notification = JsonRpcNotification::new(method, response);
+                intercept(&self.interceptors, &notification).await?
+            }
+        };
+        self.transport.send_decision(frame).await
+    }
+}
+
+/// Most decisions kept for `unsent_decision_ttl` at once.
+const UNSENT_DECISIONS: usize = 64;
+
+/// Sleep until `deadline`, or forever when there is none.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Counts a pre-check as in flight until dropped.
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    /// Count one more pre-check, unless `limit` are already in flight.
+    fn try_acquire(counter: &Arc<AtomicUsize>, limit: usize) -> Option<Self> {
+        counter
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (limit == 0 || n < limit).then_some(n + 1)
+            })
+            .ok()?;
+        Some(Self(Arc::clone(counter)))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn lock_cache(cache: &StdMutex<DecisionCache>) -> std::sync::MutexGuard<'_, DecisionCache> {
+    cache.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The parts of a pre-check recorded alongside its decision.
+struct PreCheckSummary {
+    command: String,
+    selector: Option<String>,
+    blocking: usize,
+}
+
+impl PreCheckSummary {
+    fn new(params: &PreCheckParams) -> Self {
+        Self {
+            command: params.command.clone(),
+            selector: params.selector.clone(),
+            blocking: params.blocking.len(),
+        }
+    }
+
+    /// Log the pre-check and its decision as one structured event.
+    fn log(&self, decision: &str) {
+        info!(
+            command = %self.command,
+            selector = self.selector.as_deref().unwrap_or("-"),
+            blocking = self.blocking,
+            decision,
+            "Pre-check decision"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockHub, MockTransport};
+    use serde_json::json;
+    use std::sync::atomic::AtomicBool;
+
+    struct BlockingHandler;
+
+    #[async_trait::async_trait]
+    impl SentinelHandler for BlockingHandler {
+        async fn on_pre_check(
+            &self,
+            params: PreCheckParams,
+            _cancel: CancelToken,
+        ) -> PreCheckResponse {
+            if params.blocking.is_empty() {
+                PreCheckResponse::Clear
+            } else {
+                PreCheckResponse::Hijack {
+                    reason: format!("{} blocking", params.blocking.len()),
+                    confidence: None,
+                    targets: Vec::new(),
+                }
+            }
+        }
+    }
+
+    fn mock_sentinel<H: SentinelHandler + 'static>(
+        config: SentinelConfig,
+        handler: H,
+    ) -> (Arc<Sentinel<H>>, MockHub) {
+        let (transport, hub) = MockTransport::pair();
+        let sentinel = Sentinel::new_with_transport(config, handler, transport);
+        (Arc::new(sentinel), hub)
+    }
+
+    /// Start `sentinel.run()` in the background.
+    fn spawn_run<H: SentinelHandler + 'static>(
+        sentinel: &Arc<Sentinel<H>>,
+    ) -> tokio::task::JoinHandle<Result<()>> {
+        let sentinel = Arc::clone(sentinel);
+        tokio::spawn(async move { sentinel.run().await })
+    }
+
+    /// Drop the connection and check that `run` ended cleanly.
+    ///
+    /// Only for Sentinels without auto-reconnect, which would reconnect.
+    async fn disconnect_and_join(hub: &MockHub, runner: tokio::task::JoinHandle<Result<()>>) {
+        hub.disconnect();
+        runner.await.unwrap().unwrap();
+    }
+
+    /// Close with a policy violation, which ends `run` with an error even
+    /// when auto-reconnect is on.
+    async fn close_and_join(hub: &MockHub, runner: tokio::task::JoinHandle<Result<()>>) {
+        hub.close(1008, "done");
+        assert!(runner.await.unwrap().is_err());
+    }
+
+    fn pre_check(id: &str, blocking: serde_json::Value) -> serde_json::Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "starlight.pre_check",
+            "params": { "command": "click", "selector": "#submit", "blocking": blocking },
+            "id": id,
+        })
+    }
+
+    #[cfg(feature = "jwt")]
+    #[test]
+    fn test_jwt_secret_from_env_and_file() {
+        std::env::set_var("STARLIGHT_TEST_JWT_SECRET", "from-env");
+        let config = SentinelConfig::new("S", 5)
+            .with_jwt_secret_from_env("STARLIGHT_TEST_JWT_SECRET")
+            .unwrap();
+        assert_eq!(config.jwt_secret.as_deref(), Some("from-env"));
+
+        let missing = SentinelConfig::new("S", 5).with_jwt_secret_from_env("STARLIGHT_TEST_UNSET");
+        assert!(matches!(missing, Err(Error::InvalidConfig(_))));
+
+        let path = std::env::temp_dir().join(format!("starlight-secret-{}", Uuid::new_v4()));
+        std::fs::write(&path, "from-file\n").unwrap();
+        let config = SentinelConfig::new("S", 5)
+            .with_jwt_secret_from_file(&path)
+            .unwrap();
+        assert_eq!(config.jwt_secret.as_deref(), Some("from-file"));
+        std::fs::remove_file(&path).unwrap();
+
+        let unreadable = SentinelConfig::new("S", 5).with_jwt_secret_from_file(&path);
+        assert!(matches!(unreadable, Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        // Unique prefixes, since tests share the process environment.
+        let set = |prefix: &str, vars: &[(&str, &str)]| {
+            for (name, value) in vars {
+                std::env::set_var(format!("{}_{}", prefix, name), value);
+            }
+        };
+
+        set(
+            "STARLIGHT_TEST_FULL",
+            &[
+                ("NAME", "Janitor"),
+                ("PRIORITY", "3"),
+                ("SELECTORS", ".modal, .popup,,#cookie-banner "),
+                ("CAPABILITIES", "detection,healing"),
+                ("HUB_URL", "wss://hub.internal:8443"),
+                ("JWT_SECRET", "from-env"),
+            ],
+        );
+        #[cfg(feature = "jwt")]
+        {
+            let config = SentinelConfig::from_env("STARLIGHT_TEST_FULL").unwrap();
+            assert_eq!(config.name, "Janitor");
+            assert_eq!(config.priority, 3);
+            assert_eq!(config.selectors, vec![".modal", ".popup", "#cookie-banner"]);
+            assert_eq!(config.capabilities, vec!["detection", "healing"]);
+            assert_eq!(config.hub_url.as_deref(), Some("wss://hub.internal:8443"));
+            assert_eq!(config.jwt_secret.as_deref(), Some("from-env"));
+        }
+        #[cfg(not(feature = "jwt"))]
+        assert!(matches!(
+            SentinelConfig::from_env("STARLIGHT_TEST_FULL"),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        set("STARLIGHT_TEST_MIN", &[("NAME", "Min"), ("PRIORITY", "10")]);
+        let config = SentinelConfig::from_env("STARLIGHT_TEST_MIN").unwrap();
+        assert!(config.selectors.is_empty());
+        assert_eq!(config.hub_url, None);
+
+        let error = |prefix: &str| match SentinelConfig::from_env(prefix) {
+            Err(Error::InvalidConfig(message)) => message,
+            other => panic!("expected InvalidConfig, got {:?}", other.map(|c| c.name)),
+        };
+        set("STARLIGHT_TEST_NONAME", &[("PRIORITY", "5")]);
+        assert!(error("STARLIGHT_TEST_NONAME").contains("STARLIGHT_TEST_NONAME_NAME"));
+        set("STARLIGHT_TEST_RANGE", &[("NAME", "S"), ("PRIORITY", "11")]);
+        assert!(error("STARLIGHT_TEST_RANGE").contains("STARLIGHT_TEST_RANGE_PRIORITY"));
+        set("STARLIGHT_TEST_NAN", &[("NAME", "S"), ("PRIORITY", "high")]);
+        assert!(error("STARLIGHT_TEST_NAN").contains("1 to 10"));
+        set(
+            "STARLIGHT_TEST_URL",
+            &[("NAME", "S"), ("PRIORITY", "5"), ("HUB_URL", "http://hub")],
+        );
+        assert!(error("STARLIGHT_TEST_URL").contains("ws://"));
+    }
+
+    #[tokio::test]
+    async fn test_scripted_conversation_over_mock_transport() {
+        let config = SentinelConfig::new("MockSentinel", 3)
+            .with_selectors(vec![".modal"])
+            .without_auto_reconnect();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        assert_eq!(registration["method"], "starlight.registration");
+        assert_eq!(registration["params"]["layer"], "MockSentinel");
+        assert_eq!(registration["params"]["selectors"], json!([".modal"]));
+
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&pre_check("pc-1", json!([])));
+        let clear = hub.recv_json().await.unwrap();
+        assert_eq!(clear["method"], "starlight.clear");
+
+        hub.send_json(&pre_check("pc-2", json!([{ "selector": ".modal" }])));
+        let hijack = hub.recv_json().await.unwrap();
+        assert_eq!(hijack["method"], "starlight.hijack");
+        assert_eq!(hijack["params"]["reason"], "1 blocking");
+
+        disconnect_and_join(&hub, runner).await;
+        assert!(!sentinel.is_running().await);
+    }
+
+    #[tokio::test]
+    async fn test_hijack_session_finish() {
+        let (sentinel, mut hub) = mock_sentinel(SentinelConfig::new("S", 5), DefaultHandler);
+
+        let session = sentinel.begin_hijack("popup").await.unwrap();
+        session
+            .action(ActionCommand::Click, ".close", None)
+            .await
+            .unwrap();
+        session.finish(false).await.unwrap();
+
+        let methods: Vec<_> = [
+            hub.recv_json().await.unwrap(),
+            hub.recv_json().await.unwrap(),
+            hub.recv_json().await.unwrap(),
+        ]
+        .iter()
+        .map(|frame| frame["method"].as_str().unwrap().to_string())
+        .collect();
+        assert_eq!(
+            methods,
+            ["starlight.hijack", "starlight.action", "starlight.resume"]
+        );
+        assert!(hub.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_actions_preserve_order() {
+        let (sentinel, mut hub) = mock_sentinel(SentinelConfig::new("S", 5), DefaultHandler);
+        let steps = || {
+            vec![
+                (ActionCommand::Click, ".expand".to_string(), None),
+                (
+                    ActionCommand::Fill,
+                    "#reason".to_string(),
+                    Some("no".to_string()),
+                ),
+                (ActionCommand::Click, ".confirm".to_string(), None),
+                (ActionCommand::Remove, ".overlay".to_string(), None),
+            ]
+        };
+
+        // Two sequences at once still arrive one after the other.
+        let (first, second) = tokio::join!(sentinel.actions(steps()), sentinel.actions(steps()));
+        first.unwrap();
+        second.unwrap();
+
+        let mut selectors = Vec::new();
+        while let Some(frame) = hub.try_recv() {
+            let frame: serde_json::Value = match frame {
+                crate::transport::Message::Text(text) => serde_json::from_str(&text).unwrap(),
+                other => panic!("unexpected frame {:?}", other),
+            };
+            assert_eq!(frame["method"], "starlight.action");
+            selectors.push(frame["params"]["selector"].as_str().unwrap().to_string());
+        }
+        let expected = [".expand", "#reason", ".confirm", ".overlay"];
+        assert_eq!(selectors, [expected, expected].concat());
+    }
+
+    #[tokio::test]
+    async fn test_hijack_session_resumes_on_drop() {
+        let (sentinel, mut hub) = mock_sentinel(SentinelConfig::new("S", 5), DefaultHandler);
+
+        {
+            let _session = sentinel.begin_hijack("popup").await.unwrap();
+        }
+
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.hijack");
+        let resume = hub.recv_json().await.unwrap();
+        assert_eq!(resume["method"], "starlight.resume");
+        assert_eq!(resume["params"]["request_recheck"], true);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_outcomes() {
+        let config = SentinelConfig::new("S", 5).without_auto_reconnect();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&pre_check("1", json!([])));
+        hub.send_json(&pre_check("2", json!([{ "selector": ".modal" }])));
+        hub.send_json(&pre_check("3", json!([])));
+        for _ in 0..3 {
+            hub.recv_json().await.unwrap();
+        }
+
+        // Answered without calling the handler.
+        sentinel.pause().await;
+        hub.send_json(&pre_check("4", json!([{ "selector": ".modal" }])));
+        hub.recv_json().await.unwrap();
+
+        let metrics = sentinel.metrics();
+        assert_eq!(metrics.pre_checks, 4);
+        assert_eq!(metrics.clears, 3);
+        assert_eq!(metrics.hijacks, 1);
+        assert_eq!(metrics.waits, 0);
+        assert_eq!(
+            metrics.pre_checks,
+            metrics.clears + metrics.waits + metrics.hijacks + metrics.defers
+        );
+        assert_eq!(metrics.pre_check_latency.count, 3);
+
+        disconnect_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_imperative_wait() {
+        let (sentinel, mut hub) = mock_sentinel(SentinelConfig::new("S", 5), BlockingHandler);
+
+        sentinel
+            .wait(
+                Duration::from_secs(2),
+                Some("Page still loading".to_string()),
+            )
+            .await
+            .unwrap();
+
+        let wait = hub.recv_json().await.unwrap();
+        assert_eq!(wait["method"], "starlight.wait");
+        assert_eq!(wait["params"]["retryAfterMs"], 2000);
+        assert_eq!(wait["params"]["reason"], "Page still loading");
+    }
+
+    #[tokio::test]
+    async fn test_response_dialects() {
+        for dialect in [ResponseDialect::Notification, ResponseDialect::Response] {
+            let config = SentinelConfig::new("S", 5)
+                .without_auto_reconnect()
+                .with_response_dialect(dialect);
+            let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+            let runner = spawn_run(&sentinel);
+
+            hub.send_json(&pre_check("pc-7", json!([{ "selector": ".modal" }])));
+            let frame = hub.recv_json().await.unwrap();
+            match dialect {
+                ResponseDialect::Notification => {
+                    assert_eq!(frame["method"], "starlight.hijack");
+                    assert_eq!(frame["params"]["response"], "hijack");
+                    assert_eq!(frame["params"]["reason"], "1 blocking");
+                    assert!(frame.get("id").is_none());
+                    assert!(frame.get("result").is_none());
+                }
+                ResponseDialect::Response => {
+                    assert_eq!(frame["jsonrpc"], "2.0");
+                    assert_eq!(frame["id"], "pc-7");
+                    assert_eq!(frame["result"]["response"], "hijack");
+                    assert_eq!(frame["result"]["reason"], "1 blocking");
+                    assert!(frame.get("method").is_none());
+                    assert!(frame.get("params").is_none());
+                }
+            }
+
+            disconnect_and_join(&hub, runner).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pre_check_timeout_sends_fallback() {
+        struct SlowHandler {
+            errors: std::sync::Mutex<Vec<String>>,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for SlowHandler {
+            async fn on_pre_check(
+                &self,
+                _params: PreCheckParams,
+                _cancel: CancelToken,
+            ) -> PreCheckResponse {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                PreCheckResponse::Clear
+            }
+
+            async fn on_error(&self, error: &Error) {
+                self.errors.lock().unwrap().push(error.to_string());
+            }
+        }
+
+        let fallback = PreCheckResponse::Wait {
+            retry_after_ms: 250,
+            reason: None,
+        };
+        let config = SentinelConfig::new("S", 5)
+            .without_auto_reconnect()
+            .with_pre_check_timeout(Duration::from_millis(20), fallback);
+        let handler = SlowHandler {
+            errors: std::sync::Mutex::new(Vec::new()),
+        };
+        let (sentinel, mut hub) = mock_sentinel(config, handler);
+
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&pre_check("slow", json!([])));
+        let response = hub.recv_json().await.unwrap();
+        assert_eq!(response["method"], "starlight.wait");
+        assert_eq!(response["params"]["retryAfterMs"], 250);
+
+        disconnect_and_join(&hub, runner).await;
+        assert_eq!(
+            *sentinel.handler.errors.lock().unwrap(),
+            vec![Error::Timeout.to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_action_validation() {
+        let config = SentinelConfig::new("S", 5).with_action_validation();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        let err = sentinel
+            .action(ActionCommand::Fill, "#email", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+        let err = sentinel
+            .action_and_wait(ActionCommand::Fill, "#email", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+        assert!(hub.try_recv().is_none());
+
+        sentinel
+            .action(ActionCommand::Fill, "#email", Some("me@example.com".into()))
+            .await
+            .unwrap();
+        let action = hub.recv_json().await.unwrap();
+        assert_eq!(action["params"]["text"], "me@example.com");
+
+        // Hijack sessions validate too.
+        let session = sentinel.begin_hijack("Filling form").await.unwrap();
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.hijack");
+        let err = session
+            .action(ActionCommand::Fill, "#email", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+        assert!(hub.try_recv().is_none());
+        session.finish(false).await.unwrap();
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.resume");
+
+        // Without validation the action goes out as given.
+        let (sentinel, mut hub) = mock_sentinel(SentinelConfig::new("S", 5), BlockingHandler);
+        sentinel
+            .action(ActionCommand::Fill, "#email", None)
+            .await
+            .unwrap();
+        let action = hub.recv_json().await.unwrap();
+        assert_eq!(action["params"]["cmd"], "fill");
+    }
+
+    #[tokio::test]
+    async fn test_action_resent_after_failed_write() {
+        use crate::transport::Message;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        /// Times out the first write, then sends normally.
+        struct FirstWriteFails {
+            inner: MockTransport,
+            failed: AtomicBool,
+        }
+
+        #[async_trait::async_trait]
+        impl Transport for FirstWriteFails {
+            async fn send(&self, message: Message) -> Result<()> {
+                if !self.failed.swap(true, Ordering::SeqCst) {
+                    return Err(Error::Timeout);
+                }
+                self.inner.send(message).await
+            }
+
+            async fn recv(&self) -> Result<Option<RawMessage>> {
+                self.inner.recv().await
+            }
+        }
+
+        let (inner, mut hub) = MockTransport::pair();
+        let transport = FirstWriteFails {
+            inner,
+            failed: AtomicBool::new(false),
+        };
+        let config = SentinelConfig::new("S", 5).with_send_retries(2, Duration::from_millis(1));
+        let sentinel = Sentinel::new_with_transport(config, BlockingHandler, transport);
+
+        sentinel
+            .action(ActionCommand::Click, ".close", None)
+            .await
+            .unwrap();
+        let action = hub.recv_json().await.unwrap();
+        assert_eq!(action["method"], "starlight.action");
+        assert_eq!(action["params"]["selector"], ".close");
+        assert!(hub.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_sends_only_registration() {
+        let config = SentinelConfig::new("Shadow", 5)
+            .without_auto_reconnect()
+            .with_dry_run();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        assert_eq!(registration["method"], "starlight.registration");
+
+        let runner = spawn_run(&sentinel);
+        hub.send_json(&pre_check("pc-1", json!([{ "selector": ".modal" }])));
+        while sentinel.metrics().hijacks == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        sentinel.hijack("manual").await.unwrap();
+        sentinel
+            .action(ActionCommand::Click, ".close", None)
+            .await
+            .unwrap();
+        // Give the pre-check response time to arrive, were it sent.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(hub.try_recv().is_none());
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_max_consecutive_errors_stops_run() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct FlakyHandler {
+            healthy: AtomicBool,
+            errors: AtomicUsize,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for FlakyHandler {
+            async fn on_pre_check(
+                &self,
+                _params: PreCheckParams,
+                _cancel: CancelToken,
+            ) -> PreCheckResponse {
+                if !self.healthy.load(Ordering::SeqCst) {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+                PreCheckResponse::Clear
+            }
+
+            async fn on_error(&self, _error: &Error) {
+                self.errors.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let config = SentinelConfig::new("S", 5)
+            .without_auto_reconnect()
+            .with_pre_check_timeout(Duration::from_millis(10), PreCheckResponse::Clear)
+            .with_max_consecutive_errors(3);
+        let (sentinel, mut hub) = mock_sentinel(config, FlakyHandler::default());
+
+        let runner = spawn_run(&sentinel);
+
+        // Two failures, then a success resets the count.
+        for id in ["a", "b"] {
+            hub.send_json(&pre_check(id, json!([])));
+            hub.recv_json().await.unwrap();
+        }
+        sentinel.handler.healthy.store(true, Ordering::SeqCst);
+        hub.send_json(&pre_check("c", json!([])));
+        hub.recv_json().await.unwrap();
+        assert!(sentinel.is_running().await);
+
+        sentinel.handler.healthy.store(false, Ordering::SeqCst);
+        for id in ["d", "e", "f"] {
+            hub.send_json(&pre_check(id, json!([])));
+            hub.recv_json().await.unwrap();
+        }
+
+        let result = tokio::time::timeout(Duration::from_secs(2), runner)
+            .await
+            .expect("run should stop after the third error in a row")
+            .unwrap();
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert_eq!(sentinel.handler.errors.load(Ordering::SeqCst), 5);
+        assert!(!sentinel.is_running().await);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_handlers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct SlowEntropy {
+            active: AtomicUsize,
+            peak: AtomicUsize,
+            done: AtomicUsize,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for SlowEntropy {
+            async fn on_entropy(&self, _params: EntropyParams) {
+                let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                self.active.fetch_sub(1, Ordering::SeqCst);
+                self.done.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let config = SentinelConfig::new("S", 5)
+            .without_auto_reconnect()
+            .with_max_concurrent_handlers(2);
+        let (sentinel, hub) = mock_sentinel(config, SlowEntropy::default());
+
+        let runner = spawn_run(&sentinel);
+
+        let entropy = json!({
+            "jsonrpc": "2.0",
+            "method": "starlight.entropy",
+            "params": { "url": "https://example.com" },
+        });
+        hub.send_json(&entropy);
+        hub.send_json(&entropy);
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sentinel.handler.done.load(Ordering::SeqCst) < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("entropy handlers did not finish");
+        assert_eq!(sentinel.handler.peak.load(Ordering::SeqCst), 2);
+
+        disconnect_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_strict_messages_reject_unknown_fields() {
+        struct RecordErrors(std::sync::Mutex<Vec<Option<i32>>>);
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for RecordErrors {
+            async fn on_error(&self, error: &Error) {
+                self.0.lock().unwrap().push(error.code());
+            }
+        }
+
+        let mut drifted = pre_check("pc-1", json!([]));
+        drifted["params"]["viewport"] = json!({ "width": 1280 });
+
+        for strict in [false, true] {
+            let mut config = SentinelConfig::new("S", 5).without_auto_reconnect();
+            if strict {
+                config = config.with_strict_messages();
+            }
+            let (sentinel, mut hub) =
+                mock_sentinel(config, RecordErrors(std::sync::Mutex::new(Vec::new())));
+
+            let runner = spawn_run(&sentinel);
+
+            hub.send_json(&drifted);
+            if !strict {
+                let clear = hub.recv_json().await.unwrap();
+                assert_eq!(clear["method"], "starlight.clear");
+            }
+            disconnect_and_join(&hub, runner).await;
+
+            let errors = sentinel.handler.0.lock().unwrap().clone();
+            if strict {
+                assert_eq!(errors, vec![Some(-32602)]);
+                assert!(hub.try_recv().is_none());
+            } else {
+                assert!(errors.is_empty());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_raw() {
+        let (sentinel, mut hub) = mock_sentinel(SentinelConfig::new("S", 5), BlockingHandler);
+
+        let id = sentinel
+            .send_raw("starlight.custom", json!({ "x": 1 }), false)
+            .await
+            .unwrap()
+            .unwrap();
+        let request = hub.recv_json().await.unwrap();
+        assert_eq!(request["jsonrpc"], "2.0");
+        assert_eq!(request["method"], "starlight.custom");
+        assert_eq!(request["params"]["x"], 1);
+        assert_eq!(request["id"], id.to_string());
+
+        let id = sentinel
+            .send_raw("starlight.custom_event", json!({}), true)
+            .await
+            .unwrap();
+        assert!(id.is_none());
+        let notification = hub.recv_json().await.unwrap();
+        assert_eq!(notification["method"], "starlight.custom_event");
+        assert!(notification.get("id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_method_hook() {
+        struct RecordUnknown(std::sync::Mutex<Vec<(String, serde_json::Value)>>);
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for RecordUnknown {
+            async fn on_unknown_method(&self, method: &str, params: serde_json::Value) {
+                self.0.lock().unwrap().push((method.to_string(), params));
+            }
+        }
+
+        let config = SentinelConfig::new("S", 5).without_auto_reconnect();
+        let (sentinel, hub) =
+            mock_sentinel(config, RecordUnknown(std::sync::Mutex::new(Vec::new())));
+
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "method": "starlight.future_thing",
+            "params": { "level": 2 },
+        }));
+        hub.disconnect();
+        sentinel.run().await.unwrap();
+
+        let seen = sentinel.handler.0.lock().unwrap();
+        assert_eq!(
+            *seen,
+            vec![("starlight.future_thing".to_string(), json!({ "level": 2 }))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_restores_registration() {
+        let config = SentinelConfig::new("MockSentinel", 3)
+            .with_capabilities(vec!["detection", "healing"])
+            .with_selectors(vec![".modal", ".cookie-banner"]);
+        #[cfg(feature = "jwt")]
+        let config = config.with_jwt_secret("test-secret");
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let original = hub.recv_json().await.unwrap();
+
+        let runner = spawn_run(&sentinel);
+
+        hub.disconnect();
+        let restored = hub.recv_json().await.unwrap();
+        assert_eq!(restored["method"], "starlight.registration");
+        for field in ["layer", "priority", "capabilities", "selectors"] {
+            assert_eq!(
+                restored["params"][field], original["params"][field],
+                "{}",
+                field
+            );
+        }
+        #[cfg(feature = "jwt")]
+        assert!(restored["params"]["auth_token"].is_string());
+        assert_eq!(sentinel.metrics().reconnects, 1);
+
+        close_and_join(&hub, runner).await;
+    }
+
+    /// Answer `registration` with `session_id` and wait until the run loop has seen it.
+    async fn grant_session(hub: &mut MockHub, registration: &serde_json::Value, session_id: &str) {
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "id": registration["id"],
+            "result": { "session_id": session_id }
+        }));
+        hub.send_json(&pre_check("pc-sync", json!([])));
+        hub.recv_json().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_resumes_session() {
+        let config = SentinelConfig::new("MockSentinel", 3);
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let original = hub.recv_json().await.unwrap();
+        assert!(original["params"].get("resume_session").is_none());
+
+        let runner = spawn_run(&sentinel);
+        grant_session(&mut hub, &original, "sess-1").await;
+        assert_eq!(sentinel.session_id().as_deref(), Some("sess-1"));
+
+        hub.disconnect();
+        let restored = hub.recv_json().await.unwrap();
+        assert_eq!(restored["method"], "starlight.registration");
+        assert_eq!(restored["params"]["resume_session"], "sess-1");
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_declined_resume_registers_fresh() {
+        let config = SentinelConfig::new("MockSentinel", 3);
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let original = hub.recv_json().await.unwrap();
+        let runner = spawn_run(&sentinel);
+        grant_session(&mut hub, &original, "sess-1").await;
+
+        hub.disconnect();
+        let resumed = hub.recv_json().await.unwrap();
+        assert_eq!(resumed["params"]["resume_session"], "sess-1");
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "id": resumed["id"],
+            "error": { "code": -32001, "message": "unknown session" }
+        }));
+
+        let fresh = hub.recv_json().await.unwrap();
+        assert_eq!(fresh["method"], "starlight.registration");
+        assert_ne!(fresh["id"], resumed["id"]);
+        assert!(fresh["params"].get("resume_session").is_none());
+        assert_eq!(sentinel.session_id(), None);
+
+        grant_session(&mut hub, &fresh, "sess-2").await;
+        assert_eq!(sentinel.session_id().as_deref(), Some("sess-2"));
+        assert!(sentinel.is_running().await);
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct RecordingDelay(Arc<StdMutex<Vec<Duration>>>);
+
+    #[async_trait::async_trait]
+    impl Delay for RecordingDelay {
+        async fn sleep(&self, duration: Duration) {
+            self.0.lock().unwrap().push(duration);
+        }
+    }
+
+    fn retry_after_error(id: &serde_json::Value) -> serde_json::Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": 429, "message": "slow down", "data": { "retry_after_ms": 5000 } }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_registration_retry_after_is_honored() {
+        let delays = RecordingDelay::default();
+        let config = SentinelConfig::new("MockSentinel", 3).with_delay(delays.clone());
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        hub.send_json(&retry_after_error(&registration["id"]));
+
+        let runner = spawn_run(&sentinel);
+        let retried = hub.recv_json().await.unwrap();
+        assert_eq!(retried["method"], "starlight.registration");
+        assert_ne!(retried["id"], registration["id"]);
+        assert_eq!(*delays.0.lock().unwrap(), vec![Duration::from_secs(5)]);
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_registration_retry_after_without_auto_reconnect() {
+        let config = SentinelConfig::new("MockSentinel", 3).without_auto_reconnect();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        hub.send_json(&retry_after_error(&registration["id"]));
+
+        let err = sentinel.run().await.unwrap_err();
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(5)));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_waits_for_retry_after() {
+        let delays = RecordingDelay::default();
+        let config = SentinelConfig::new("MockSentinel", 3).with_delay(delays.clone());
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        hub.recv_json().await.unwrap();
+        let runner = spawn_run(&sentinel);
+
+        let call = tokio::spawn({
+            let sentinel = Arc::clone(&sentinel);
+            async move {
+                sentinel
+                    .action_and_wait(ActionCommand::Click, "#ok", None)
+                    .await
+            }
+        });
+        let request = hub.recv_json().await.unwrap();
+        hub.send_json(&retry_after_error(&request["id"]));
+        let ack = call.await.unwrap().unwrap();
+        assert_eq!(ack.retry_after, Some(Duration::from_secs(5)));
+        assert!(delays.0.lock().unwrap().is_empty());
+
+        hub.disconnect();
+        let restored = hub.recv_json().await.unwrap();
+        assert_eq!(restored["method"], "starlight.registration");
+        assert_eq!(*delays.0.lock().unwrap(), vec![Duration::from_secs(5)]);
+
+        // Only the reconnect right after the request waits.
+        hub.disconnect();
+        hub.recv_json().await.unwrap();
+        assert_eq!(delays.0.lock().unwrap().len(), 1);
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_stop_deregisters_before_closing() {
+        use crate::transport::Message;
+
+        let config = SentinelConfig::new("MockSentinel", 3).without_auto_reconnect();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        hub.recv_json().await.unwrap();
+
+        sentinel.stop().await;
+        match hub.recv().await {
+            Some(Message::Text(text)) => {
+                let deregister: serde_json::Value = serde_json::from_str(&text).unwrap();
+                assert_eq!(deregister["method"], "starlight.deregister");
+                assert_eq!(deregister["params"]["layer"], "MockSentinel");
+                assert!(deregister.get("id").is_none());
+            }
+            other => panic!("expected deregister, got {:?}", other),
+        }
+        assert!(matches!(hub.recv().await, Some(Message::Close(_))));
+
+        // Already closed: nothing more is sent and stopping still succeeds.
+        sentinel.stop().await;
+        assert!(hub.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_registration_sends_selector_priorities() {
+        let config = SentinelConfig::new("MockSentinel", 5)
+            .with_selectors(vec![".captcha", ".banner"])
+            .with_selector_priority(".captcha", 0)
+            .with_selector_priority(".banner", 42);
+        assert_eq!(config.selector_priorities[".captcha"], 1);
+        assert_eq!(config.selector_priorities[".banner"], 10);
+
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        assert_eq!(
+            registration["params"]["selector_priorities"],
+            json!({ ".captcha": 1, ".banner": 10 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_runtime_selector_updates() {
+        let config = SentinelConfig::new("MockSentinel", 3).with_selectors(vec![".modal"]);
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel
+            .add_selectors(vec![".modal".into(), ".paywall".into()])
+            .await
+            .unwrap();
+        let update = hub.recv_json().await.unwrap();
+        assert_eq!(update["method"], "starlight.update_selectors");
+        assert_eq!(update["params"]["add"], json!([".paywall"]));
+        assert_eq!(update["params"]["remove"], json!([]));
+
+        sentinel
+            .remove_selectors(vec![".modal".into()])
+            .await
+            .unwrap();
+        let update = hub.recv_json().await.unwrap();
+        assert_eq!(update["params"]["remove"], json!([".modal"]));
+        assert_eq!(sentinel.selectors().await, vec![".paywall"]);
+
+        let runner = spawn_run(&sentinel);
+
+        hub.disconnect();
+        let restored = hub.recv_json().await.unwrap();
+        assert_eq!(restored["method"], "starlight.registration");
+        assert_eq!(restored["params"]["selectors"], json!([".paywall"]));
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_interceptors_see_both_directions() {
+        struct Signer;
+
+        #[async_trait::async_trait]
+        impl MessageInterceptor for Signer {
+            async fn on_outbound(&self, frame: &mut serde_json::Value) {
+                frame["signature"] = json!("signed");
+            }
+        }
+
+        #[derive(Default)]
+        struct Recorder {
+            seen: std::sync::Mutex<Vec<String>>,
+        }
+
+        #[async_trait::async_trait]
+        impl MessageInterceptor for Arc<Recorder> {
+            async fn on_outbound(&self, frame: &mut serde_json::Value) {
+                // Runs after Signer, so the signature is already present.
+                assert_eq!(frame["signature"], "signed");
+                let method = frame["method"].as_str().unwrap_or_default();
+                self.seen.lock().unwrap().push(format!("out:{}", method));
+            }
+
+            async fn on_inbound(&self, msg: &RawMessage) {
+                self.seen.lock().unwrap().push(format!("in:{}", msg.method));
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let (transport, mut hub) = MockTransport::pair();
+        let sentinel = Sentinel::new_with_transport(
+            SentinelConfig::new("MockSentinel", 3),
+            BlockingHandler,
+            transport,
+        )
+        .with_interceptor(Signer)
+        .with_interceptor(Arc::clone(&recorder));
+        let sentinel = Arc::new(sentinel);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        assert_eq!(registration["signature"], "signed");
+
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&pre_check("pc-1", json!([])));
+        let response = hub.recv_json().await.unwrap();
+        assert_eq!(response["method"], "starlight.clear");
+        assert_eq!(response["signature"], "signed");
+
+        sentinel.hijack("Intercepted").await.unwrap();
+        hub.recv_json().await.unwrap();
+
+        close_and_join(&hub, runner).await;
+
+        assert_eq!(
+            *recorder.seen.lock().unwrap(),
+            vec![
+                "out:starlight.registration",
+                "in:starlight.pre_check",
+                "out:starlight.clear",
+                "out:starlight.hijack",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registration_result_version_mismatch() {
+        let (sentinel, mut hub) =
+            mock_sentinel(SentinelConfig::new("MockSentinel", 3), BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+
+        // A compatible minor version is accepted and the loop keeps running.
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "id": registration["id"],
+            "result": { "protocol_version": "1.1.0" }
+        }));
+        let runner = spawn_run(&sentinel);
+        hub.send_json(&pre_check("pc-1", json!([])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.clear");
+
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "id": registration["id"],
+            "result": { "protocol_version": "2.0.0" }
+        }));
+        let err = runner.await.unwrap().unwrap_err();
+        assert!(matches!(err, Error::Handshake(_)), "{:?}", err);
+        assert!(!sentinel.is_running().await);
+    }
+
+    #[tokio::test]
+    async fn test_id_generator_names_requests() {
+        let next = AtomicUsize::new(1);
+        let config = SentinelConfig::new("MockSentinel", 3).with_id_generator(move |method| {
+            format!("trace-7:{}:{}", method, next.fetch_add(1, Ordering::SeqCst))
+        });
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        assert_eq!(registration["id"], "trace-7:starlight.registration:1");
+
+        let id = sentinel
+            .send_raw("starlight.custom", json!({}), false)
+            .await
+            .unwrap();
+        assert_eq!(id, Some(Id::from("trace-7:starlight.custom:2")));
+        assert_eq!(
+            hub.recv_json().await.unwrap()["id"],
+            "trace-7:starlight.custom:2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_id_prefix_tags_requests() {
+        let config = SentinelConfig::new("MockSentinel", 3).with_id_prefix("fleet-7");
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        let id = registration["id"].as_str().unwrap();
+        let uuid = id.strip_prefix("fleet-7-reg-").unwrap();
+        assert!(Uuid::parse_str(uuid).is_ok(), "{}", id);
+
+        let id = sentinel
+            .send_raw("starlight.custom", json!({}), false)
+            .await
+            .unwrap()
+            .unwrap()
+            .to_string();
+        assert!(id.starts_with("fleet-7-") && !id.contains("reg"), "{}", id);
+
+        // An explicit generator wins over the prefix.
+        let config = SentinelConfig::new("MockSentinel", 3)
+            .with_id_prefix("fleet-7")
+            .with_id_generator(|method| format!("custom:{}", method));
+        assert_eq!(
+            config.next_id(methods::REGISTRATION),
+            Id::from("custom:starlight.registration")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hub_info_from_registration_result() {
+        let config = SentinelConfig::new("MockSentinel", 3).without_auto_reconnect();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel.handshake().await.unwrap();
+        let registration = hub.recv_json().await.unwrap();
+        assert_eq!(sentinel.hub_info(), None);
+
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "id": registration["id"],
+            "result": {
+                "protocol_version": "1.0.0",
+                "hub_version": "3.2.1",
+                "hub_instance_id": "hub-eu-1"
+            }
+        }));
+        let runner = spawn_run(&sentinel);
+        hub.send_json(&pre_check("pc-1", json!([])));
+        hub.recv_json().await.unwrap();
+
+        let info = sentinel.hub_info().unwrap();
+        assert_eq!(info.protocol_version.as_deref(), Some("1.0.0"));
+        assert_eq!(info.version.as_deref(), Some("3.2.1"));
+        assert_eq!(info.instance_id.as_deref(), Some("hub-eu-1"));
+
+        disconnect_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_auto_clear_when_stable() {
+        struct AlwaysHijack;
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for AlwaysHijack {
+            async fn on_pre_check(
+                &self,
+                _params: PreCheckParams,
+                _cancel: CancelToken,
+            ) -> PreCheckResponse {
+                PreCheckResponse::hijack("handler")
+            }
+        }
+
+        let entropy = |mutations: u32, network_pending: u32| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": "starlight.entropy",
+                "params": { "url": "/", "mutations": mutations, "network_pending": network_pending }
+            })
+        };
+
+        let config = SentinelConfig::new("MockSentinel", 3)
+            .without_auto_reconnect()
+            .with_auto_clear_when_stable();
+        let (sentinel, mut hub) = mock_sentinel(config, AlwaysHijack);
+        let runner = spawn_run(&sentinel);
+
+        // No entropy seen yet: the handler decides.
+        hub.send_json(&pre_check("pc-1", json!([])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.hijack");
+
+        hub.send_json(&entropy(0, 0));
+        hub.send_json(&pre_check("pc-2", json!([])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.clear");
+        assert!(sentinel.last_entropy().unwrap().is_stable());
+
+        // Blocking elements still go to the handler.
+        hub.send_json(&pre_check("pc-3", json!([{ "selector": ".modal" }])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.hijack");
+
+        hub.send_json(&entropy(4, 0));
+        hub.send_json(&pre_check("pc-4", json!([])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.hijack");
+
+        disconnect_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_entropy_stable_callback() {
+        #[derive(Default)]
+        struct EntropyHandler {
+            raw: std::sync::Mutex<Vec<u32>>,
+            stable: std::sync::Mutex<Vec<String>>,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for Arc<EntropyHandler> {
+            async fn on_entropy(&self, params: EntropyParams) {
+                self.raw.lock().unwrap().push(params.mutations);
+            }
+
+            async fn on_entropy_stable(&self, params: EntropyParams) {
+                self.stable.lock().unwrap().push(params.url);
+            }
+        }
+
+        let entropy = |url: &str, mutations: u32, network_pending: u32| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": "starlight.entropy",
+                "params": { "url": url, "mutations": mutations, "network_pending": network_pending }
+            })
+        };
+
+        for stable_only in [false, true] {
+            let mut config = SentinelConfig::new("MockSentinel", 3).without_auto_reconnect();
+            if stable_only {
+                config = config.with_entropy_stable_only();
+            }
+            let handler = Arc::new(EntropyHandler::default());
+            let (sentinel, hub) = mock_sentinel(config, Arc::clone(&handler));
+
+            hub.send_json(&entropy("/noisy", 12, 3));
+            hub.send_json(&entropy("/loading", 0, 2));
+            hub.send_json(&entropy("/settled", 0, 0));
+            hub.disconnect();
+            let _ = sentinel.run().await;
+
+            assert_eq!(*handler.stable.lock().unwrap(), vec!["/settled"]);
+            let expected: Vec<u32> = if stable_only { vec![0] } else { vec![12, 0, 0] };
+            assert_eq!(*handler.raw.lock().unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paused_sentinel_clears_pre_checks() {
+        let (sentinel, mut hub) =
+            mock_sentinel(SentinelConfig::new("MockSentinel", 3), BlockingHandler);
+        let runner = spawn_run(&sentinel);
+        let blocking = json!([{ "selector": ".modal" }]);
+
+        sentinel.pause().await;
+        assert!(sentinel.is_paused().await);
+        hub.send_json(&pre_check("pc-1", blocking.clone()));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.clear");
+
+        sentinel.resume_processing().await;
+        assert!(!sentinel.is_paused().await);
+        hub.send_json(&pre_check("pc-2", blocking));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.hijack");
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_decision_cache() {
+        #[derive(Default)]
+        struct CountingHandler {
+            calls: std::sync::atomic::AtomicUsize,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for Arc<CountingHandler> {
+            async fn on_pre_check(
+                &self,
+                _params: PreCheckParams,
+                _cancel: CancelToken,
+            ) -> PreCheckResponse {
+                self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                PreCheckResponse::Hijack {
+                    reason: "expensive check".into(),
+                    confidence: None,
+                    targets: Vec::new(),
+                }
+            }
+        }
+
+        let config =
+            SentinelConfig::new("MockSentinel", 3).with_decision_cache(16, Duration::from_secs(60));
+        let handler = Arc::new(CountingHandler::default());
+        let (sentinel, mut hub) = mock_sentinel(config, Arc::clone(&handler));
+        let calls = || handler.calls.load(std::sync::atomic::Ordering::SeqCst);
+
+        let runner = spawn_run(&sentinel);
+
+        for id in ["pc-1", "pc-2"] {
+            hub.send_json(&pre_check(id, json!([])));
+            assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.hijack");
+        }
+        assert_eq!(calls(), 1);
+
+        // Context updates invalidate cached decisions.
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "method": "starlight.context_update",
+            "params": { "context": { "user": "qa" } }
+        }));
+        hub.send_json(&pre_check("pc-3", json!([])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.hijack");
+        assert_eq!(calls(), 2);
+
+        sentinel.clear_decision_cache();
+        hub.send_json(&pre_check("pc-4", json!([])));
+        hub.recv_json().await.unwrap();
+        assert_eq!(calls(), 3);
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_unsent_decision_answers_repeated_pre_check() {
+        #[derive(Default)]
+        struct GatedHandler {
+            calls: AtomicUsize,
+            started: tokio::sync::Notify,
+            release: tokio::sync::Notify,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for Arc<GatedHandler> {
+            async fn on_pre_check(
+                &self,
+                _params: PreCheckParams,
+                _cancel: CancelToken,
+            ) -> PreCheckResponse {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                self.started.notify_one();
+                self.release.notified().await;
+                PreCheckResponse::hijack("expensive check")
+            }
+        }
+
+        let config = SentinelConfig::new("MockSentinel", 3)
+            .with_unsent_decision_replay(Duration::from_secs(30));
+        let handler = Arc::new(GatedHandler::default());
+        let (sentinel, mut hub) = mock_sentinel(config, Arc::clone(&handler));
+        let runner = spawn_run(&sentinel);
+
+        // The connection drops after the handler starts deciding.
+        hub.send_json(&pre_check("pc-1", json!([])));
+        handler.started.notified().await;
+        hub.disconnect();
+        tokio::task::yield_now().await;
+        handler.release.notify_one();
+
+        let registration = hub.recv_json().await.unwrap();
+        assert_eq!(registration["method"], "starlight.registration");
+
+        // The Hub asks again and gets the kept decision.
+        hub.send_json(&pre_check("pc-2", json!([])));
+        let response = hub.recv_json().await.unwrap();
+        assert_eq!(response["method"], "starlight.hijack");
+        assert_eq!(response["params"]["reason"], "expensive check");
+        assert_eq!(handler.calls.load(Ordering::SeqCst), 1);
+
+        // It is used only once.
+        hub.send_json(&pre_check("pc-3", json!([])));
+        handler.started.notified().await;
+        handler.release.notify_one();
+        hub.recv_json().await.unwrap();
+        assert_eq!(handler.calls.load(Ordering::SeqCst), 2);
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_pre_check_cancelled_on_connection_loss() {
+        #[derive(Default)]
+        struct SlowHandler {
+            started: tokio::sync::Notify,
+            cancelled: AtomicBool,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for Arc<SlowHandler> {
+            async fn on_pre_check(
+                &self,
+                _params: PreCheckParams,
+                cancel: CancelToken,
+            ) -> PreCheckResponse {
+                self.started.notify_one();
+                tokio::select! {
+                    _ = cancel.cancelled() => self.cancelled.store(true, Ordering::SeqCst),
+                    _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                }
+                PreCheckResponse::Clear
+            }
+        }
+
+        let config = SentinelConfig::new("MockSentinel", 3).without_auto_reconnect();
+        let handler = Arc::new(SlowHandler::default());
+        let (sentinel, hub) = mock_sentinel(config, Arc::clone(&handler));
+
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&pre_check("pc-1", json!([])));
+        handler.started.notified().await;
+        hub.disconnect();
+
+        tokio::time::timeout(Duration::from_secs(1), runner)
+            .await
+            .expect("handler should stop early")
+            .unwrap()
+            .unwrap();
+        assert!(handler.cancelled.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_on_idle_when_hub_goes_silent() {
+        #[derive(Default)]
+        struct IdleHandler {
+            idle: std::sync::Mutex<Vec<Duration>>,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for Arc<IdleHandler> {
+            async fn on_idle(&self, since: Duration) {
+                self.idle.lock().unwrap().push(since);
+            }
+        }
+
+        let config = SentinelConfig::new("MockSentinel", 3)
+            .without_auto_reconnect()
+            .with_idle_timeout(Duration::from_millis(50));
+        let handler = Arc::new(IdleHandler::default());
+        let (sentinel, mut hub) = mock_sentinel(config, Arc::clone(&handler));
+        let idle_count = || handler.idle.lock().unwrap().len();
+
+        let runner = spawn_run(&sentinel);
+
+        // Messages arriving more often than the timeout keep it from firing.
+        for id in ["pc-1", "pc-2", "pc-3"] {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            hub.send_json(&pre_check(id, json!([])));
+            hub.recv_json().await.unwrap();
+        }
+        assert_eq!(idle_count(), 0);
+
+        tokio::time::sleep(Duration::from_millis(180)).await;
+        disconnect_and_join(&hub, runner).await;
+
+        let idle = handler.idle.lock().unwrap().clone();
+        assert!(idle.len() >= 2, "{:?}", idle);
+        assert!(idle[0] >= Duration::from_millis(50));
+        assert!(idle[1] > idle[0]);
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_client_config() {
+        use futures_util::StreamExt;
+        use tokio::net::TcpListener;
+
+        // Serves one session that ends after registration, then refuses
+        // every reconnect by dropping the socket before the upgrade.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        tokio::spawn({
+            let accepted = Arc::clone(&accepted);
+            async move {
+                while let Ok((tcp, _)) = listener.accept().await {
+                    if accepted.fetch_add(1, Ordering::SeqCst) == 0 {
+                        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                        ws.next().await;
+                        let _ = ws.close(None).await;
+                    }
+                }
+            }
+        });
+
+        let client_config = ClientConfig {
+            reconnect_delay_ms: 5,
+            max_reconnect_delay_ms: 10,
+            max_reconnect_attempts: 2,
+            ..ClientConfig::new(url)
+        };
+        let mut sentinel = Sentinel::new(SentinelConfig::new("S", 5), BlockingHandler);
+        sentinel.connect_with(client_config).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), sentinel.run())
+            .await
+            .expect("reconnect attempts should be bounded")
+            .unwrap();
+        assert_eq!(accepted.load(Ordering::SeqCst), 1 + 2);
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry_until_hub_is_up() {
+        use futures_util::StreamExt;
+        use std::net::{SocketAddr, TcpListener};
+
+        // Starts the Hub once the second attempt has failed.
+        #[derive(Debug)]
+        struct StartHubAfter {
+            addr: SocketAddr,
+            delays: Arc<AtomicUsize>,
+        }
+
+        impl BackoffStrategy for StartHubAfter {
+            fn next_delay(&mut self, attempt: u32) -> Duration {
+                self.delays.fetch_add(1, Ordering::SeqCst);
+                if attempt == 2 {
+                    let listener = TcpListener::bind(self.addr).unwrap();
+                    listener.set_nonblocking(true).unwrap();
+                    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                    tokio::spawn(async move {
+                        let (tcp, _) = listener.accept().await.unwrap();
+                        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                        while ws.next().await.is_some() {}
+                    });
+                }
+                Duration::from_millis(1)
+            }
+        }
+
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let delays = Arc::new(AtomicUsize::new(0));
+        let backoff = StartHubAfter {
+            addr,
+            delays: Arc::clone(&delays),
+        };
+
+        let mut sentinel = Sentinel::new(SentinelConfig::new("S", 5), BlockingHandler);
+        sentinel
+            .connect_with_retry(&format!("ws://{}", addr), 5, backoff)
+            .await
+            .unwrap();
+        assert_eq!(delays.load(Ordering::SeqCst), 2);
+        assert!(sentinel.health().await.connected);
+
+        // Every attempt failing returns the last error.
+        let mut sentinel = Sentinel::new(SentinelConfig::new("S", 5), BlockingHandler);
+        let dead = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let err = sentinel
+            .connect_with_retry(
+                &format!("ws://{}", dead),
+                2,
+                crate::backoff::ConstantBackoff::new(Duration::from_millis(1)),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Connection(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_action_while_running_over_websocket() {
+        use crate::transport::Message;
+        use futures_util::StreamExt;
+        use tokio::net::TcpListener;
+
+        // A Hub that records every text frame but never sends anything, so
+        // run() stays blocked waiting for a message.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (frames_tx, mut frames) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            while let Some(Ok(frame)) = ws.next().await {
+                if let Message::Text(text) = frame {
+                    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    let _ = frames_tx.send(value["method"].as_str().unwrap().to_string());
+                }
+            }
+        });
+
+        let config = SentinelConfig::new("MockSentinel", 3).without_auto_reconnect();
+        let mut sentinel = Sentinel::new(config, BlockingHandler);
+        sentinel.connect(&url).await.unwrap();
+        let sentinel = Arc::new(sentinel);
+        assert_eq!(frames.recv().await.unwrap(), "starlight.registration");
+
+        let runner = spawn_run(&sentinel);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        tokio::time::timeout(Duration::from_secs(1), async {
+            sentinel.hijack("Dismiss popup").await.unwrap();
+            sentinel
+                .action(ActionCommand::Click, ".dismiss", None)
+                .await
+                .unwrap();
+        })
+        .await
+        .expect("sends must not wait for run() to receive");
+        assert_eq!(frames.recv().await.unwrap(), "starlight.hijack");
+        assert_eq!(frames.recv().await.unwrap(), "starlight.action");
+
+        sentinel.stop().await;
+        tokio::time::timeout(Duration::from_secs(1), runner)
+            .await
+            .expect("run() should end after stop()")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_coalescing_leaves_decisions_alone() {
+        use crate::ratelimit::RateLimitAction;
+        use crate::transport::Message;
+        use futures_util::{SinkExt, StreamExt};
+        use tokio::net::TcpListener;
+
+        // A Hub that sends two pre-checks after registration and records
+        // every text frame.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (frames_tx, mut frames) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            while let Some(Ok(frame)) = ws.next().await {
+                let Message::Text(text) = frame else {
+                    continue;
+                };
+                let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                if value["method"] == "starlight.registration" {
+                    for id in ["pc-1", "pc-2"] {
+                        let pre_check = pre_check(id, json!([{ "selector": ".modal" }]));
+                        ws.send(Message::Text(pre_check.to_string())).await.unwrap();
+                    }
+                }
+                let _ = frames_tx.send(value);
+            }
+        });
+
+        let config = SentinelConfig::new("MockSentinel", 3).without_auto_reconnect();
+        let mut sentinel = Sentinel::new(config, BlockingHandler);
+        let client_config = ClientConfig::new(&url)
+            .with_hijack_coalescing(Duration::from_secs(60))
+            .with_rate_limit_action(RateLimitAction::Error);
+        sentinel.connect_with(client_config).await.unwrap();
+        let sentinel = Arc::new(sentinel);
+        let runner = spawn_run(&sentinel);
+
+        assert_eq!(
+            frames.recv().await.unwrap()["method"],
+            "starlight.registration"
+        );
+        for _ in 0..2 {
+            let decision = tokio::time::timeout(Duration::from_secs(1), frames.recv())
+                .await
+                .expect("both decisions should reach the Hub")
+                .unwrap();
+            assert_eq!(decision["method"], "starlight.hijack");
+            assert_eq!(decision["params"]["reason"], "1 blocking");
+        }
+        assert_eq!(sentinel.metrics().hijacks, 2);
+
+        // Hijacks the Sentinel sends itself are still coalesced.
+        sentinel.hijack("manual").await.unwrap();
+        let err = sentinel.hijack("manual").await.unwrap_err();
+        assert!(matches!(err, Error::RateLimited(_)), "{:?}", err);
+
+        sentinel.stop().await;
+        runner.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_acknowledged_actions() {
+        let config =
+            SentinelConfig::new("MockSentinel", 3).with_request_timeout(Duration::from_millis(200));
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+        let runner = spawn_run(&sentinel);
+
+        let reply = |hub: &MockHub, id: &serde_json::Value, body: serde_json::Value| {
+            let mut response = json!({ "jsonrpc": "2.0", "id": id });
+            response
+                .as_object_mut()
+                .unwrap()
+                .extend(body.as_object().unwrap().clone());
+            hub.send_json(&response);
+        };
+
+        // Accepted
+        let call = tokio::spawn({
+            let sentinel = Arc::clone(&sentinel);
+            async move {
+                sentinel
+                    .action_and_wait(ActionCommand::Click, "#ok", None)
+                    .await
+            }
+        });
+        let request = hub.recv_json().await.unwrap();
+        assert_eq!(request["method"], "starlight.action");
+        reply(
+            &hub,
+            &request["id"],
+            json!({ "result": { "accepted": true } }),
+        );
+        let ack = call.await.unwrap().unwrap();
+        assert!(ack.accepted);
+        assert_eq!(ack.error, None);
+
+        // Rejected in the result, and as a JSON-RPC error
+        for body in [
+            json!({ "result": { "accepted": false, "error": "selector not found" } }),
+            json!({ "error": { "code": -32000, "message": "selector not found" } }),
+        ] {
+            let call = tokio::spawn({
+                let sentinel = Arc::clone(&sentinel);
+                async move {
+                    sentinel
+                        .action_and_wait(ActionCommand::Click, "#missing", None)
+                        .await
+                }
+            });
+            let request = hub.recv_json().await.unwrap();
+            reply(&hub, &request["id"], body);
+            let ack = call.await.unwrap().unwrap();
+            assert!(!ack.accepted);
+            assert_eq!(ack.error.as_deref(), Some("selector not found"));
+        }
+
+        // A sequence stops at the first rejection
+        let call = tokio::spawn({
+            let sentinel = Arc::clone(&sentinel);
+            async move {
+                sentinel
+                    .actions_and_wait(vec![
+                        (ActionCommand::Click, "#a".to_string(), None),
+                        (ActionCommand::Click, "#b".to_string(), None),
+                        (ActionCommand::Click, "#c".to_string(), None),
+                    ])
+                    .await
+            }
+        });
+        for (selector, accepted) in [("#a", true), ("#b", false)] {
+            let request = hub.recv_json().await.unwrap();
+            assert_eq!(request["params"]["selector"], selector);
+            reply(
+                &hub,
+                &request["id"],
+                json!({ "result": { "accepted": accepted } }),
+            );
+        }
+        let acks = call.await.unwrap().unwrap();
+        assert_eq!(
+            acks.iter().map(|ack| ack.accepted).collect::<Vec<_>>(),
+            [true, false]
+        );
+        assert!(hub.try_recv().is_none());
+
+        // No reply at all
+        let err = sentinel.resume_and_wait(true).await.unwrap_err();
+        assert!(matches!(err, Error::Timeout));
+
+        close_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_events_across_reconnect() {
+        let config = SentinelConfig::new("MockSentinel", 3);
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+        let mut events = sentinel.events();
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&pre_check("pc-1", json!([])));
+        hub.recv_json().await.unwrap();
+        hub.disconnect();
+        let registration = hub.recv_json().await.unwrap();
+        assert_eq!(registration["method"], "starlight.registration");
+        close_and_join(&hub, runner).await;
+
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            seen.push(event);
+        }
+        assert_eq!(
+            seen,
+            vec![
+                SentinelEvent::MessageReceived {
+                    method: "starlight.pre_check".into()
+                },
+                SentinelEvent::Disconnected,
+                SentinelEvent::Reconnecting,
+                SentinelEvent::Connected,
+                SentinelEvent::HandshakeComplete,
+                SentinelEvent::Disconnected,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connected_for() {
+        let config = SentinelConfig::new("MockSentinel", 3);
+        let (sentinel, _hub) = mock_sentinel(config, BlockingHandler);
+        assert_eq!(sentinel.connected_for(), None);
+
+        sentinel.handshake().await.unwrap();
+        let first = sentinel.connected_for().unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let later = sentinel.connected_for().unwrap();
+        assert!(later >= first + Duration::from_millis(20));
+        assert!(sentinel.health().await.connected_for_ms.unwrap() >= 20);
+
+        sentinel.stop().await;
+        assert_eq!(sentinel.connected_for(), None);
+    }
+
+    #[tokio::test]
+    async fn test_health_across_disconnect() {
+        let config = SentinelConfig::new("MockSentinel", 3);
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        let health = sentinel.health().await;
+        assert!(health.connected);
+        assert!(!health.running);
+        assert_eq!(health.last_message_ms_ago, None);
+        assert_eq!(health.reconnects, 0);
+        assert_eq!(health.hub_url, None);
+        assert_eq!(health.connected_for_ms, None);
+
+        let runner = spawn_run(&sentinel);
+        hub.send_json(&pre_check("pc-1", json!([])));
+        hub.recv_json().await.unwrap();
+
+        let health = sentinel.health().await;
+        assert!(health.is_healthy());
+        assert!(health.last_message_ms_ago.is_some());
+
+        // Reconnects and re-registers
+        hub.disconnect();
+        hub.recv_json().await.unwrap();
+        let health = sentinel.health().await;
+        assert!(health.is_healthy());
+        assert_eq!(health.reconnects, 1);
+
+        close_and_join(&hub, runner).await;
+        let health = sentinel.health().await;
+        assert!(!health.connected);
+        assert!(!health.running);
+        assert_eq!(health.connected_for_ms, None);
+
+        let json = serde_json::to_value(&health).unwrap();
+        assert_eq!(json["reconnects"], 1);
+        assert_eq!(json["running"], false);
+    }
+
+    #[tokio::test]
+    async fn test_send_error_response() {
+        let config = SentinelConfig::new("MockSentinel", 3);
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        sentinel
+            .send_error(7, JsonRpcError::method_not_found())
+            .await
+            .unwrap();
+        let sent = hub.recv_json().await.unwrap();
+        assert_eq!(sent["id"], 7);
+        assert_eq!(sent["error"]["code"], -32601);
+        assert!(sent.get("result").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_send_intent() {
+        let config = SentinelConfig::new("MockSentinel", 3);
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        let intent = IntentParams::command("click", ".modal .close")
+            .with_context("expect", json!("navigation"));
+        sentinel.send_intent(intent).await.unwrap();
+
+        let sent = hub.recv_json().await.unwrap();
+        assert_eq!(sent["method"], "starlight.intent");
+        assert!(sent.get("id").is_none());
+        assert_eq!(
+            sent["params"],
+            json!({
+                "cmd": "click",
+                "selector": ".modal .close",
+                "context": { "expect": "navigation" }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pre_checks_over_in_flight_limit_wait() {
+        #[derive(Default)]
+        struct Held {
+            release: tokio::sync::Notify,
+            calls: AtomicUsize,
+        }
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for Held {
+            async fn on_pre_check(
+                &self,
+                _params: PreCheckParams,
+                _cancel: CancelToken,
+            ) -> PreCheckResponse {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                self.release.notified().await;
+                PreCheckResponse::Clear
+            }
+        }
+
+        let config = SentinelConfig::new("S", 5)
+            .without_auto_reconnect()
+            .with_max_concurrent_handlers(8)
+            .with_max_in_flight_pre_checks(2, Duration::from_millis(300));
+        let (sentinel, mut hub) = mock_sentinel(config, Held::default());
+        let runner = spawn_run(&sentinel);
+
+        for i in 0..5 {
+            hub.send_json(&pre_check(&format!("pc-{}", i), json!([])));
+        }
+
+        // The three over the limit are turned away without reaching the handler.
+        for _ in 0..3 {
+            let wait = hub.recv_json().await.unwrap();
+            assert_eq!(wait["method"], "starlight.wait");
+            assert_eq!(wait["params"]["retryAfterMs"], 300);
+        }
+        assert_eq!(sentinel.handler.calls.load(Ordering::SeqCst), 2);
+
+        sentinel.handler.release.notify_waiters();
+        for _ in 0..2 {
+            assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.clear");
+        }
+
+        // Capacity is freed once the responses are sent.
+        hub.send_json(&pre_check("pc-5", json!([])));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sentinel.handler.calls.load(Ordering::SeqCst) < 3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("handler was not called again");
+        sentinel.handler.release.notify_waiters();
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.clear");
+
+        disconnect_and_join(&hub, runner).await;
+    }
+
+    #[tokio::test]
+    async fn test_malformed_params_reported_and_skipped() {
+        #[derive(Default)]
+        struct Errors(StdMutex<Vec<String>>);
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for Errors {
+            async fn on_pre_check(
+                &self,
+                _params: PreCheckParams,
+                _cancel: CancelToken,
+            ) -> PreCheckResponse {
+                PreCheckResponse::Clear
+            }
+
+            async fn on_error(&self, error: &Error) {
+                assert_eq!(error.code(), Some(-32602));
+                self.0.lock().unwrap().push(error.to_string());
+            }
+        }
+
+        let config = SentinelConfig::new("S", 5).without_auto_reconnect();
+        let (sentinel, mut hub) = mock_sentinel(config, Errors::default());
+        let runner = spawn_run(&sentinel);
+
+        for (method, params) in [
+            ("starlight.pre_check", json!(null)),
+            ("starlight.pre_check", json!({ "command": ["click"] })),
+            ("starlight.entropy", json!(null)),
+            (
+                "starlight.entropy",
+                json!({ "url": "https://example.com", "mutations": "many" }),
+            ),
+        ] {
+            hub.send_json(
+                &json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": "bad" }),
+            );
+        }
+
+        // The loop carries on with the next, valid message.
+        hub.send_json(&pre_check("pc-ok", json!([])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.clear");
+        disconnect_and_join(&hub, runner).await;
+
+        let errors = sentinel.handler.0.lock().unwrap().clone();
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors[0].contains("starlight.pre_check: Invalid params: invalid type: null"));
+        assert!(errors[1].contains("Invalid params.command: invalid type: sequence"));
+        assert!(errors[2].contains("starlight.entropy: Invalid params: invalid type: null"));
+        assert!(errors[3].contains("Invalid params.mutations: invalid type: string"));
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        #[derive(Clone, Default)]
+        struct Calls(Arc<StdMutex<Vec<String>>>);
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for Calls {
+            async fn on_pre_check(
+                &self,
+                params: PreCheckParams,
+                cancel: CancelToken,
+            ) -> PreCheckResponse {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("pre_check {}", params.command));
+                BlockingHandler.on_pre_check(params, cancel).await
+            }
+
+            async fn on_entropy(&self, params: EntropyParams) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("entropy {}", params.url));
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!("starlight-record-{}.jsonl", Uuid::new_v4()));
+        let config = SentinelConfig::new("S", 5)
+            .without_auto_reconnect()
+            .with_record_to(&path);
+        let recorded = Calls::default();
+        let (sentinel, mut hub) = mock_sentinel(config, recorded.clone());
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&pre_check("pc-1", json!([{ "selector": ".modal" }])));
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "method": "starlight.entropy",
+            "params": { "url": "https://example.com" },
+        }));
+        hub.send_json(&pre_check("pc-2", json!([])));
+        hub.recv_json().await.unwrap();
+        hub.recv_json().await.unwrap();
+        disconnect_and_join(&hub, runner).await;
+
+        let recording = crate::record::read_recording(&path).await.unwrap();
+        let directions: Vec<_> = recording.iter().map(|r| r.direction).collect();
+        use crate::record::Direction::{Inbound, Outbound};
+        assert_eq!(
+            directions,
+            vec![Inbound, Outbound, Inbound, Inbound, Outbound]
+        );
+        let outbound: Vec<_> = recording
+            .iter()
+            .filter(|r| r.direction == Outbound)
+            .map(|r| r.frame.clone())
+            .collect();
+
+        let replayed = Calls::default();
+        let sent = crate::record::replay(&path, replayed.clone())
+            .await
+            .unwrap();
+        assert_eq!(sent, outbound);
+        assert_eq!(*replayed.0.lock().unwrap(), *recorded.0.lock().unwrap());
+        assert_eq!(replayed.0.lock().unwrap().len(), 3);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_pre_checks_stream_answers_instead_of_handler() {
+        use futures_util::StreamExt;
+
+        let config = SentinelConfig::new("Test", 5).without_auto_reconnect();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+        let mut pre_checks = Box::pin(sentinel.pre_checks());
+        let runner = Arc::clone(&sentinel);
+        let run = tokio::spawn(async move { runner.run().await });
+
+        hub.send_json(&pre_check("1", json!([])));
+        let request = pre_checks.next().await.unwrap();
+        assert_eq!(request.params.command, "click");
+        request.respond(PreCheckResponse::hijack("from stream"));
+
+        let sent = hub.recv_json().await.unwrap();
+        assert_eq!(sent["method"], "starlight.hijack");
+        assert_eq!(sent["params"]["reason"], "from stream");
+
+        // Dropping the stream hands pre-checks back to the handler.
+        drop(pre_checks);
+        hub.send_json(&pre_check("2", json!([])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.clear");
+
+        hub.disconnect();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_deferred_pre_check_answered_from_task() {
+        #[derive(Default)]
+        struct ExternalLookup(StdMutex<Vec<String>>);
+
+        #[async_trait::async_trait]
+        impl SentinelHandler for ExternalLookup {
+            async fn on_pre_check_deferred(
+                &self,
+                id: String,
+                params: PreCheckParams,
+                responder: PreCheckResponder,
+            ) {
+                self.0.lock().unwrap().push(id);
+                if params.has_blocking() {
+                    // Forgotten: the fallback is sent instead.
+                    return;
+                }
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    responder.respond(PreCheckResponse::hijack("looked up"));
+                });
+            }
+        }
+
+        let config = SentinelConfig::new("Test", 5)
+            .without_auto_reconnect()
+            .with_pre_check_timeout(
+                Duration::from_secs(5),
+                PreCheckResponse::wait(Duration::from_millis(100)),
+            );
+        let (sentinel, mut hub) = mock_sentinel(config, ExternalLookup::default());
+        let runner = Arc::clone(&sentinel);
+        let run = tokio::spawn(async move { runner.run().await });
+
+        hub.send_json(&pre_check("pc-1", json!([])));
+        let sent = hub.recv_json().await.unwrap();
+        assert_eq!(sent["method"], "starlight.hijack");
+        assert_eq!(sent["params"]["reason"], "looked up");
+
+        hub.send_json(&pre_check("pc-2", json!([{ "selector": ".modal" }])));
+        assert_eq!(hub.recv_json().await.unwrap()["method"], "starlight.wait");
+        assert_eq!(*sentinel.handler.0.lock().unwrap(), vec!["pc-1", "pc-2"]);
+
+        hub.disconnect();
+        run.await.unwrap().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_until_signal_stops_on_sigterm() {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        // Keeps SIGTERM from killing the test process before the Sentinel
+        // starts listening for it.
+        let _guard = signal(SignalKind::terminate()).unwrap();
+
+        let config = SentinelConfig::new("S", 5).with_shutdown_signals([ShutdownSignal::Terminate]);
+        let (sentinel, _hub) = mock_sentinel(config, BlockingHandler);
+        let runner = Arc::clone(&sentinel);
+        let run = tokio::spawn(async move { runner.run_until_signal().await });
+        while !sentinel.is_running().await {
+            tokio::task::yield_now().await;
+        }
+
+        let killed = std::process::Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(killed.success());
+
+        let result = tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .expect("loop should exit on SIGTERM")
+            .unwrap();
+        assert!(result.is_ok());
+        assert!(!sentinel.is_running().await);
+        assert!(!sentinel.health().await.connected);
+    }
+
+    #[tokio::test]
+    async fn test_no_reconnect_after_policy_close() {
+        let config = SentinelConfig::new("S", 5);
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        hub.close(1008, "invalid token");
+        let err = sentinel.run().await.unwrap_err();
+
+        assert_eq!(err.close_code(), Some(1008));
+        assert_eq!(sentinel.metrics().reconnects, 0);
+        assert!(hub.try_recv().is_none(), "no re-registration expected");
+        assert!(!sentinel.is_running().await);
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_message_span_carries_request_id() {
+        let config = SentinelConfig::new("S", 5).without_auto_reconnect();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&pre_check("pc-42", json!([{ "selector": ".modal" }])));
+        hub.recv_json().await.unwrap();
+        disconnect_and_join(&hub, runner).await;
+
+        assert!(logs_contain("message{id=pc-42 method=starlight.pre_check"));
+        assert!(logs_contain("response=\"hijack\""));
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_log_decisions() {
+        let config = SentinelConfig::new("S", 5)
+            .without_auto_reconnect()
+            .with_log_decisions();
+        let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+        let runner = spawn_run(&sentinel);
+
+        hub.send_json(&json!({
+            "jsonrpc": "2.0",
+            "method": "starlight.pre_check",
+            "params": {
+                "command": "fill",
+                "selector": "#email",
+                "blocking": [{ "selector": ".modal" }, { "selector": ".overlay" }]
+            },
+            "id": "pc-7"
+        }));
+        hub.recv_json().await.unwrap();
+        disconnect_and_join(&hub, runner).await;
+
+        assert!(logs_contain(
+            "Pre-check decision command=fill selector=\"#email\" blocking=2 decision=\"hijack\""
+        ));
+    }
+
+    #[cfg(not(feature = "jwt"))]
+    mod without_jwt {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_registers_without_auth() {
+            let config = SentinelConfig::new("MockSentinel", 3)
+                .with_selectors(vec![".modal"])
+                .without_auto_reconnect();
+            let (sentinel, mut hub) = mock_sentinel(config, BlockingHandler);
+
+            sentinel.handshake().await.unwrap();
+            let registration = hub.recv_json().await.unwrap();
+            assert_eq!(registration["method"], "starlight.registration");
+            assert_eq!(registration["params"]["layer"], "MockSentinel");
+            assert!(registration["params"].get("auth_token").is_none());
+
+            hub.send_json(&json!({
+                "jsonrpc": "2.0",
+                "id": registration["id"],
+                "result": { "success": true, "protocol_version": "1.0.0" }
+            }));
+            let runner = spawn_run(&sentinel);
+            hub.send_json(&pre_check("pc-1", json!([{ "selector": ".modal" }])));
+            let response = hub.recv_json().await.unwrap();
+            assert_eq!(response["method"], "starlight.hijack");
+            assert!(sentinel.hub_info().is_some());
+
+            disconnect_and_join(&hub, runner).await;
+        }
+    }
 }
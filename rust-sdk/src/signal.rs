@@ -0,0 +1,83 @@
+//! OS signals that stop a Sentinel started with
+//! [`Sentinel::run_until_signal`](crate::Sentinel::run_until_signal).
+
+use std::future::Future;
+use std::pin::Pin;
+
+use futures_util::future::select_all;
+
+use crate::error::{Error, Result};
+
+/// A signal that asks a Sentinel to shut down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ShutdownSignal {
+    /// Ctrl-C (SIGINT on unix, CTRL_C_EVENT on Windows)
+    CtrlC,
+
+    /// SIGTERM, as sent by `kill`, systemd and container runtimes
+    #[cfg(unix)]
+    Terminate,
+
+    /// SIGHUP
+    #[cfg(unix)]
+    Hangup,
+
+    /// SIGQUIT
+    #[cfg(unix)]
+    Quit,
+}
+
+impl ShutdownSignal {
+    /// Ctrl-C, plus SIGTERM on unix.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::CtrlC,
+            #[cfg(unix)]
+            Self::Terminate,
+        ]
+    }
+}
+
+type Wait = Pin<Box<dyn Future<Output = Result<ShutdownSignal>> + Send>>;
+
+fn listen_error(signal: ShutdownSignal, e: std::io::Error) -> Error {
+    Error::InvalidConfig(format!("Cannot listen for {:?}: {}", signal, e))
+}
+
+/// Wait until one of `signals` arrives. Never returns if `signals` is empty.
+pub(crate) async fn wait_for(signals: &[ShutdownSignal]) -> Result<ShutdownSignal> {
+    let mut waits: Vec<Wait> = Vec::with_capacity(signals.len());
+    for &signal in signals {
+        let wait: Wait = match signal {
+            ShutdownSignal::CtrlC => Box::pin(async move {
+                tokio::signal::ctrl_c()
+                    .await
+                    .map(|()| signal)
+                    .map_err(|e| listen_error(signal, e))
+            }),
+            #[cfg(unix)]
+            unix => {
+                use tokio::signal::unix::{signal as listen, SignalKind};
+
+                let kind = match unix {
+                    ShutdownSignal::Hangup => SignalKind::hangup(),
+                    ShutdownSignal::Quit => SignalKind::quit(),
+                    _ => SignalKind::terminate(),
+                };
+                // Registered now, so nothing sent from here on is missed.
+                let mut stream = listen(kind).map_err(|e| listen_error(signal, e))?;
+                Box::pin(async move {
+                    stream.recv().await;
+                    Ok(signal)
+                })
+            }
+        };
+        waits.push(wait);
+    }
+
+    if waits.is_empty() {
+        return std::future::pending().await;
+    }
+    select_all(waits).await.0
+}
@@ -0,0 +1,92 @@
+//! Answering pre-checks outside `on_pre_check`: as a stream instead of
+//! through a handler, or later through a [`PreCheckResponder`].
+
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::cancel::CancelToken;
+use crate::messages::{PreCheckParams, PreCheckResponse};
+
+/// A pre-check waiting for a decision, from [`Sentinel::pre_checks`](crate::Sentinel::pre_checks).
+///
+/// Answer it with [`respond`](Self::respond). Dropping it without
+/// responding sends `pre_check_fallback` instead.
+#[derive(Debug)]
+pub struct PreCheckRequest {
+    /// The pre-check sent by the Hub
+    pub params: PreCheckParams,
+
+    /// Cancelled if the connection is lost before responding
+    pub cancel: CancelToken,
+
+    respond: oneshot::Sender<PreCheckResponse>,
+}
+
+impl PreCheckRequest {
+    /// Send the decision for this pre-check to the Hub.
+    pub fn respond(self, response: PreCheckResponse) {
+        // The dispatcher only stops waiting once the run loop is gone.
+        let _ = self.respond.send(response);
+    }
+}
+
+/// Sends the decision for a pre-check handed to
+/// [`SentinelHandler::on_pre_check_deferred`](crate::SentinelHandler::on_pre_check_deferred).
+///
+/// Can be moved into another task and answered there. Dropping it without
+/// responding sends `pre_check_fallback` instead.
+#[derive(Debug)]
+pub struct PreCheckResponder {
+    cancel: CancelToken,
+    respond: oneshot::Sender<PreCheckResponse>,
+}
+
+impl PreCheckResponder {
+    /// A responder and the receiver its decision arrives on.
+    pub(crate) fn new(cancel: CancelToken) -> (Self, oneshot::Receiver<PreCheckResponse>) {
+        let (respond, response) = oneshot::channel();
+        (Self { cancel, respond }, response)
+    }
+
+    /// Cancelled if the connection is lost before responding.
+    pub fn cancel(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// Send the decision for this pre-check to the Hub.
+    pub fn respond(self, response: PreCheckResponse) {
+        // The dispatcher only stops waiting once the run loop is gone.
+        let _ = self.respond.send(response);
+    }
+}
+
+/// Where pre-checks go when someone is consuming them as a stream.
+pub(crate) type PreCheckSubscriber = Arc<Mutex<Option<mpsc::UnboundedSender<PreCheckRequest>>>>;
+
+/// Hand a pre-check to the stream and wait for its decision.
+///
+/// Gives the request back if nobody is listening. `Ok(None)` means it was
+/// dropped without a response.
+#[allow(clippy::result_large_err)]
+pub(crate) async fn ask(
+    subscriber: &PreCheckSubscriber,
+    params: PreCheckParams,
+    cancel: CancelToken,
+) -> Result<Option<PreCheckResponse>, (PreCheckParams, CancelToken)> {
+    let sender = subscriber.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let Some(sender) = sender else {
+        return Err((params, cancel));
+    };
+
+    let (respond, response) = oneshot::channel();
+    let request = PreCheckRequest {
+        params,
+        cancel,
+        respond,
+    };
+    if let Err(mpsc::error::SendError(request)) = sender.send(request) {
+        return Err((request.params, request.cancel));
+    }
+    Ok(response.await.ok())
+}
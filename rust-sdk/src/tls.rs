@@ -0,0 +1,334 @@
+//! TLS configuration for `wss://` Hub connections.
+//!
+//! Requires the `tls` feature. Supports custom root certificates and, for
+//! Hubs that require mutual TLS, presenting a client certificate.
+
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+use crate::error::{Error, Result};
+
+/// TLS settings for connecting to a Hub over `wss://`.
+///
+/// By default the Mozilla root store (via `webpki-roots`) is trusted. Extra
+/// roots can be added for Hubs using a private CA or a self-signed cert.
+///
+/// # Example
+/// ```no_run
+/// use starlight::client::ClientConfig;
+/// use starlight::tls::TlsConfig;
+///
+/// let pem = std::fs::read("staging-ca.pem").unwrap();
+/// let tls = TlsConfig::new().with_root_cert_pem(&pem).unwrap();
+/// let config = ClientConfig::new("wss://hub.staging:8443").with_tls(tls);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    extra_roots: Vec<CertificateDer<'static>>,
+    use_default_roots: bool,
+    accept_invalid_certs: bool,
+    client_identity: Option<Arc<ClientIdentity>>,
+    custom: Option<Arc<rustls::ClientConfig>>,
+}
+
+/// Client certificate chain and matching private key for mutual TLS.
+#[derive(Debug)]
+struct ClientIdentity {
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            extra_roots: Vec::new(),
+            use_default_roots: true,
+            accept_invalid_certs: false,
+            client_identity: None,
+            custom: None,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Create a TLS config trusting the default root store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a fully custom rustls config; all other settings are ignored.
+    pub fn from_rustls(config: Arc<rustls::ClientConfig>) -> Self {
+        Self {
+            custom: Some(config),
+            ..Default::default()
+        }
+    }
+
+    /// Trust every certificate in a PEM bundle as an additional root.
+    #[allow(clippy::result_large_err)]
+    pub fn with_root_cert_pem(mut self, pem: &[u8]) -> Result<Self> {
+        let certs = rustls_pemfile::certs(&mut &pem[..])
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::InvalidConfig(format!("Invalid root certificate PEM: {}", e)))?;
+
+        if certs.is_empty() {
+            return Err(Error::InvalidConfig(
+                "No certificates found in root certificate PEM".to_string(),
+            ));
+        }
+
+        self.extra_roots.extend(certs);
+        Ok(self)
+    }
+
+    /// Trust a DER-encoded certificate as an additional root.
+    pub fn with_root_cert_der(mut self, der: Vec<u8>) -> Self {
+        self.extra_roots.push(CertificateDer::from(der));
+        self
+    }
+
+    /// Trust only the explicitly added roots, not the default root store.
+    pub fn without_default_roots(mut self) -> Self {
+        self.use_default_roots = false;
+        self
+    }
+
+    /// Present a client certificate during the handshake (mutual TLS).
+    ///
+    /// `cert_chain_pem` holds the leaf certificate first, followed by any
+    /// intermediates. `key_pem` holds the matching PKCS#8, PKCS#1 or SEC1
+    /// private key.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidConfig`] if either PEM is malformed or the key
+    /// does not belong to the leaf certificate.
+    #[allow(clippy::result_large_err)]
+    pub fn with_client_cert_pem(self, cert_chain_pem: &[u8], key_pem: &[u8]) -> Result<Self> {
+        let cert_chain = rustls_pemfile::certs(&mut &cert_chain_pem[..])
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::InvalidConfig(format!("Invalid client certificate PEM: {}", e)))?;
+
+        let key = rustls_pemfile::private_key(&mut &key_pem[..])
+            .map_err(|e| Error::InvalidConfig(format!("Invalid client key PEM: {}", e)))?
+            .ok_or_else(|| {
+                Error::InvalidConfig("No private key found in client key PEM".to_string())
+            })?;
+
+        self.with_client_identity(cert_chain, key)
+    }
+
+    /// Present a DER-encoded client certificate chain and private key.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidConfig`] if the key is not a recognized DER
+    /// private key or does not belong to the leaf certificate.
+    #[allow(clippy::result_large_err)]
+    pub fn with_client_cert_der(self, cert_chain: Vec<Vec<u8>>, key: Vec<u8>) -> Result<Self> {
+        let cert_chain = cert_chain.into_iter().map(CertificateDer::from).collect();
+        let key = PrivateKeyDer::try_from(key)
+            .map_err(|e| Error::InvalidConfig(format!("Invalid client key DER: {}", e)))?;
+
+        self.with_client_identity(cert_chain, key)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn with_client_identity(
+        mut self,
+        cert_chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<Self> {
+        let leaf = cert_chain
+            .first()
+            .ok_or_else(|| Error::InvalidConfig("Client certificate chain is empty".to_string()))?;
+        check_key_matches(leaf, &key)?;
+
+        self.client_identity = Some(Arc::new(ClientIdentity { cert_chain, key }));
+        Ok(self)
+    }
+
+    /// Disable server certificate verification.
+    ///
+    /// **Dangerous**: this accepts any certificate, including expired or
+    /// forged ones. Only use it against a local Hub during development.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Build the rustls client config.
+    #[allow(clippy::result_large_err)]
+    pub fn build(&self) -> Result<Arc<rustls::ClientConfig>> {
+        if let Some(ref custom) = self.custom {
+            return Ok(Arc::clone(custom));
+        }
+
+        let builder = if self.accept_invalid_certs {
+            rustls::ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerification))
+        } else {
+            rustls::ClientConfig::builder().with_root_certificates(self.root_store()?)
+        };
+
+        let config = match self.client_identity {
+            Some(ref identity) => builder
+                .with_client_auth_cert(identity.cert_chain.clone(), identity.key.clone_key())
+                .map_err(|e| Error::InvalidConfig(format!("Invalid client certificate: {}", e)))?,
+            None => builder.with_no_client_auth(),
+        };
+
+        Ok(Arc::new(config))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn root_store(&self) -> Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+
+        if self.use_default_roots {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+
+        for cert in &self.extra_roots {
+            roots
+                .add(cert.clone())
+                .map_err(|e| Error::InvalidConfig(format!("Invalid root certificate: {}", e)))?;
+        }
+
+        Ok(roots)
+    }
+}
+
+/// Check that `key` belongs to `leaf` by signing a probe and verifying it
+/// against the certificate's public key.
+#[allow(clippy::result_large_err)]
+fn check_key_matches(leaf: &CertificateDer<'_>, key: &PrivateKeyDer<'_>) -> Result<()> {
+    const PROBE: &[u8] = b"starlight client identity check";
+
+    let algorithms = rustls::crypto::ring::default_provider().signature_verification_algorithms;
+
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(key)
+        .map_err(|e| Error::InvalidConfig(format!("Unsupported client key: {}", e)))?;
+    let signer = signing_key
+        .choose_scheme(&algorithms.supported_schemes())
+        .ok_or_else(|| Error::InvalidConfig("Unsupported client key algorithm".to_string()))?;
+    let signature = signer
+        .sign(PROBE)
+        .map_err(|e| Error::InvalidConfig(format!("Client key cannot sign: {}", e)))?;
+
+    let cert = webpki::EndEntityCert::try_from(leaf)
+        .map_err(|e| Error::InvalidConfig(format!("Invalid client certificate: {}", e)))?;
+
+    let matches = algorithms
+        .mapping
+        .iter()
+        .filter(|(scheme, _)| *scheme == signer.scheme())
+        .flat_map(|(_, algs)| algs.iter())
+        .any(|alg| cert.verify_signature(*alg, PROBE, &signature).is_ok());
+
+    if matches {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(
+            "Client private key does not match the client certificate".to_string(),
+        ))
+    }
+}
+
+/// Certificate verifier that accepts everything.
+#[derive(Debug)]
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA_PEM: &[u8] = include_bytes!("../testdata/ca.pem");
+    const CLIENT_PEM: &[u8] = include_bytes!("../testdata/client.pem");
+    const CLIENT_KEY: &[u8] = include_bytes!("../testdata/client.key");
+    const UNRELATED_KEY: &[u8] = include_bytes!("../testdata/unrelated.key");
+
+    #[test]
+    fn test_custom_root_cert() {
+        let tls = TlsConfig::new()
+            .without_default_roots()
+            .with_root_cert_pem(CA_PEM)
+            .unwrap();
+
+        assert_eq!(tls.root_store().unwrap().len(), 1);
+        assert!(tls.build().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_root_pem() {
+        let result = TlsConfig::new().with_root_cert_pem(b"not a certificate");
+        assert!(matches!(result, Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_danger_accept_invalid_certs() {
+        let tls = TlsConfig::new().danger_accept_invalid_certs(true);
+        assert!(tls.build().is_ok());
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let tls = TlsConfig::new()
+            .with_root_cert_pem(CA_PEM)
+            .unwrap()
+            .with_client_cert_pem(CLIENT_PEM, CLIENT_KEY)
+            .unwrap();
+
+        let config = tls.build().unwrap();
+        assert!(config.client_auth_cert_resolver.has_certs());
+    }
+
+    #[test]
+    fn test_client_key_mismatch() {
+        let result = TlsConfig::new().with_client_cert_pem(CLIENT_PEM, UNRELATED_KEY);
+
+        match result {
+            Err(Error::InvalidConfig(msg)) => assert!(msg.contains("does not match")),
+            other => panic!("expected InvalidConfig, got {:?}", other.map(|_| ())),
+        }
+    }
+}
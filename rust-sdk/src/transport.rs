@@ -0,0 +1,318 @@
+//! Transport abstraction between a Sentinel and the Starlight Hub.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::backoff::BackoffStrategy;
+use crate::clock::Delay;
+use crate::error::{Error, Result};
+use crate::messages::{methods, RawMessage};
+
+pub use tokio_tungstenite::tungstenite::Message;
+
+/// A bidirectional message channel to the Hub.
+///
+/// [`WebSocketClient`](crate::WebSocketClient) is the production
+/// implementation; [`MockTransport`](crate::mock::MockTransport) lets
+/// Sentinels be exercised in-memory without a running Hub.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Send a frame to the Hub.
+    async fn send(&self, message: Message) -> Result<()>;
+
+    /// Send a frame and wait until it has been written to the connection.
+    ///
+    /// Used when resending after failures, which [`send`](Self::send) may
+    /// only report for later frames if it merely queues.
+    async fn send_confirmed(&self, message: Message) -> Result<()> {
+        self.send(message).await
+    }
+
+    /// Send a pre-check decision to the Hub.
+    ///
+    /// Unlike [`send`](Self::send), never rate-limited or coalesced: each
+    /// decision answers a pre-check the Hub is waiting on.
+    async fn send_decision(&self, message: Message) -> Result<()> {
+        self.send(message).await
+    }
+
+    /// Receive the next protocol message from the Hub.
+    ///
+    /// Returns `Ok(None)` for frames that carry no protocol message
+    /// (ping/pong and the like).
+    async fn recv(&self) -> Result<Option<RawMessage>>;
+
+    /// Re-establish the connection after it was lost.
+    async fn reconnect(&self) -> Result<()> {
+        Err(Error::NotConnected)
+    }
+
+    /// Close the connection.
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Check if the transport is connected.
+    async fn is_connected(&self) -> bool {
+        true
+    }
+
+    /// The Hub endpoint in use, if the transport has one.
+    async fn url(&self) -> Option<String> {
+        None
+    }
+
+    /// Decide whether a connection error should trigger a reconnect.
+    ///
+    /// Defaults to [`Error::is_retryable`], which treats policy and
+    /// authentication close codes as fatal.
+    fn should_reconnect(&self, error: &Error) -> bool {
+        error.is_retryable()
+    }
+}
+
+/// Logs the messages a Sentinel would send instead of sending them.
+///
+/// Registration and deregistration still go through, so a Sentinel with
+/// [`SentinelConfig::with_dry_run`](crate::SentinelConfig::with_dry_run)
+/// connects like any other. So do control frames. Everything else is
+/// only logged.
+pub(crate) struct DryRunTransport {
+    inner: Arc<dyn Transport>,
+}
+
+impl DryRunTransport {
+    pub(crate) fn new(inner: Arc<dyn Transport>) -> Self {
+        Self { inner }
+    }
+
+    /// Whether `message` goes out for real, logging it if not.
+    fn passes(message: &Message) -> bool {
+        let Message::Text(ref text) = message else {
+            return true;
+        };
+        let method = serde_json::from_str::<serde_json::Value>(text)
+            .ok()
+            .and_then(|frame| frame["method"].as_str().map(str::to_string));
+        match method.as_deref() {
+            Some(methods::REGISTRATION | methods::DEREGISTER) => true,
+            _ => {
+                tracing::info!("would send: {}", text);
+                false
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for DryRunTransport {
+    async fn send(&self, message: Message) -> Result<()> {
+        if !Self::passes(&message) {
+            return Ok(());
+        }
+        self.inner.send(message).await
+    }
+
+    async fn send_confirmed(&self, message: Message) -> Result<()> {
+        if !Self::passes(&message) {
+            return Ok(());
+        }
+        self.inner.send_confirmed(message).await
+    }
+
+    async fn send_decision(&self, message: Message) -> Result<()> {
+        if !Self::passes(&message) {
+            return Ok(());
+        }
+        self.inner.send_decision(message).await
+    }
+
+    async fn recv(&self) -> Result<Option<RawMessage>> {
+        self.inner.recv().await
+    }
+
+    async fn reconnect(&self) -> Result<()> {
+        self.inner.reconnect().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.inner.close().await
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.inner.is_connected().await
+    }
+
+    async fn url(&self) -> Option<String> {
+        self.inner.url().await
+    }
+
+    fn should_reconnect(&self, error: &Error) -> bool {
+        self.inner.should_reconnect(error)
+    }
+}
+
+/// Send `message`, trying again up to `retries` times after errors that
+/// [`Error::is_retryable`] accepts, or that only say the connection is gone
+/// (`NotConnected`, `ChannelError`) while the transport reports itself
+/// disconnected. Other errors are returned at once.
+///
+/// Waits `backoff.next_delay(attempt)` before each retry, with `delay` or
+/// tokio's timer. With `reconnect`, a transport that reports itself
+/// disconnected is reconnected before the message is sent again.
+pub(crate) async fn send_with_retry(
+    transport: &dyn Transport,
+    message: Message,
+    retries: u32,
+    backoff: &mut dyn BackoffStrategy,
+    reconnect: bool,
+    delay: Option<&dyn Delay>,
+) -> Result<()> {
+    backoff.reset();
+    let mut attempt = 0;
+    loop {
+        let error = match transport.send_confirmed(message.clone()).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= retries => return Err(e),
+            Err(e) if e.is_retryable() || lost(transport, &e).await => e,
+            Err(e) => return Err(e),
+        };
+
+        attempt += 1;
+        let wait = backoff.next_delay(attempt);
+        warn!(
+            "Send failed ({}), retry {} of {} in {:?}",
+            error, attempt, retries, wait
+        );
+        match delay {
+            Some(delay) => delay.sleep(wait).await,
+            None => tokio::time::sleep(wait).await,
+        }
+        if reconnect && !transport.is_connected().await {
+            transport.reconnect().await?;
+        }
+    }
+}
+
+/// Whether `error` only says the connection is gone, e.g. the writer
+/// ended after a failed write, which a reconnect fixes.
+async fn lost(transport: &dyn Transport, error: &Error) -> bool {
+    matches!(error, Error::NotConnected | Error::ChannelError) && !transport.is_connected().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backoff::ConstantBackoff;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Error as WsError;
+
+    /// Fails the first `failures` writes with `error`, dropping the connection.
+    struct FlakyTransport {
+        failures: AtomicU32,
+        error: fn() -> Error,
+        connected: AtomicBool,
+        reconnects: AtomicU32,
+        sent: Mutex<Vec<Message>>,
+    }
+
+    impl FlakyTransport {
+        fn new(failures: u32, error: fn() -> Error) -> Self {
+            Self {
+                failures: AtomicU32::new(failures),
+                error,
+                connected: AtomicBool::new(true),
+                reconnects: AtomicU32::new(0),
+                sent: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Transport for FlakyTransport {
+        async fn send(&self, message: Message) -> Result<()> {
+            let failing = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if failing {
+                self.connected.store(false, Ordering::SeqCst);
+                return Err((self.error)());
+            }
+            self.sent.lock().unwrap().push(message);
+            Ok(())
+        }
+
+        async fn recv(&self) -> Result<Option<RawMessage>> {
+            Ok(None)
+        }
+
+        async fn reconnect(&self) -> Result<()> {
+            self.reconnects.fetch_add(1, Ordering::SeqCst);
+            self.connected.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn is_connected(&self) -> bool {
+            self.connected.load(Ordering::SeqCst)
+        }
+    }
+
+    fn write_error() -> Error {
+        Error::Connection(WsError::ConnectionClosed)
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_recovers_from_failed_write() {
+        let transport = FlakyTransport::new(1, write_error);
+        let mut backoff = ConstantBackoff::new(Duration::from_millis(1));
+        let message = Message::Text("{}".to_string());
+
+        send_with_retry(&transport, message.clone(), 2, &mut backoff, true, None)
+            .await
+            .unwrap();
+
+        assert_eq!(*transport.sent.lock().unwrap(), vec![message]);
+        assert_eq!(transport.reconnects.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_gives_up() {
+        let mut backoff = ConstantBackoff::new(Duration::from_millis(1));
+        let message = Message::Text("{}".to_string());
+
+        // Out of retries.
+        let transport = FlakyTransport::new(3, write_error);
+        let err = send_with_retry(&transport, message.clone(), 2, &mut backoff, false, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Connection(_)));
+        assert!(transport.sent.lock().unwrap().is_empty());
+        assert_eq!(transport.reconnects.load(Ordering::SeqCst), 0);
+
+        // Not retryable: fails on the first attempt.
+        let transport = FlakyTransport::new(1, || Error::InvalidConfig("bad frame".into()));
+        let err = send_with_retry(&transport, message, 2, &mut backoff, true, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+        assert_eq!(transport.reconnects.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_after_writer_gone() {
+        let transport = FlakyTransport::new(1, || Error::ChannelError);
+        let mut backoff = ConstantBackoff::new(Duration::from_millis(1));
+        let message = Message::Text("{}".to_string());
+
+        send_with_retry(&transport, message.clone(), 2, &mut backoff, true, None)
+            .await
+            .unwrap();
+        assert_eq!(*transport.sent.lock().unwrap(), vec![message]);
+        assert_eq!(transport.reconnects.load(Ordering::SeqCst), 1);
+    }
+}